@@ -0,0 +1,201 @@
+use image::RgbaImage;
+
+// Lưới lấy mẫu thưa cho badge active/idle: 32x18 điểm là đủ phân biệt màn hình đứng yên với
+// đang thay đổi, nhưng bỏ sót thay đổi nhỏ (con trỏ, một dòng chữ) nằm giữa các điểm mẫu
+const GRID_COLS: u32 = 32;
+const GRID_ROWS: u32 = 18;
+// Lệch ít hơn mức này mỗi kênh thì coi như nhiễu nén, không tính là đổi
+const CHANNEL_THRESHOLD: u8 = 16;
+
+#[derive(Default)]
+pub struct ActivityTracker {
+    previous: Vec<[u8; 4]>,
+}
+
+impl ActivityTracker {
+    // Phần trăm điểm mẫu thay đổi so với frame trước. Frame đầu hoặc đổi kích thước = 100
+    pub fn update(&mut self, img: &RgbaImage) -> f64 {
+        let samples = sample(img);
+        let changed = if samples.len() == self.previous.len() {
+            samples
+                .iter()
+                .zip(&self.previous)
+                .filter(|(a, b)| differs(a, b))
+                .count()
+        } else {
+            samples.len()
+        };
+        let percent = changed as f64 * 100.0 / samples.len().max(1) as f64;
+        self.previous = samples;
+        percent
+    }
+}
+
+fn sample(img: &RgbaImage) -> Vec<[u8; 4]> {
+    let (width, height) = img.dimensions();
+    let cols = GRID_COLS.min(width);
+    let rows = GRID_ROWS.min(height);
+    let mut samples = Vec::with_capacity((cols * rows) as usize);
+    for row in 0..rows {
+        for col in 0..cols {
+            // Lấy điểm giữa mỗi ô lưới
+            let x = (col * 2 + 1) * width / (cols * 2);
+            let y = (row * 2 + 1) * height / (rows * 2);
+            samples.push(img.get_pixel(x, y).0);
+        }
+    }
+    samples
+}
+
+fn differs(a: &[u8; 4], b: &[u8; 4]) -> bool {
+    a.iter()
+        .zip(b)
+        .take(3)
+        .any(|(x, y)| x.abs_diff(*y) > CHANNEL_THRESHOLD)
+}
+
+// Khối vuông để băm toàn frame, 16 px: màn hình 1080p thành ~8000 khối
+const BLOCK: u32 = 16;
+
+// Băm mọi pixel theo khối để quyết định bỏ qua frame: khác dù chỉ một pixel cũng thấy, và
+// phần trăm khối đổi cho biết thay đổi lớn hay nhỏ. Chỉ giữ một u64 mỗi khối thay vì cả frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameDigest {
+    width: u32,
+    height: u32,
+    blocks: Vec<u64>,
+}
+
+impl FrameDigest {
+    pub fn new(img: &RgbaImage) -> Self {
+        let (width, height) = img.dimensions();
+        let cols = width.div_ceil(BLOCK) as usize;
+        let rows = height.div_ceil(BLOCK) as usize;
+        let mut blocks = vec![FNV_OFFSET; cols * rows];
+        let row_bytes = width as usize * 4;
+        let block_bytes = BLOCK as usize * 4;
+        for (y, row) in img.as_raw().chunks_exact(row_bytes.max(1)).enumerate() {
+            let band = &mut blocks[(y / BLOCK as usize) * cols..][..cols];
+            for (hash, chunk) in band.iter_mut().zip(row.chunks(block_bytes)) {
+                *hash = mix(*hash, chunk);
+            }
+        }
+        Self {
+            width,
+            height,
+            blocks,
+        }
+    }
+
+    // Phần trăm khối khác `previous`, khác kích thước = 100
+    pub fn changed_pct(&self, previous: &FrameDigest) -> f64 {
+        if (self.width, self.height) != (previous.width, previous.height) {
+            return 100.0;
+        }
+        let changed = self
+            .blocks
+            .iter()
+            .zip(&previous.blocks)
+            .filter(|(a, b)| a != b)
+            .count();
+        changed as f64 * 100.0 / self.blocks.len().max(1) as f64
+    }
+}
+
+// Mốc so sánh là frame đã broadcast gần nhất chứ không phải tick trước: khi đang bỏ qua frame
+// (đứng yên, capture_on_change), thay đổi chậm dồn lại qua nhiều tick cho tới khi vượt ngưỡng
+#[derive(Default)]
+pub struct ChangeBaseline {
+    broadcast: Option<FrameDigest>,
+    captured: Option<FrameDigest>,
+}
+
+impl ChangeBaseline {
+    // Phần trăm khối đổi so với frame đã broadcast, chưa broadcast gì thì 100
+    pub fn compare(&mut self, img: &RgbaImage) -> f64 {
+        let digest = FrameDigest::new(img);
+        let pct = self.broadcast.as_ref().map_or(100.0, |b| digest.changed_pct(b));
+        self.captured = Some(digest);
+        pct
+    }
+
+    // Frame vừa so sánh đã được gửi đi, thành mốc mới
+    pub fn mark_broadcast(&mut self) {
+        if let Some(digest) = self.captured.take() {
+            self.broadcast = Some(digest);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+// FNV-1a theo từng u64, đủ nhanh để băm mọi pixel mỗi tick
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+fn mix(mut hash: u64, bytes: &[u8]) -> u64 {
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    let mut words = bytes.chunks_exact(8);
+    for word in &mut words {
+        let word = u64::from_le_bytes(word.try_into().unwrap_or_default());
+        hash = (hash ^ word).wrapping_mul(PRIME);
+    }
+    for &byte in words.remainder() {
+        hash = (hash ^ u64::from(byte)).wrapping_mul(PRIME);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digest_sees_a_pixel_the_sparse_grid_misses() {
+        let before = RgbaImage::from_pixel(1920, 1080, image::Rgba([30, 30, 30, 255]));
+        let mut after = before.clone();
+        after.put_pixel(1, 1, image::Rgba([255, 255, 255, 255]));
+
+        let mut tracker = ActivityTracker::default();
+        tracker.update(&before);
+        assert_eq!(tracker.update(&after), 0.0);
+
+        let changed = FrameDigest::new(&after).changed_pct(&FrameDigest::new(&before));
+        assert!(changed > 0.0 && changed < 0.1);
+    }
+
+    #[test]
+    fn identical_frames_have_no_changed_blocks() {
+        let img = RgbaImage::from_fn(333, 77, |x, y| image::Rgba([x as u8, y as u8, 0, 255]));
+        assert_eq!(FrameDigest::new(&img).changed_pct(&FrameDigest::new(&img.clone())), 0.0);
+    }
+
+    #[test]
+    fn slow_change_accumulates_until_broadcast() {
+        let mut img = RgbaImage::from_pixel(160, 160, image::Rgba([0, 0, 0, 255]));
+        let mut baseline = ChangeBaseline::default();
+        assert_eq!(baseline.compare(&img), 100.0);
+        baseline.mark_broadcast();
+
+        // Mỗi tick đổi thêm một khối (1%), không broadcast thì phần trăm cứ tăng dần
+        let mut last = 0.0;
+        for block in 0..5 {
+            img.put_pixel(block * 16, 0, image::Rgba([255, 255, 255, 255]));
+            let pct = baseline.compare(&img);
+            assert!(pct > last);
+            last = pct;
+        }
+        assert_eq!(last, 5.0);
+
+        baseline.mark_broadcast();
+        assert_eq!(baseline.compare(&img), 0.0);
+    }
+
+    #[test]
+    fn resized_frame_counts_as_fully_changed() {
+        let small = RgbaImage::new(16, 16);
+        let large = RgbaImage::new(32, 16);
+        assert_eq!(FrameDigest::new(&large).changed_pct(&FrameDigest::new(&small)), 100.0);
+    }
+}
@@ -0,0 +1,61 @@
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpSocket};
+
+use crate::error::ServerError;
+
+// Bỏ trống = 0.0.0.0 như trước. Địa chỉ cụ thể phải là IP của một interface trên máy,
+// để host có thể chỉ mở server trên card LAN thay vì cả VPN/bridge.
+pub fn resolve_bind_ip(requested: Option<&str>) -> Result<IpAddr, ServerError> {
+    let Some(requested) = requested.map(str::trim).filter(|r| !r.is_empty()) else {
+        return Ok(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    };
+
+    let ip: IpAddr = requested
+        .parse()
+        .map_err(|_| ServerError::InvalidBindAddress(requested.to_string()))?;
+    if ip.is_unspecified() || ip.is_loopback() {
+        return Ok(ip);
+    }
+
+    let interfaces = local_ip_address::list_afinet_netifas().unwrap_or_default();
+    if interfaces.iter().any(|(_, addr)| *addr == ip) {
+        Ok(ip)
+    } else {
+        Err(ServerError::InvalidBindAddress(requested.to_string()))
+    }
+}
+
+// Cổng vừa đóng còn TIME_WAIT thì thử lại vài lần trước khi báo AddressInUse
+const BIND_ATTEMPTS: u32 = 4;
+const BIND_BACKOFF: Duration = Duration::from_millis(100);
+
+pub async fn bind_listener(addr: SocketAddr) -> Result<TcpListener, ServerError> {
+    let mut delay = BIND_BACKOFF;
+    for attempt in 1..=BIND_ATTEMPTS {
+        match try_bind(addr) {
+            Ok(listener) => return Ok(listener),
+            Err(e) if e.kind() == ErrorKind::AddrInUse && attempt < BIND_ATTEMPTS => {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            Err(e) if e.kind() == ErrorKind::AddrInUse => break,
+            Err(e) => return Err(ServerError::Bind(e)),
+        }
+    }
+    Err(ServerError::AddressInUse(addr.port()))
+}
+
+fn try_bind(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    // Trên Windows SO_REUSEADDR cho phép process khác chiếm cùng cổng, nên chỉ bật ở Unix
+    #[cfg(unix)]
+    socket.set_reuseaddr(true)?;
+    socket.bind(addr)?;
+    socket.listen(1024)
+}
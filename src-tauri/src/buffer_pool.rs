@@ -0,0 +1,104 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+// Buffer dùng lại được: xoá nội dung nhưng giữ capacity
+pub trait Reusable: Default {
+    fn reset(&mut self);
+}
+
+impl Reusable for Vec<u8> {
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+impl Reusable for String {
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+// Buffer của Frame (JPEG, base64) quay về pool khi Arc<Frame> cuối cùng bị drop, tick sau
+// encode thẳng vào đó thay vì cấp phát lại. Chỉ giữ tối đa max_free buffer rảnh
+pub struct BufferPool<T> {
+    free: Mutex<Vec<T>>,
+    max_free: usize,
+}
+
+impl<T: Reusable> BufferPool<T> {
+    pub fn new(max_free: usize) -> Arc<Self> {
+        Arc::new(Self {
+            free: Mutex::new(Vec::with_capacity(max_free)),
+            max_free,
+        })
+    }
+
+    // Buffer rỗng, lấy từ pool nếu còn
+    pub fn take(self: &Arc<Self>) -> Pooled<T> {
+        let value = self.free.lock().ok().and_then(|mut free| free.pop()).unwrap_or_default();
+        Pooled {
+            value,
+            pool: Arc::clone(self),
+        }
+    }
+
+    #[cfg(test)]
+    fn free_count(&self) -> usize {
+        self.free.lock().map(|free| free.len()).unwrap_or(0)
+    }
+}
+
+pub struct Pooled<T: Reusable> {
+    value: T,
+    pool: Arc<BufferPool<T>>,
+}
+
+impl<T: Reusable> Deref for Pooled<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Reusable> DerefMut for Pooled<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: Reusable> Drop for Pooled<T> {
+    fn drop(&mut self) {
+        let mut value = std::mem::take(&mut self.value);
+        value.reset();
+        if let Ok(mut free) = self.pool.free.lock() {
+            if free.len() < self.pool.max_free {
+                free.push(value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dropped_buffer_is_reused_with_its_capacity() {
+        let pool = BufferPool::<Vec<u8>>::new(2);
+        let mut buf = pool.take();
+        buf.extend_from_slice(&[1; 4096]);
+        drop(buf);
+        let buf = pool.take();
+        assert!(buf.is_empty());
+        assert!(buf.capacity() >= 4096);
+    }
+
+    #[test]
+    fn pool_keeps_at_most_max_free_buffers() {
+        let pool = BufferPool::<String>::new(2);
+        let taken: Vec<_> = (0..5).map(|_| pool.take()).collect();
+        drop(taken);
+        assert_eq!(pool.free_count(), 2);
+    }
+}
@@ -0,0 +1,345 @@
+use image::RgbaImage;
+use serde::Deserialize;
+use std::cell::Cell;
+use std::process::Command;
+use xcap::{Monitor, Window};
+
+use crate::error::ServerError;
+
+// Nguồn ảnh cho luồng capture chung; nguồn mới (vùng màn hình, camera ảo) chỉ cần impl trait này
+pub trait CaptureSource {
+    fn capture(&self) -> Result<RgbaImage, ServerError>;
+    // Kích thước gốc theo toạ độ desktop, để tính tỉ lệ frame gửi đi theo từng chiều
+    fn width(&self) -> u32;
+    fn height(&self) -> u32;
+    // Tên backend đang chụp, hiện trong trạng thái server
+    fn backend(&self) -> &'static str;
+    // Số pixel vật lý trên một pixel logic (2.0 trên Retina), nguồn không biết thì 1.0
+    fn scale_factor(&self) -> f32 {
+        1.0
+    }
+    // Góc trên trái trên desktop để đổi toạ độ input, nguồn không nằm trên desktop thì (0, 0)
+    fn origin(&self) -> (i32, i32) {
+        (0, 0)
+    }
+}
+
+pub struct MonitorSource(Monitor);
+
+impl CaptureSource for MonitorSource {
+    fn capture(&self) -> Result<RgbaImage, ServerError> {
+        Ok(self.0.capture_image()?)
+    }
+
+    fn width(&self) -> u32 {
+        self.0.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.0.height()
+    }
+
+    fn backend(&self) -> &'static str {
+        "xcap"
+    }
+
+    fn scale_factor(&self) -> f32 {
+        self.0.scale_factor()
+    }
+
+    fn origin(&self) -> (i32, i32) {
+        (self.0.x(), self.0.y())
+    }
+}
+
+pub struct WindowSource(Window);
+
+impl CaptureSource for WindowSource {
+    fn capture(&self) -> Result<RgbaImage, ServerError> {
+        Ok(self.0.capture_image()?)
+    }
+
+    fn width(&self) -> u32 {
+        self.0.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.0.height()
+    }
+
+    fn backend(&self) -> &'static str {
+        "xcap"
+    }
+
+    fn scale_factor(&self) -> f32 {
+        self.0.current_monitor().scale_factor()
+    }
+
+    fn origin(&self) -> (i32, i32) {
+        (self.0.x(), self.0.y())
+    }
+}
+
+// Ảnh ghép lớn hơn mức này thì từ chối thay vì cấp phát hàng trăm MB mỗi frame
+const MAX_REGION_DIMENSION: u32 = 16384;
+
+// Hình chữ nhật theo toạ độ desktop ảo (như x/y/width/height của list_monitors), có thể
+// nằm vắt qua nhiều màn hình
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    // Phần giao theo toạ độ desktop, None = không chạm nhau
+    fn intersect(&self, monitor: &Monitor) -> Option<Region> {
+        let left = self.x.max(monitor.x());
+        let top = self.y.max(monitor.y());
+        let right = (self.x + self.width as i32).min(monitor.x() + monitor.width() as i32);
+        let bottom = (self.y + self.height as i32).min(monitor.y() + monitor.height() as i32);
+        (right > left && bottom > top).then(|| Region {
+            x: left,
+            y: top,
+            width: (right - left) as u32,
+            height: (bottom - top) as u32,
+        })
+    }
+}
+
+// Chụp mọi màn hình chạm vào vùng rồi chỉ ghép phần giao. Mỗi màn hình có tỉ lệ pixel ảnh /
+// toạ độ desktop riêng (HiDPI), ảnh ghép lấy tỉ lệ lớn nhất để phần trên màn hình nét nhất
+// không bị mờ, phần của màn hình thưa hơn được phóng lên. Chỗ vùng không có màn hình để đen
+pub struct RegionSource(Region);
+
+impl CaptureSource for RegionSource {
+    fn capture(&self) -> Result<RgbaImage, ServerError> {
+        let region = self.0;
+        let mut parts = Vec::new();
+        for monitor in Monitor::all().map_err(|_| ServerError::NoMonitor)? {
+            if let Some(part) = region.intersect(&monitor) {
+                let img = monitor.capture_image()?;
+                let ratio = img.width() as f64 / monitor.width().max(1) as f64;
+                parts.push((monitor, part, img, ratio));
+            }
+        }
+        // Màn hình chứa vùng bị rút: chờ cắm lại như khi chia sẻ một màn hình
+        if parts.is_empty() {
+            return Err(ServerError::NoMonitor);
+        }
+
+        let ratio = parts.iter().map(|p| p.3).fold(1.0, f64::max);
+        let to_px = |v: i64| (v as f64 * ratio).round() as i64;
+        let width = to_px(region.width as i64).max(1) as u32;
+        let height = to_px(region.height as i64).max(1) as u32;
+        if width > MAX_REGION_DIMENSION || height > MAX_REGION_DIMENSION {
+            return Err(ServerError::Capture(format!(
+                "Region too large to capture: {}x{}",
+                width, height
+            )));
+        }
+
+        let mut canvas = RgbaImage::new(width, height);
+        for (monitor, part, img, monitor_ratio) in parts {
+            // Toạ độ desktop -> pixel trong ảnh của màn hình, chặn trong ảnh vì làm tròn
+            let src = |v: i32, origin: i32, limit: u32| {
+                (((v - origin) as f64 * monitor_ratio).round() as u32).min(limit)
+            };
+            let sx = src(part.x, monitor.x(), img.width());
+            let sy = src(part.y, monitor.y(), img.height());
+            let sw = src(part.x + part.width as i32, monitor.x(), img.width()) - sx;
+            let sh = src(part.y + part.height as i32, monitor.y(), img.height()) - sy;
+            if sw == 0 || sh == 0 {
+                continue;
+            }
+            let piece = image::imageops::crop_imm(&img, sx, sy, sw, sh).to_image();
+            let dw = to_px(part.width as i64).max(1) as u32;
+            let dh = to_px(part.height as i64).max(1) as u32;
+            let piece = if (sw, sh) == (dw, dh) {
+                piece
+            } else {
+                image::imageops::resize(&piece, dw, dh, image::imageops::FilterType::Triangle)
+            };
+            let dx = to_px((part.x - region.x) as i64);
+            let dy = to_px((part.y - region.y) as i64);
+            image::imageops::replace(&mut canvas, &piece, dx, dy);
+        }
+        Ok(canvas)
+    }
+
+    fn width(&self) -> u32 {
+        self.0.width
+    }
+
+    fn height(&self) -> u32 {
+        self.0.height
+    }
+
+    fn backend(&self) -> &'static str {
+        "xcap"
+    }
+
+    // Theo màn hình dày pixel nhất mà vùng chạm vào, khớp với tỉ lệ của ảnh ghép
+    fn scale_factor(&self) -> f32 {
+        Monitor::all()
+            .unwrap_or_default()
+            .iter()
+            .filter(|m| self.0.intersect(m).is_some())
+            .map(|m| m.scale_factor())
+            .fold(1.0, f32::max)
+    }
+
+    fn origin(&self) -> (i32, i32) {
+        (self.0.x, self.0.y)
+    }
+}
+
+// Lệnh chụp màn hình có sẵn của hệ điều hành, in PNG ra stdout, thử lần lượt
+#[cfg(target_os = "linux")]
+const SCREENSHOT_COMMANDS: &[&[&str]] = &[&["grim", "-"], &["import", "-window", "root", "png:-"]];
+#[cfg(target_os = "macos")]
+const SCREENSHOT_COMMANDS: &[&[&str]] = &[&["screencapture", "-x", "-t", "png", "/dev/stdout"]];
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+const SCREENSHOT_COMMANDS: &[&[&str]] = &[];
+
+// Chụp cả desktop bằng lệnh ngoài khi xcap không dùng được (vd Wayland thiếu quyền).
+// Chậm hơn xcap nhiều và không chọn được màn hình hay cửa sổ.
+#[derive(Default)]
+pub struct CommandSource {
+    width: Cell<u32>,
+    height: Cell<u32>,
+}
+
+impl CaptureSource for CommandSource {
+    fn capture(&self) -> Result<RgbaImage, ServerError> {
+        for args in SCREENSHOT_COMMANDS {
+            let Ok(output) = Command::new(args[0]).args(&args[1..]).output() else {
+                continue;
+            };
+            if !output.status.success() {
+                continue;
+            }
+            if let Ok(img) = image::load_from_memory(&output.stdout) {
+                let img = img.to_rgba8();
+                self.width.set(img.width());
+                self.height.set(img.height());
+                return Ok(img);
+            }
+        }
+        Err(ServerError::NoMonitor)
+    }
+
+    // Ảnh chụp bằng lệnh đã theo pixel thật, không biết tỉ lệ HiDPI
+    fn width(&self) -> u32 {
+        self.width.get()
+    }
+
+    fn height(&self) -> u32 {
+        self.height.get()
+    }
+
+    fn backend(&self) -> &'static str {
+        "command"
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureBackend {
+    // xcap, không liệt kê được màn hình nào thì chuyển sang lệnh chụp
+    #[default]
+    Auto,
+    Xcap,
+    Command,
+}
+
+// Chọn trong start_screen_server: {"kind": "monitor", "id": 1}, {"kind": "window", "id": 42}
+// hoặc {"kind": "region", "x": 1800, "y": 200, "width": 800, "height": 600}
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum SourceKind {
+    // Id ổn định từ list_monitors, bỏ trống = màn hình chính
+    Monitor { id: Option<u32> },
+    // Id từ list_windows
+    Window { id: u32 },
+    // Vùng theo toạ độ desktop ảo, được vắt qua nhiều màn hình
+    Region(Region),
+}
+
+impl Default for SourceKind {
+    fn default() -> Self {
+        SourceKind::Monitor { id: None }
+    }
+}
+
+impl SourceKind {
+    // Tra lại mỗi lần capture vì màn hình có thể bị rút, cửa sổ có thể bị đóng
+    pub fn open(self, backend: CaptureBackend) -> Result<Box<dyn CaptureSource>, ServerError> {
+        match (self, backend) {
+            (SourceKind::Monitor { .. }, CaptureBackend::Command) => {
+                Ok(Box::new(CommandSource::default()))
+            }
+            // Chỉ chuyển khi xcap không thấy màn hình nào; màn hình đã chọn bị rút thì vẫn
+            // báo NoMonitor để chờ cắm lại
+            (SourceKind::Monitor { id }, CaptureBackend::Auto) => match Monitor::all() {
+                Ok(monitors) if !monitors.is_empty() => {
+                    Ok(Box::new(MonitorSource(pick_monitor(monitors, id)?)))
+                }
+                _ => Ok(Box::new(CommandSource::default())),
+            },
+            (SourceKind::Monitor { id }, CaptureBackend::Xcap) => {
+                Ok(Box::new(MonitorSource(monitor_by_id(id)?)))
+            }
+            (SourceKind::Window { .. }, CaptureBackend::Command) => Err(ServerError::Capture(
+                "Window capture is not supported by the command backend".to_string(),
+            )),
+            (SourceKind::Window { id }, _) => {
+                let window = Window::all()?
+                    .into_iter()
+                    .find(|w| w.id() == id)
+                    .ok_or(ServerError::WindowClosed)?;
+                Ok(Box::new(WindowSource(window)))
+            }
+            (SourceKind::Region(_), CaptureBackend::Command) => Err(ServerError::Capture(
+                "Region capture is not supported by the command backend".to_string(),
+            )),
+            (SourceKind::Region(region), _) => {
+                if region.width == 0 || region.height == 0 {
+                    return Err(ServerError::Capture("Region must not be empty".to_string()));
+                }
+                Ok(Box::new(RegionSource(region)))
+            }
+        }
+    }
+
+    // Kiểm tra trước khi mở server, để không có server nào chạy mà không bao giờ ra frame.
+    // Lệnh chụp thì phải chạy thử mới biết có dùng được không, vùng thì phải chạm màn hình nào đó
+    pub fn check(self, backend: CaptureBackend) -> Result<(), ServerError> {
+        let source = self.open(backend)?;
+        if source.backend() == "command" || matches!(self, SourceKind::Region(_)) {
+            source.capture()?;
+        }
+        Ok(())
+    }
+}
+
+// Thứ tự trong Monitor::all() thay đổi khi cắm/rút màn hình, nên tra theo id.
+// Không có id thì dùng màn hình chính. Không liệt kê được màn hình (headless, vài phiên RDP)
+// cũng coi như không có màn hình.
+pub fn monitor_by_id(id: Option<u32>) -> Result<Monitor, ServerError> {
+    let monitors = Monitor::all().map_err(|_| ServerError::NoMonitor)?;
+    pick_monitor(monitors, id)
+}
+
+fn pick_monitor(monitors: Vec<Monitor>, id: Option<u32>) -> Result<Monitor, ServerError> {
+    let monitor = match id {
+        Some(id) => monitors.into_iter().find(|m| m.id() == id),
+        None => {
+            let primary = monitors.iter().position(|m| m.is_primary()).unwrap_or(0);
+            monitors.into_iter().nth(primary)
+        }
+    };
+    monitor.ok_or(ServerError::NoMonitor)
+}
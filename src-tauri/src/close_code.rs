@@ -0,0 +1,64 @@
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+
+// Mã đóng WebSocket riêng của app (dải 4000-4999), dùng chung cho screen server và signaling.
+// Reason trong Close frame có dạng "<tên>" hoặc "<tên>;retry-after=<giây>":
+//   4001 busy         - server đang bận (accept/message quá nhanh), thử lại sau retry-after
+//   4002 unauthorized - không được phép kết nối, đừng tự kết nối lại
+//   4003 capacity     - đã đủ client và hàng đợi cũng đầy, thử lại sau retry-after
+//   4004 room-closed  - phòng bị đóng từ phía server (close_room), đừng tự kết nối lại
+//   4005 dropped      - host ngắt riêng kết nối này (drop_connection), đừng tự kết nối lại
+// Client nên chờ ít nhất retry-after giây và tăng dần thời gian chờ nếu vẫn bị từ chối.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseReason {
+    Busy,
+    // Chưa có xác thực, giữ sẵn mã để client xử lý thống nhất
+    #[allow(dead_code)]
+    Unauthorized,
+    Capacity,
+    RoomClosed,
+    Dropped,
+}
+
+impl CloseReason {
+    pub fn code(self) -> u16 {
+        match self {
+            CloseReason::Busy => 4001,
+            CloseReason::Unauthorized => 4002,
+            CloseReason::Capacity => 4003,
+            CloseReason::RoomClosed => 4004,
+            CloseReason::Dropped => 4005,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CloseReason::Busy => "busy",
+            CloseReason::Unauthorized => "unauthorized",
+            CloseReason::Capacity => "capacity",
+            CloseReason::RoomClosed => "room-closed",
+            CloseReason::Dropped => "dropped",
+        }
+    }
+
+    pub fn retry_after_secs(self) -> Option<u64> {
+        match self {
+            CloseReason::Busy => Some(2),
+            CloseReason::Unauthorized => None,
+            CloseReason::Capacity => Some(5),
+            CloseReason::RoomClosed => None,
+            CloseReason::Dropped => None,
+        }
+    }
+
+    pub fn frame(self) -> CloseFrame<'static> {
+        let reason = match self.retry_after_secs() {
+            Some(secs) => format!("{};retry-after={}", self.name(), secs),
+            None => self.name().to_string(),
+        };
+        CloseFrame {
+            code: CloseCode::from(self.code()),
+            reason: reason.into(),
+        }
+    }
+}
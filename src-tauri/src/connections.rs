@@ -0,0 +1,127 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+#[derive(Serialize, Clone)]
+pub struct ConnectionInfo {
+    id: u64,
+    // "screen" hoặc "signaling"
+    server: &'static str,
+    remote_addr: Option<String>,
+    // screen: "viewer" hoặc "queued" (đang chờ slot); signaling: "host"/"viewer",
+    // None khi chưa gửi message host/viewer
+    role: Option<&'static str>,
+    // Id của screen server nhận kết nối
+    server_id: Option<String>,
+    // Phòng signaling đã vào
+    room: Option<String>,
+    // Unix millis
+    connected_at: u64,
+}
+
+struct Entry {
+    info: ConnectionInfo,
+    drop_signal: Arc<Notify>,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+lazy_static::lazy_static! {
+    // Mọi kết nối WebSocket đang sống của cả screen server và signaling server
+    static ref CONNECTIONS: Mutex<HashMap<u64, Entry>> = Mutex::new(HashMap::new());
+}
+
+// Giữ trong task của kết nối, task kết thúc thì tự xoá khỏi registry
+pub struct Registration {
+    id: u64,
+    remote_addr: Option<SocketAddr>,
+    drop_signal: Arc<Notify>,
+}
+
+impl Registration {
+    pub fn new(server: &'static str, remote_addr: Option<SocketAddr>) -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+        let drop_signal = Arc::new(Notify::new());
+        let info = ConnectionInfo {
+            id,
+            server,
+            remote_addr: remote_addr.map(|a| a.to_string()),
+            role: None,
+            server_id: None,
+            room: None,
+            connected_at: crate::now_millis(),
+        };
+        let entry = Entry {
+            info,
+            drop_signal: Arc::clone(&drop_signal),
+        };
+        lock().insert(id, entry);
+        Self {
+            id,
+            remote_addr,
+            drop_signal,
+        }
+    }
+
+    pub fn remote_ip(&self) -> Option<IpAddr> {
+        self.remote_addr.map(|addr| addr.ip())
+    }
+
+    pub fn set_role(&self, role: &'static str) {
+        self.update(|info| info.role = Some(role));
+    }
+
+    pub fn set_server_id(&self, server_id: &str) {
+        self.update(|info| info.server_id = Some(server_id.to_string()));
+    }
+
+    pub fn set_room(&self, room: &str) {
+        self.update(|info| info.room = Some(room.to_string()));
+    }
+
+    // Báo khi drop_connection chọn kết nối này. Permit được giữ nên không lỡ tín hiệu
+    // gửi trước lúc bắt đầu chờ
+    pub fn drop_signal(&self) -> Arc<Notify> {
+        Arc::clone(&self.drop_signal)
+    }
+
+    fn update(&self, f: impl FnOnce(&mut ConnectionInfo)) {
+        if let Some(entry) = lock().get_mut(&self.id) {
+            f(&mut entry.info);
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        lock().remove(&self.id);
+    }
+}
+
+fn lock() -> std::sync::MutexGuard<'static, HashMap<u64, Entry>> {
+    CONNECTIONS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Sắp theo thứ tự kết nối
+#[tauri::command]
+pub fn list_connections() -> Vec<ConnectionInfo> {
+    let mut connections: Vec<ConnectionInfo> =
+        lock().values().map(|entry| entry.info.clone()).collect();
+    connections.sort_by_key(|info| info.id);
+    connections
+}
+
+// Kết nối đóng với mã 4005 (dropped). false = không có kết nối nào với id này
+#[tauri::command]
+pub fn drop_connection(id: u64) -> bool {
+    match lock().get(&id) {
+        Some(entry) => {
+            entry.drop_signal.notify_one();
+            true
+        }
+        None => false,
+    }
+}
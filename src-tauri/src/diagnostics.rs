@@ -0,0 +1,96 @@
+use serde::Serialize;
+use tokio::net::TcpListener;
+use tokio::process::Command;
+
+use crate::screen_share;
+
+#[derive(Serialize, Clone)]
+pub struct CheckResult {
+    passed: bool,
+    message: String,
+}
+
+impl CheckResult {
+    fn pass(message: impl Into<String>) -> Self {
+        Self {
+            passed: true,
+            message: message.into(),
+        }
+    }
+
+    fn fail(message: impl Into<String>) -> Self {
+        Self {
+            passed: false,
+            message: message.into(),
+        }
+    }
+}
+
+// Kiểm tra nhanh môi trường khi hỗ trợ người dùng
+#[derive(Serialize, Clone)]
+pub struct Diagnostics {
+    local_ip: CheckResult,
+    ping: CheckResult,
+    arp: CheckResult,
+    capture: CheckResult,
+    bind: CheckResult,
+}
+
+fn check_local_ip() -> CheckResult {
+    match local_ip_address::local_ip() {
+        Ok(ip) => CheckResult::pass(ip.to_string()),
+        Err(e) => CheckResult::fail(format!("Cannot detect local IP: {}", e)),
+    }
+}
+
+// Ping loopback: lỗi spawn = không có lệnh ping, exit code lỗi = thường do thiếu quyền ICMP
+async fn check_ping() -> CheckResult {
+    let count_flag = if cfg!(target_os = "windows") { "-n" } else { "-c" };
+    match Command::new("ping").args([count_flag, "1", "127.0.0.1"]).output().await {
+        Ok(o) if o.status.success() => CheckResult::pass("ping works"),
+        Ok(o) => CheckResult::fail(format!(
+            "ping failed (permission?): {}",
+            String::from_utf8_lossy(&o.stderr).trim()
+        )),
+        Err(e) => CheckResult::fail(format!("ping not available: {}", e)),
+    }
+}
+
+async fn check_arp() -> CheckResult {
+    match Command::new("arp").arg("-a").output().await {
+        Ok(o) if o.status.success() => {
+            let entries = String::from_utf8_lossy(&o.stdout).lines().count();
+            CheckResult::pass(format!("arp available ({} lines)", entries))
+        }
+        Ok(o) => CheckResult::fail(format!("arp exited with {}", o.status)),
+        Err(e) => CheckResult::fail(format!("arp not available: {}", e)),
+    }
+}
+
+async fn check_capture() -> CheckResult {
+    match tokio::task::spawn_blocking(screen_share::test_capture).await {
+        Ok(Ok(message)) => CheckResult::pass(message),
+        Ok(Err(e)) => CheckResult::fail(e.to_string()),
+        Err(e) => CheckResult::fail(e.to_string()),
+    }
+}
+
+async fn check_bind() -> CheckResult {
+    match TcpListener::bind("0.0.0.0:0").await.and_then(|l| l.local_addr()) {
+        Ok(addr) => CheckResult::pass(format!("bound {}", addr)),
+        Err(e) => CheckResult::fail(format!("Cannot bind a port: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn run_diagnostics() -> Diagnostics {
+    let (ping, arp, capture, bind) =
+        tokio::join!(check_ping(), check_arp(), check_capture(), check_bind());
+    Diagnostics {
+        local_ip: check_local_ip(),
+        ping,
+        arp,
+        capture,
+        bind,
+    }
+}
@@ -0,0 +1,169 @@
+use serde::Serialize;
+use std::fmt;
+use std::io::ErrorKind;
+
+// Payload lỗi chung của mọi command: frontend phân nhánh theo code, retriable để hiện nút thử lại
+#[derive(Debug, Clone, Serialize)]
+pub struct AppError {
+    pub code: String,
+    pub message: String,
+    pub retriable: bool,
+}
+
+impl AppError {
+    pub fn new(code: &str, message: impl Into<String>, retriable: bool) -> Self {
+        Self {
+            code: code.to_string(),
+            message: message.into(),
+            retriable,
+        }
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        Self::new("InvalidInput", message, false)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new("NotFound", message, false)
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<ServerError> for AppError {
+    fn from(e: ServerError) -> Self {
+        Self::new(e.kind(), e.to_string(), e.retriable())
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        let retriable = matches!(
+            e.kind(),
+            ErrorKind::TimedOut
+                | ErrorKind::Interrupted
+                | ErrorKind::WouldBlock
+                | ErrorKind::ConnectionRefused
+                | ErrorKind::ConnectionReset
+                | ErrorKind::AddrInUse
+        );
+        Self::new("Io", e.to_string(), retriable)
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::new("Serialization", e.to_string(), false)
+    }
+}
+
+impl From<local_ip_address::Error> for AppError {
+    fn from(e: local_ip_address::Error) -> Self {
+        Self::new("Network", e.to_string(), true)
+    }
+}
+
+impl From<xcap::XCapError> for AppError {
+    fn from(e: xcap::XCapError) -> Self {
+        ServerError::from(e).into()
+    }
+}
+
+// Lỗi chung của screen server và signaling server
+#[derive(Debug)]
+pub enum ServerError {
+    AlreadyRunning,
+    Bind(std::io::Error),
+    AddressInUse(u16),
+    InvalidBindAddress(String),
+    Tls(String),
+    NoMonitor,
+    WindowClosed,
+    Capture(String),
+    RoomNotFound,
+}
+
+impl ServerError {
+    // Lỗi tạm thời (port bận, capture hỏng một lần) thì frontend có thể thử lại
+    fn retriable(&self) -> bool {
+        matches!(
+            self,
+            ServerError::Bind(_)
+                | ServerError::AddressInUse(_)
+                | ServerError::NoMonitor
+                | ServerError::Capture(_)
+        )
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            ServerError::AlreadyRunning => "AlreadyRunning",
+            ServerError::Bind(_) => "Bind",
+            ServerError::AddressInUse(_) => "AddressInUse",
+            ServerError::InvalidBindAddress(_) => "InvalidBindAddress",
+            ServerError::Tls(_) => "Tls",
+            ServerError::NoMonitor => "NoMonitor",
+            ServerError::WindowClosed => "WindowClosed",
+            ServerError::Capture(_) => "Capture",
+            ServerError::RoomNotFound => "RoomNotFound",
+        }
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::AlreadyRunning => write!(f, "Server already running"),
+            ServerError::Bind(e) => write!(f, "Failed to bind port: {}", e),
+            ServerError::AddressInUse(port) => write!(f, "Port {} is already in use", port),
+            ServerError::InvalidBindAddress(ip) => {
+                write!(f, "{} is not an address of this machine", ip)
+            }
+            ServerError::Tls(e) => write!(f, "TLS setup failed: {}", e),
+            ServerError::NoMonitor => write!(f, "No monitor found"),
+            ServerError::WindowClosed => write!(f, "Shared window was closed"),
+            ServerError::Capture(e) => write!(f, "Capture failed: {}", e),
+            ServerError::RoomNotFound => write!(f, "Room not found"),
+        }
+    }
+}
+
+impl std::error::Error for ServerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ServerError::Bind(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+// Lỗi IO của server chỉ đến từ bước bind listener
+impl From<std::io::Error> for ServerError {
+    fn from(e: std::io::Error) -> Self {
+        ServerError::Bind(e)
+    }
+}
+
+impl From<xcap::XCapError> for ServerError {
+    fn from(e: xcap::XCapError) -> Self {
+        ServerError::Capture(e.to_string())
+    }
+}
+
+impl From<image::ImageError> for ServerError {
+    fn from(e: image::ImageError) -> Self {
+        ServerError::Capture(e.to_string())
+    }
+}
+
+impl From<jpeg_encoder::EncodingError> for ServerError {
+    fn from(e: jpeg_encoder::EncodingError) -> Self {
+        ServerError::Capture(e.to_string())
+    }
+}
@@ -0,0 +1,63 @@
+use serde::Deserialize;
+
+// Con trỏ lệch khỏi tâm vùng ít hơn chừng này pixel thì giữ nguyên vùng, tránh rung
+const DEAD_ZONE: f64 = 24.0;
+// Mỗi frame tâm vùng đi được phần này quãng đường tới con trỏ
+const SMOOTHING: f64 = 0.35;
+
+// Chỉ chia sẻ một vùng width x height quanh con trỏ, vd cho video hướng dẫn
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FollowCursor {
+    pub width: u32,
+    pub height: u32,
+}
+
+// Giữ tâm vùng giữa các frame
+#[derive(Default)]
+pub struct CursorFollower {
+    center: Option<(f64, f64)>,
+}
+
+impl CursorFollower {
+    pub fn reset(&mut self) {
+        self.center = None;
+    }
+
+    // cursor theo pixel của ảnh vừa chụp, None = không đọc được thì giữ vùng cũ.
+    // Trả về (x, y, width, height) của vùng crop, luôn nằm trọn trong ảnh
+    pub fn region(
+        &mut self,
+        cursor: Option<(f64, f64)>,
+        size: FollowCursor,
+        image: (u32, u32),
+    ) -> (u32, u32, u32, u32) {
+        let width = size.width.clamp(1, image.0.max(1));
+        let height = size.height.clamp(1, image.1.max(1));
+        let image_center = (image.0 as f64 / 2.0, image.1 as f64 / 2.0);
+        let target = cursor.or(self.center).unwrap_or(image_center);
+
+        let center = match self.center {
+            Some(current) => {
+                let (dx, dy) = (target.0 - current.0, target.1 - current.1);
+                if dx.hypot(dy) < DEAD_ZONE {
+                    current
+                } else {
+                    (current.0 + dx * SMOOTHING, current.1 + dy * SMOOTHING)
+                }
+            }
+            None => target,
+        };
+        self.center = Some(center);
+
+        let clamp = |center: f64, len: u32, bound: u32| {
+            let max = bound.saturating_sub(len) as f64;
+            (center - len as f64 / 2.0).round().clamp(0.0, max) as u32
+        };
+        (
+            clamp(center.0, width, image.0),
+            clamp(center.1, height, image.1),
+            width,
+            height,
+        )
+    }
+}
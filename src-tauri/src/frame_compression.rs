@@ -0,0 +1,148 @@
+// Tên codec trong handshake. Message đã nén là một frame zstd đầy đủ của cả message gốc
+// ("TILE"...). Frame zstd bắt đầu bằng 28 B5 2F FD, JPEG bằng FF D8, nên client phân biệt được
+// mà không cần thêm header
+pub const ZSTD: &str = "zstd";
+// Khoảng mức nén nhận từ cấu hình, trên 19 là chế độ ultra tốn rất nhiều bộ nhớ
+pub const MIN_LEVEL: u32 = 1;
+pub const MAX_LEVEL: u32 = 19;
+// JPEG trong message gần như không nén thêm được, phần lợi nằm ở header JSON và các ô giống
+// nhau. Đo trên message tiles (default_level_is_close_to_the_best_ratio): mức cao hơn bớt thêm
+// chưa tới 1% mà tốn CPU gấp nhiều lần
+pub const DEFAULT_LEVEL: u32 = 1;
+// Bản nén phải nhỏ hơn ít nhất 10% mới gửi, không thì client giải nén phí công
+const MIN_SAVING_PCT: usize = 10;
+
+// Codec đầu tiên server hỗ trợ trong danh sách client gửi
+pub fn negotiate(offered: &[String]) -> Option<&'static str> {
+    offered.iter().any(|c| c == ZSTD).then_some(ZSTD)
+}
+
+// None khi nén không lợi, gửi message gốc
+pub fn compress(message: &[u8], level: u32) -> Option<Vec<u8>> {
+    let compressed = zstd_compress(message, level)?;
+    (compressed.len() * 100 <= message.len() * (100 - MIN_SAVING_PCT)).then_some(compressed)
+}
+
+// Kích thước sau nén / kích thước gốc, tính cả khi compress sẽ bỏ bản nén
+pub fn ratio(message: &[u8], level: u32) -> Option<f64> {
+    let compressed = zstd_compress(message, level).filter(|_| !message.is_empty())?;
+    Some(compressed.len() as f64 / message.len() as f64)
+}
+
+fn zstd_compress(message: &[u8], level: u32) -> Option<Vec<u8>> {
+    zstd::bulk::compress(message, level.clamp(MIN_LEVEL, MAX_LEVEL) as i32).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+    use std::time::{Duration, Instant};
+    use tokio_tungstenite::tungstenite::Message;
+
+    use crate::screen_share::{encode_jpeg, ChromaSubsampling};
+    use crate::tiles::{TileGrid, TileTracker};
+
+    // Màn hình làm việc giả lập: thanh tiêu đề và sidebar màu phẳng, vùng soạn thảo nền trắng
+    // với các dòng "chữ" giả ngẫu nhiên, một vùng ảnh nhiều màu ở góc
+    fn desktop(width: u32, height: u32, seed: u32) -> RgbaImage {
+        let mut state = seed.wrapping_mul(2654435761).max(1);
+        let mut noise = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+        let mut img = RgbaImage::from_pixel(width, height, Rgba([255, 255, 255, 255]));
+        for (x, y, px) in img.enumerate_pixels_mut() {
+            if y < 40 {
+                *px = Rgba([45, 45, 48, 255]);
+            } else if x < 240 {
+                *px = Rgba([240, 240, 243, 255]);
+            } else if x >= width * 3 / 4 && y >= height / 2 {
+                let n = (noise() % 24) as u8;
+                *px = Rgba([(x % 256) as u8 ^ n, (y % 256) as u8, 128 + n, 255]);
+            } else {
+                // Dòng chữ cao 12 px cách 20 px, "ký tự" rộng 7 px
+                let in_line = (y - 40) % 20 < 12;
+                let glyph = (x / 7 + y / 20 * 31 + seed) % 11;
+                if in_line && glyph != 0 && noise() % 3 == 0 {
+                    *px = Rgba([30, 30, 30, 255]);
+                }
+            }
+        }
+        img
+    }
+
+    // Keyframe đủ lưới và message delta khi chỉ vùng soạn thảo đổi, như khi gõ chữ
+    fn tile_messages() -> Vec<Vec<u8>> {
+        let (width, height) = (1280, 720);
+        let chroma = ChromaSubsampling::default();
+        let mut tracker = TileTracker::default();
+        let mut previous: Option<TileGrid> = None;
+        let mut messages = Vec::new();
+        for seed in 0..4 {
+            let mut img = desktop(width, height, 0);
+            if seed > 0 {
+                let typed = desktop(width, height, seed);
+                for y in 200..280 {
+                    for x in 240..width * 3 / 4 {
+                        img.put_pixel(x, y, *typed.get_pixel(x, y));
+                    }
+                }
+            }
+            let grid = TileGrid::encode(&img, 70, chroma, previous.as_ref()).unwrap();
+            if let Some((Message::Binary(data), _)) = tracker.message(0, 0, &grid, false, true) {
+                messages.push(data);
+            }
+            previous = Some(grid);
+        }
+        messages
+    }
+
+    // Tổng kích thước sau nén / trước nén và thời gian nén cả bộ message ở một mức
+    fn measure(messages: &[Vec<u8>], level: u32) -> (f64, Duration) {
+        let started = Instant::now();
+        let total: usize = messages.iter().map(Vec::len).sum();
+        let compressed: usize =
+            messages.iter().map(|m| zstd_compress(m, level).unwrap().len()).sum();
+        (compressed as f64 / total as f64, started.elapsed())
+    }
+
+    // Đo trên máy dev (release): mức 1 ≈ 0.885, 3 ≈ 0.882, 9 ≈ 0.881, 19 ≈ 0.880, còn thời gian
+    // mức 3 gấp 1.4 lần mức 1, mức 19 gấp 40 lần. Lợi ích nằm ở các ô trùng nhau chứ không ở mức
+    #[test]
+    fn default_level_is_close_to_the_best_ratio() {
+        let messages = tile_messages();
+        let (default_ratio, _) = measure(&messages, DEFAULT_LEVEL);
+        let (best_ratio, _) = measure(&messages, MAX_LEVEL);
+        assert!(default_ratio < 1.0 - MIN_SAVING_PCT as f64 / 100.0, "{}", default_ratio);
+        assert!(default_ratio - best_ratio < 0.02, "{} vs {}", default_ratio, best_ratio);
+    }
+
+    // So thời gian phụ thuộc máy nên không chạy mặc định: cargo test --release -- --ignored
+    #[test]
+    #[ignore]
+    fn default_level_is_much_faster_than_the_best() {
+        let messages = tile_messages();
+        let (_, default_time) = measure(&messages, DEFAULT_LEVEL);
+        let (_, best_time) = measure(&messages, MAX_LEVEL);
+        assert!(default_time * 4 < best_time, "{:?} vs {:?}", default_time, best_time);
+    }
+
+    #[test]
+    fn already_compressed_jpeg_is_not_sent_compressed() {
+        let img = desktop(640, 360, 7);
+        let mut jpeg = Vec::new();
+        encode_jpeg(&mut jpeg, &img, 70, ChromaSubsampling::default()).unwrap();
+        assert_eq!(compress(&jpeg, DEFAULT_LEVEL), None);
+    }
+
+    #[test]
+    fn compressed_message_is_a_zstd_frame_of_the_original() {
+        let message = tile_messages().remove(0);
+        let compressed = compress(&message, DEFAULT_LEVEL).unwrap();
+        assert_eq!(compressed[..4], [0x28, 0xB5, 0x2F, 0xFD]);
+        assert_eq!(zstd::bulk::decompress(&compressed, message.len()).unwrap(), message);
+    }
+}
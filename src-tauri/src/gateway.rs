@@ -0,0 +1,48 @@
+use std::net::IpAddr;
+use tokio::process::Command;
+
+// Linux: "default via 192.168.1.1 dev wlan0 proto dhcp metric 600"
+fn parse_ip_route(output: &str) -> Option<IpAddr> {
+    output.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        if parts.next()? != "default" {
+            return None;
+        }
+        parts.find(|p| *p == "via")?;
+        parts.next()?.parse().ok()
+    })
+}
+
+// macOS: dòng "    gateway: 192.168.1.1" của `route -n get default`
+fn parse_route_get(output: &str) -> Option<IpAddr> {
+    output.lines().find_map(|line| {
+        let value = line.trim().strip_prefix("gateway:")?;
+        value.trim().parse().ok()
+    })
+}
+
+// Windows: "          0.0.0.0          0.0.0.0      192.168.1.1    192.168.1.10     25"
+fn parse_route_print(output: &str) -> Option<IpAddr> {
+    output.lines().find_map(|line| {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            ["0.0.0.0", "0.0.0.0", gateway, ..] => gateway.parse().ok(),
+            _ => None,
+        }
+    })
+}
+
+async fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().await.ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+pub async fn default_gateway() -> Option<IpAddr> {
+    if cfg!(target_os = "windows") {
+        parse_route_print(&run("route", &["print", "0.0.0.0"]).await?)
+    } else if cfg!(target_os = "macos") {
+        parse_route_get(&run("route", &["-n", "get", "default"]).await?)
+    } else {
+        parse_ip_route(&run("ip", &["route", "show", "default"]).await?)
+    }
+}
@@ -0,0 +1,138 @@
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, Semaphore};
+
+use crate::bind_addr::{bind_listener, resolve_bind_ip};
+use crate::error::{AppError, ServerError};
+use crate::mjpeg::read_request_path;
+use crate::screen_share::{self, is_server_running};
+use crate::signaling::{self, is_signaling_running};
+
+// curl http://host:port/health cho uptime monitor, không cần WebSocket hay Tauri
+pub const HEALTH_PATH: &str = "/health";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+// Mỗi kết nối chiếm task tới REQUEST_TIMEOUT, quá số này thì đóng ngay kết nối mới
+const MAX_CONNECTIONS: usize = 32;
+
+static APP_STARTED: OnceLock<Instant> = OnceLock::new();
+
+struct HealthServer {
+    shutdown_tx: broadcast::Sender<()>,
+    bound_addr: SocketAddr,
+}
+
+lazy_static::lazy_static! {
+    static ref HEALTH_SERVER: std::sync::Mutex<Option<HealthServer>> =
+        std::sync::Mutex::new(None);
+}
+
+// Chỉ số đếm, không có IP, mã phòng hay token để mở port này ra ngoài vẫn an toàn
+#[derive(Serialize)]
+struct HealthReport {
+    // Có ít nhất một server (screen hoặc signaling) đang chạy
+    running: bool,
+    screen_running: bool,
+    signaling_running: bool,
+    // Viewer WebSocket của các screen server
+    client_count: usize,
+    room_count: usize,
+    // Tính từ lúc mở app
+    uptime_secs: u64,
+}
+
+pub fn mark_started() {
+    APP_STARTED.get_or_init(Instant::now);
+}
+
+async fn report() -> HealthReport {
+    let screen_running = is_server_running(None);
+    let signaling_running = is_signaling_running();
+    HealthReport {
+        running: screen_running || signaling_running,
+        screen_running,
+        signaling_running,
+        client_count: screen_share::client_count(),
+        room_count: signaling::room_count().await,
+        uptime_secs: APP_STARTED.get_or_init(Instant::now).elapsed().as_secs(),
+    }
+}
+
+// Luôn 200 khi app còn sống, monitor muốn kiểm tra server thì đọc field running
+async fn respond(mut stream: TcpStream) {
+    let (status, body) = match read_request_path(&mut stream, REQUEST_TIMEOUT).await {
+        Some(path) if path == HEALTH_PATH => {
+            let body = serde_json::to_string(&report().await).unwrap_or_default();
+            ("200 OK", body)
+        }
+        Some(_) => ("404 Not Found", String::new()),
+        None => ("400 Bad Request", String::new()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\n\
+         Content-Type: application/json\r\n\
+         Cache-Control: no-store\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+// Đang chạy thì trả lại port cũ
+#[tauri::command]
+pub async fn start_health_server(port: u16, bind_ip: Option<String>) -> Result<u16, AppError> {
+    let running = HEALTH_SERVER.lock().ok().and_then(|s| s.as_ref().map(|s| s.bound_addr));
+    if let Some(addr) = running {
+        return Ok(addr.port());
+    }
+
+    let bind_ip = resolve_bind_ip(bind_ip.as_deref())?;
+    let listener = bind_listener(SocketAddr::new(bind_ip, port)).await?;
+    let bound_addr = listener.local_addr().map_err(ServerError::Bind)?;
+    let (shutdown_tx, mut shutdown_rx) = broadcast::channel::<()>(1);
+    if let Ok(mut server) = HEALTH_SERVER.lock() {
+        *server = Some(HealthServer {
+            shutdown_tx,
+            bound_addr,
+        });
+    }
+
+    let slots = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                result = listener.accept() => {
+                    let Ok((stream, _)) = result else { continue };
+                    // Hết slot thì drop stream, monitor sẽ thử lại ở lần poll sau
+                    if let Ok(permit) = Arc::clone(&slots).try_acquire_owned() {
+                        tokio::spawn(async move {
+                            respond(stream).await;
+                            drop(permit);
+                        });
+                    }
+                }
+                _ = shutdown_rx.recv() => break,
+            }
+        }
+    });
+
+    Ok(bound_addr.port())
+}
+
+#[tauri::command]
+pub fn stop_health_server() -> bool {
+    match HEALTH_SERVER.lock().ok().and_then(|mut s| s.take()) {
+        Some(server) => {
+            let _ = server.shutdown_tx.send(());
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,29 @@
+use serde::Serialize;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::time::{interval_at, Instant, Interval, MissedTickBehavior};
+
+// Frontend không thấy heartbeat quá vài chu kỳ thì coi server đã treo
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Clone)]
+pub struct HeartbeatEvent {
+    // "screen" hoặc "signaling"
+    pub kind: &'static str,
+    pub id: String,
+    pub uptime_secs: u64,
+    pub clients: usize,
+    // Chỉ signaling server có room
+    pub rooms: Option<usize>,
+}
+
+// Tick đầu tiên sau một chu kỳ, "server-ready"/kết quả start đã báo server đang chạy
+pub fn ticker() -> Interval {
+    let mut ticker = interval_at(Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    ticker
+}
+
+pub fn emit(app: &AppHandle, event: HeartbeatEvent) {
+    let _ = app.emit("heartbeat", event);
+}
@@ -0,0 +1,110 @@
+use crate::HostInfo;
+
+pub fn same_host(a: &HostInfo, b: &HostInfo) -> bool {
+    if a.ip == b.ip {
+        return true;
+    }
+    matches!((&a.mac, &b.mac), (Some(x), Some(y)) if x.eq_ignore_ascii_case(y))
+}
+
+// Host mới xuất hiện và host biến mất giữa hai lần quét. So theo IP, MAC chỉ để nhận ra
+// host đổi IP qua DHCP, nên host lần này thiếu MAC (ARP chưa kịp trả) vẫn là host cũ
+pub fn presence_changes<'a>(
+    previous: &'a [HostInfo],
+    current: &'a [HostInfo],
+) -> (Vec<&'a HostInfo>, Vec<&'a HostInfo>) {
+    let online = current
+        .iter()
+        .filter(|h| !previous.iter().any(|p| same_host(p, h)))
+        .collect();
+    let offline = previous
+        .iter()
+        .filter(|p| !current.iter().any(|h| same_host(p, h)))
+        .collect();
+    (online, offline)
+}
+
+// Giữ trường giàu thông tin nhất: giá trị mới nếu có, không thì lấy của lần trước
+pub fn merge_host(old: &HostInfo, new: HostInfo) -> HostInfo {
+    HostInfo {
+        hostname: new.hostname.or_else(|| old.hostname.clone()),
+        mac: new.mac.or_else(|| old.mac.clone()),
+        vendor: new.vendor.or_else(|| old.vendor.clone()),
+        dns_name: new.dns_name.or_else(|| old.dns_name.clone()),
+        netbios_name: new.netbios_name.or_else(|| old.netbios_name.clone()),
+        os_guess: new.os_guess.or_else(|| old.os_guess.clone()),
+        ipv6: new.ipv6.or_else(|| old.ipv6.clone()),
+        ports: if new.ports.is_empty() {
+            old.ports.clone()
+        } else {
+            new.ports
+        },
+        services: if new.services.is_empty() {
+            old.services.clone()
+        } else {
+            new.services
+        },
+        last_seen: new.last_seen.max(old.last_seen),
+        ..new
+    }
+}
+
+// Gộp có cộng dồn: host cũ không thấy lại vẫn được giữ với last_seen cũ
+pub fn merge_hosts(old: &[HostInfo], new: Vec<HostInfo>) -> Vec<HostInfo> {
+    let mut merged: Vec<HostInfo> = new
+        .into_iter()
+        .map(|host| match old.iter().find(|o| same_host(o, &host)) {
+            Some(previous) => merge_host(previous, host),
+            None => host,
+        })
+        .collect();
+
+    for host in old {
+        if !merged.iter().any(|h| same_host(h, host)) {
+            merged.push(host.clone());
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host(ip: &str, mac: Option<&str>) -> HostInfo {
+        HostInfo {
+            ip: ip.to_string(),
+            mac: mac.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn ips(hosts: Vec<&HostInfo>) -> Vec<&str> {
+        hosts.into_iter().map(|h| h.ip.as_str()).collect()
+    }
+
+    #[test]
+    fn missing_mac_is_not_a_presence_change() {
+        let previous = [host("192.168.1.5", Some("aa:bb:cc:dd:ee:ff"))];
+        let current = [host("192.168.1.5", None)];
+        let (online, offline) = presence_changes(&previous, &current);
+        assert!(online.is_empty() && offline.is_empty());
+    }
+
+    #[test]
+    fn changed_ip_is_matched_by_mac() {
+        let previous = [host("192.168.1.5", Some("aa:bb:cc:dd:ee:ff"))];
+        let current = [host("192.168.1.9", Some("AA:BB:CC:DD:EE:FF"))];
+        let (online, offline) = presence_changes(&previous, &current);
+        assert!(online.is_empty() && offline.is_empty());
+    }
+
+    #[test]
+    fn reports_new_and_gone_hosts() {
+        let previous = [host("192.168.1.5", None), host("192.168.1.6", Some("00:11:32:00:00:01"))];
+        let current = [host("192.168.1.5", None), host("192.168.1.7", Some("00:11:32:00:00:02"))];
+        let (online, offline) = presence_changes(&previous, &current);
+        assert_eq!(ips(online), ["192.168.1.7"]);
+        assert_eq!(ips(offline), ["192.168.1.6"]);
+    }
+}
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::host_merge::merge_hosts;
+use crate::{HostInfo, SCAN_CACHE};
+
+const DEFAULT_FILE_NAME: &str = "hosts.json";
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+const CSV_HEADER: &str = "ip,hostname,source,mac,vendor,dns_name,netbios_name,os_guess,ports";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedHosts {
+    // Unix millis của lần quét được lưu
+    scanned_at: u64,
+    hosts: Vec<HostInfo>,
+}
+
+// Không truyền path thì lưu trong thư mục data của app
+fn resolve_path(app: &AppHandle, path: Option<String>) -> Result<PathBuf, AppError> {
+    match path {
+        Some(p) => Ok(PathBuf::from(p)),
+        None => app
+            .path()
+            .app_data_dir()
+            .map(|dir| dir.join(DEFAULT_FILE_NAME))
+            .map_err(|e| AppError::new("Path", e.to_string(), false)),
+    }
+}
+
+#[tauri::command]
+pub async fn save_hosts(app: AppHandle, path: Option<String>) -> Result<String, AppError> {
+    let saved = {
+        let cache = SCAN_CACHE.lock().await;
+        let cache = cache
+            .as_ref()
+            .ok_or_else(|| AppError::not_found("No scan result to save"))?;
+        SavedHosts {
+            scanned_at: cache.result.scanned_at,
+            hosts: cache.result.hosts.clone(),
+        }
+    };
+
+    let path = resolve_path(&app, path)?;
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let json = serde_json::to_string_pretty(&saved)?;
+    tokio::fs::write(&path, json).await?;
+
+    Ok(path.to_string_lossy().to_string())
+}
+
+// Bọc ngoặc kép khi có dấu phẩy, ngoặc kép hoặc xuống dòng
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn to_csv(hosts: &[HostInfo]) -> String {
+    let mut out = String::from(CSV_HEADER);
+    out.push('\n');
+    for host in hosts {
+        let ports = host
+            .ports
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let fields = [
+            host.ip.as_str(),
+            host.hostname.as_deref().unwrap_or(""),
+            host.source.as_str(),
+            host.mac.as_deref().unwrap_or(""),
+            host.vendor.as_deref().unwrap_or(""),
+            host.dns_name.as_deref().unwrap_or(""),
+            host.netbios_name.as_deref().unwrap_or(""),
+            host.os_guess.as_deref().unwrap_or(""),
+            ports.as_str(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+// Xuất kết quả quét gần nhất cho tool khác, trả về số dòng đã ghi
+#[tauri::command]
+pub async fn export_scan(path: String, format: ExportFormat) -> Result<usize, AppError> {
+    let hosts = {
+        let cache = SCAN_CACHE.lock().await;
+        let cache = cache
+            .as_ref()
+            .ok_or_else(|| AppError::not_found("No scan result to export"))?;
+        cache.result.hosts.clone()
+    };
+
+    let content = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&hosts)?,
+        ExportFormat::Csv => to_csv(&hosts),
+    };
+
+    let path = PathBuf::from(path);
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(&path, content).await?;
+
+    Ok(hosts.len())
+}
+
+#[tauri::command]
+pub async fn load_hosts(app: AppHandle, path: Option<String>) -> Result<SavedHosts, AppError> {
+    let path = resolve_path(&app, path)?;
+    let json = tokio::fs::read_to_string(&path).await?;
+    let saved: SavedHosts = serde_json::from_str(&json)?;
+
+    // Kết quả quét mới được ưu tiên, host cũ bổ sung trường còn thiếu hoặc được thêm vào
+    let fresh = SCAN_CACHE
+        .lock()
+        .await
+        .as_ref()
+        .map(|cache| cache.result.hosts.clone())
+        .unwrap_or_default();
+    let hosts = merge_hosts(&saved.hosts, fresh);
+
+    Ok(SavedHosts {
+        scanned_at: saved.scanned_at,
+        hosts,
+    })
+}
@@ -0,0 +1,83 @@
+use std::net::IpAddr;
+
+// Một IP đơn hoặc dải CIDR (vd "192.168.1.0/24", "fe80::/10")
+#[derive(Clone, Copy, Debug)]
+struct IpRange {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        let (addr, prefix) = match value.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (value, None),
+        };
+
+        let network: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("Invalid IP or CIDR: {}", value))?;
+        let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+
+        let prefix = match prefix {
+            Some(p) => p
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max_prefix)
+                .ok_or(format!("Invalid CIDR prefix: {}", value))?,
+            None => max_prefix,
+        };
+
+        Ok(Self { network, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+// Áp dụng trước khi gửi bất kỳ probe nào
+#[derive(Clone, Default, Debug)]
+pub struct TargetFilter {
+    include: Vec<IpRange>,
+    exclude: Vec<IpRange>,
+}
+
+impl TargetFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, String> {
+        Ok(Self {
+            include: include
+                .iter()
+                .map(|v| IpRange::parse(v))
+                .collect::<Result<_, _>>()?,
+            exclude: exclude
+                .iter()
+                .map(|v| IpRange::parse(v))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    // Có include thì chỉ cho phép IP nằm trong include; exclude luôn thắng
+    pub fn allows(&self, ip: &str) -> bool {
+        let ip: IpAddr = match ip.parse() {
+            Ok(ip) => ip,
+            Err(_) => return false,
+        };
+
+        if self.exclude.iter().any(|r| r.contains(ip)) {
+            return false;
+        }
+        self.include.is_empty() || self.include.iter().any(|r| r.contains(ip))
+    }
+}
@@ -1,47 +1,585 @@
+mod activity;
+mod bind_addr;
+mod buffer_pool;
+mod capture_source;
+mod close_code;
+mod connections;
+mod diagnostics;
+mod error;
+mod follow_cursor;
+mod frame_compression;
+mod gateway;
+mod health;
+mod heartbeat;
+mod host_merge;
+mod hosts_store;
+mod ip_filter;
+mod logging;
+mod mjpeg;
+mod name_resolver;
+mod neighbor;
+mod netbios;
+mod os_guess;
+mod oui;
+mod ping;
+mod recording;
+mod remote_input;
+mod scan_profile;
+mod screen_probe;
 mod screen_share;
-mod signaling;
+mod sdp_codec;
+mod service_probe;
+mod shutdown;
+pub mod signaling;
+mod status;
+mod stream_handshake;
+mod tiles;
+mod tls;
+mod topology;
+mod udp_probe;
+mod watermark;
 
 use mdns_sd::{ServiceDaemon, ServiceEvent};
-use serde::Serialize;
-use std::collections::HashMap;
-use std::net::IpAddr;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
 use tokio::net::TcpStream;
-use tokio::process::Command;
-use tokio::sync::Mutex;
-use tokio::time::timeout;
+use tokio::sync::{Mutex, Notify, Semaphore};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::{timeout, MissedTickBehavior};
 
-use screen_share::{is_server_running, start_screen_server, stop_screen_server};
-use signaling::{start_signaling_server, stop_signaling_server};
+use connections::{drop_connection, list_connections};
+use diagnostics::run_diagnostics;
+use error::AppError;
+use health::{start_health_server, stop_health_server};
+use hosts_store::{export_scan, load_hosts, save_hosts};
+use ip_filter::TargetFilter;
+use name_resolver::clear_name_cache;
+use ping::{PingLatency, PingReply};
+use recording::{start_recording, stop_recording};
+use scan_profile::ScanProfile;
+use screen_share::{
+    benchmark_capture, capture_all_monitors, capture_screenshot_png, get_capture_stats,
+    get_server_load, is_server_running, list_monitors, list_windows, set_capture_monitor,
+    set_client_cap, start_local_preview, start_screen_server, stop_local_preview,
+    stop_screen_server, QualityTier,
+};
+use service_probe::PortService;
+use shutdown::shutdown_all;
+use signaling::{
+    close_room, room_stats, set_remote_control, start_signaling_server, stop_signaling_server,
+    viewer_displays,
+};
+use status::get_server_status;
 
-#[derive(Serialize, Clone)]
+#[doc(hidden)]
+pub use screen_share::FramePipeline;
+use stream_handshake::{TierFrameSize, MAX_THROUGHPUT_BYTES};
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct HostInfo {
     ip: String,
     hostname: Option<String>,
     source: String,
+    // true = `ip` là IPv6 (thiết bị chỉ quảng bá IPv6 qua mDNS)
+    is_ipv6: bool,
+    // Địa chỉ IPv6 của host dual-stack, `ip` vẫn là IPv4
+    ipv6: Option<String>,
+    mac: Option<String>,
+    vendor: Option<String>,
+    // Tên lấy riêng từ từng nguồn (PTR và NetBIOS)
+    dns_name: Option<String>,
+    netbios_name: Option<String>,
+    ports: Vec<u16>,
+    // Giao thức nhận ra trên từng port mở, chỉ có khi bật identify_services
+    services: Vec<PortService>,
+    // Chỉ có khi host trả lời ping (xem os_guess)
+    os_guess: Option<String>,
+    // Unix millis lần cuối thấy host
+    last_seen: u64,
+    // Default gateway và chính máy đang quét, để UI ghim lên đầu
+    is_gateway: bool,
+    is_self: bool,
+}
+
+impl HostInfo {
+    fn new(ip: String, hostname: Option<String>, source: &str) -> Self {
+        Self {
+            ip,
+            hostname,
+            source: source.to_string(),
+            last_seen: now_millis(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct InspectOptions {
+    port_start: u16,
+    port_end: u16,
+    timeout_ms: u64,
+    concurrency: usize,
+    // Gửi thêm một probe nhỏ tới mỗi port mở để phân biệt HTTP/HTTPS/TCP thuần
+    identify_services: bool,
+}
+
+impl Default for InspectOptions {
+    fn default() -> Self {
+        Self {
+            port_start: 1,
+            port_end: 1024,
+            timeout_ms: 300,
+            concurrency: 200,
+            identify_services: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ScanOptions {
+    // Port UDP cần thăm dò (vd 161 SNMP, 5353 mDNS), rỗng = bỏ qua bước UDP
+    udp_ports: Vec<u16>,
+    // Preset concurrency/timeout/delay, các trường bên dưới nếu có thì ghi đè preset
+    profile: ScanProfile,
+    // Số host được probe cùng lúc (ARP priming, danh sách IP, và sweep subnet nếu đặt)
+    concurrency: Option<usize>,
+    timeout_ms: Option<u64>,
+    // Chờ ngẫu nhiên tối đa chừng này trước mỗi probe
+    probe_delay_ms: Option<u64>,
+    // So với lần quét trước và phát event "host-online"/"host-offline"
+    emit_changes: bool,
+    // IP hoặc CIDR; có include thì chỉ quét trong include, exclude không bao giờ bị chạm tới
+    include: Vec<String>,
+    exclude: Vec<String>,
+    // Gõ cửa cả subnet bằng TCP connect trước khi đọc `arp -a` để bảng ARP đầy đủ hơn (tốn thêm traffic)
+    prime_arp: bool,
+    // Nhận diện giao thức trên port TCP đã trả lời khi quét subnet
+    identify_services: bool,
+    // Bỏ chính máy đang quét (mọi IP của các interface) khỏi kết quả
+    exclude_self: bool,
+    // Giữ cả thiết bị chỉ có IPv6 mà mDNS tìm được (các bước quét khác vẫn chỉ IPv4)
+    ipv6: bool,
+}
+
+impl ScanOptions {
+    fn target_filter(&self) -> Result<TargetFilter, AppError> {
+        TargetFilter::new(&self.include, &self.exclude).map_err(AppError::invalid_input)
+    }
+
+    fn concurrency(&self) -> usize {
+        self.concurrency.unwrap_or(self.profile.preset().concurrency)
+    }
+
+    fn sweep_concurrency(&self) -> usize {
+        self.concurrency.unwrap_or(self.profile.preset().sweep_concurrency)
+    }
+
+    fn timeout_ms(&self) -> u64 {
+        self.timeout_ms.unwrap_or(self.profile.preset().timeout_ms)
+    }
+
+    fn probe_delay_ms(&self) -> u64 {
+        self.probe_delay_ms.unwrap_or(self.profile.preset().probe_delay_ms)
+    }
+
+    // Kết quả cache chỉ dùng lại cho đúng bộ option đã tạo ra nó. emit_changes không đổi
+    // kết quả quét nên không tính
+    fn cache_key(&self) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        (&self.udp_ports, self.profile, self.concurrency, self.timeout_ms).hash(&mut hasher);
+        (self.probe_delay_ms, &self.include, &self.exclude, self.prime_arp).hash(&mut hasher);
+        (self.identify_services, self.exclude_self, self.ipv6).hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            udp_ports: Vec::new(),
+            profile: ScanProfile::default(),
+            concurrency: None,
+            timeout_ms: None,
+            probe_delay_ms: None,
+            emit_changes: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            prime_arp: false,
+            identify_services: false,
+            exclude_self: true,
+            ipv6: false,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct SourceCounts {
+    mdns: usize,
+    arp: usize,
+    tcp: usize,
+    ping: usize,
+    udp: usize,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct PhaseTimings {
+    mdns_ms: u64,
+    arp_ms: u64,
+    tcp_ms: u64,
+    udp_ms: u64,
+    enrich_ms: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ScanResult {
+    // Trùng với scan_id trong event "scan-started"/"host-online"/"host-offline" của lần quét này
+    scan_id: String,
+    hosts: Vec<HostInfo>,
+    // Unix millis lúc quét xong
+    scanned_at: u64,
+    took_ms: u64,
+    counts: SourceCounts,
+    phases: PhaseTimings,
+}
+
+// Kết quả quét gần nhất, dùng lại nếu còn trong TTL và cùng option
+struct ScanCache {
+    result: ScanResult,
+    scanned_at: Instant,
+    options_key: u64,
+}
+
+const DEFAULT_SCAN_CACHE_TTL_SECS: u64 = 60;
+
+lazy_static::lazy_static! {
+    static ref SCAN_CACHE: Mutex<Option<ScanCache>> = Mutex::new(None);
+    static ref SCAN_WATCH: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+    // Lần quét đang chạy theo scan_id, cancel_scan báo qua Notify
+    static ref RUNNING_SCANS: std::sync::Mutex<HashMap<String, Arc<Notify>>> =
+        std::sync::Mutex::new(HashMap::new());
+}
+
+// Số lần quét đang chạy (thủ công hoặc watch)
+static SCANS_IN_PROGRESS: AtomicUsize = AtomicUsize::new(0);
+
+struct ScanGuard {
+    scan_id: String,
+}
+
+impl ScanGuard {
+    // None khi scan_id đang được một lượt quét khác dùng
+    fn new(scan_id: String) -> Option<(Self, Arc<Notify>)> {
+        let cancel = Arc::new(Notify::new());
+        let mut running = RUNNING_SCANS.lock().unwrap_or_else(|e| e.into_inner());
+        if running.contains_key(&scan_id) {
+            return None;
+        }
+        running.insert(scan_id.clone(), Arc::clone(&cancel));
+        SCANS_IN_PROGRESS.fetch_add(1, Ordering::SeqCst);
+        Some((ScanGuard { scan_id }, cancel))
+    }
+}
+
+impl Drop for ScanGuard {
+    fn drop(&mut self) {
+        if let Ok(mut running) = RUNNING_SCANS.lock() {
+            running.remove(&self.scan_id);
+        }
+        SCANS_IN_PROGRESS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Serialize, Clone)]
+struct ScanStartedEvent {
+    scan_id: String,
+    // "manual" (scan_network) hoặc "watch", để frontend biết id nào là của lần quét mình gọi
+    trigger: &'static str,
+}
+
+// Field của host nằm phẳng như trước, chỉ thêm scan_id
+#[derive(Serialize, Clone)]
+struct HostPresenceEvent<'a> {
+    scan_id: &'a str,
+    #[serde(flatten)]
+    host: &'a HostInfo,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum IpFamily {
+    #[default]
+    V4,
+    V6,
+}
+
+// Mặc định IPv4: địa chỉ đưa cho viewer và subnet để quét đều theo IPv4
+#[tauri::command]
+fn get_local_ip(family: Option<IpFamily>) -> Result<String, AppError> {
+    match family.unwrap_or_default() {
+        IpFamily::V4 => Ok(local_ipv4()?.to_string()),
+        IpFamily::V6 => Ok(local_ipv6()?.to_string()),
+    }
+}
+
+// Gọi rõ theo họ địa chỉ thay vì dựa vào mặc định của local_ip()
+pub(crate) fn local_ipv4() -> Result<Ipv4Addr, AppError> {
+    match local_ip_address::local_ip() {
+        Ok(IpAddr::V4(ip)) => Ok(ip),
+        Ok(IpAddr::V6(_)) | Err(local_ip_address::Error::LocalIpAddressNotFound) => {
+            Err(no_local_address("IPv4"))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn local_ipv6() -> Result<Ipv6Addr, AppError> {
+    match local_ip_address::local_ipv6() {
+        Ok(IpAddr::V6(ip)) => Ok(ip),
+        Ok(IpAddr::V4(_)) | Err(local_ip_address::Error::LocalIpAddressNotFound) => {
+            Err(no_local_address("IPv6"))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Retriable: interface có thể vừa mới lên (Wi-Fi chưa kết nối xong)
+fn no_local_address(family: &str) -> AppError {
+    let message = format!("No {} address on any network interface", family);
+    AppError::new("Network", message, true)
+}
+
+// scan_id do frontend tự sinh để cancel_scan được ngay khi lệnh còn chạy, bỏ trống thì
+// server tạo. Trả kết quả cache thì không quét nên scan_id là của lần quét đã cache
+#[tauri::command]
+async fn scan_network(
+    app: AppHandle,
+    force: Option<bool>,
+    cache_ttl_secs: Option<u64>,
+    options: Option<ScanOptions>,
+    scan_id: Option<String>,
+) -> Result<ScanResult, AppError> {
+    let ttl = Duration::from_secs(cache_ttl_secs.unwrap_or(DEFAULT_SCAN_CACHE_TTL_SECS));
+    let options = options.unwrap_or_default();
+
+    if !force.unwrap_or(false) {
+        if let Some(cache) = SCAN_CACHE.lock().await.as_ref() {
+            if cache.scanned_at.elapsed() < ttl && cache.options_key == options.cache_key() {
+                return Ok(cache.result.clone());
+            }
+        }
+    }
+
+    scan_and_cache(&app, "manual", scan_id, options).await
+}
+
+async fn scan_and_cache(
+    app: &AppHandle,
+    trigger: &'static str,
+    scan_id: Option<String>,
+    options: ScanOptions,
+) -> Result<ScanResult, AppError> {
+    let scan_id = match scan_id.map(|id| id.trim().to_string()) {
+        Some(id) if id.is_empty() => return Err(AppError::invalid_input("Scan id is empty")),
+        Some(id) => id,
+        None => uuid::Uuid::new_v4().to_string(),
+    };
+    let (_guard, cancel) = ScanGuard::new(scan_id.clone())
+        .ok_or_else(|| AppError::invalid_input(format!("Scan {} is already running", scan_id)))?;
+    let started = ScanStartedEvent {
+        scan_id: scan_id.clone(),
+        trigger,
+    };
+    let _ = app.emit("scan-started", started);
+
+    let emit_changes = options.emit_changes;
+    let options_key = options.cache_key();
+    let mut result = tokio::select! {
+        result = run_scan(scan_id, options) => result?,
+        _ = cancel.notified() => {
+            return Err(AppError::new("Cancelled", "Scan was cancelled", false));
+        }
+    };
+
+    let mut cache = SCAN_CACHE.lock().await;
+    // Bổ sung trường còn thiếu (hostname, MAC, ...) từ lần quét trước cho host vẫn còn
+    if let Some(previous) = cache.as_ref() {
+        result.hosts = std::mem::take(&mut result.hosts)
+            .into_iter()
+            .map(|host| {
+                match previous.result.hosts.iter().find(|p| host_merge::same_host(p, &host)) {
+                    Some(old) => host_merge::merge_host(old, host),
+                    None => host,
+                }
+            })
+            .collect();
+    }
+    let previous = cache.replace(ScanCache {
+        result: result.clone(),
+        scanned_at: Instant::now(),
+        options_key,
+    });
+    drop(cache);
+
+    if emit_changes {
+        if let Some(previous) = previous {
+            emit_presence_changes(app, &result.scan_id, &previous.result.hosts, &result.hosts);
+        }
+    }
+
+    Ok(result)
+}
+
+fn emit_presence_changes(
+    app: &AppHandle,
+    scan_id: &str,
+    previous: &[HostInfo],
+    current: &[HostInfo],
+) {
+    let (online, offline) = host_merge::presence_changes(previous, current);
+    for host in online {
+        let _ = app.emit("host-online", HostPresenceEvent { scan_id, host });
+    }
+    for host in offline {
+        let _ = app.emit("host-offline", HostPresenceEvent { scan_id, host });
+    }
 }
 
+// false = không có lần quét nào đang chạy với scan_id này (đã xong hoặc sai id)
 #[tauri::command]
-fn get_local_ip() -> Result<String, String> {
-    local_ip_address::local_ip()
-        .map(|ip| ip.to_string())
-        .map_err(|e| e.to_string())
+fn cancel_scan(scan_id: String) -> bool {
+    let running = RUNNING_SCANS.lock().unwrap_or_else(|e| e.into_inner());
+    match running.get(&scan_id) {
+        Some(cancel) => {
+            cancel.notify_one();
+            true
+        }
+        None => false,
+    }
 }
 
 #[tauri::command]
-async fn scan_network() -> Result<Vec<HostInfo>, String> {
+async fn start_scan_watch(
+    app: AppHandle,
+    interval_secs: u64,
+    options: Option<ScanOptions>,
+) -> Result<(), AppError> {
+    if interval_secs == 0 {
+        return Err(AppError::invalid_input("Interval must be greater than 0"));
+    }
+
+    let mut options = options.unwrap_or_default();
+    options.emit_changes = true;
+
+    let handle = tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            ticker.tick().await;
+            // Lần quét trước (kể cả quét thủ công) chưa xong thì bỏ qua tick này
+            if SCANS_IN_PROGRESS.load(Ordering::SeqCst) > 0 {
+                continue;
+            }
+            let _ = scan_and_cache(&app, "watch", None, options.clone()).await;
+        }
+    });
+
+    if let Some(previous) = SCAN_WATCH.lock().await.replace(handle) {
+        previous.abort();
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn stop_scan_watch() -> bool {
+    match SCAN_WATCH.lock().await.take() {
+        Some(handle) => {
+            handle.abort();
+            true
+        }
+        None => false,
+    }
+}
+
+#[tauri::command]
+async fn clear_scan_cache() {
+    *SCAN_CACHE.lock().await = None;
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn elapsed_ms(since: Instant) -> u64 {
+    since.elapsed().as_millis() as u64
+}
+
+// Máy có thể có nhiều interface (LAN, Wi-Fi, VPN), IP nào cũng là "chính mình"
+fn local_addresses() -> Vec<IpAddr> {
+    let mut addrs: Vec<IpAddr> = local_ip_address::list_afinet_netifas()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(_, addr)| addr)
+        .collect();
+    if let Ok(ip) = local_ip_address::local_ip() {
+        addrs.push(ip);
+    }
+    addrs
+}
+
+async fn mark_gateway_and_self(hosts: &mut [HostInfo]) {
+    let gateway = gateway::default_gateway().await;
+    let local = local_addresses();
+
+    for host in hosts {
+        let addr = host.ip.parse::<IpAddr>().ok();
+        host.is_gateway = addr.is_some() && addr == gateway;
+        host.is_self = addr.is_some_and(|a| a.is_loopback() || local.contains(&a));
+    }
+}
+
+// Sắp theo địa chỉ số (IPv4 trước IPv6), IP không parse được xếp cuối
+fn ip_sort_key(host: &HostInfo) -> (bool, Option<IpAddr>) {
+    let addr = host.ip.parse::<IpAddr>().ok();
+    (addr.is_none(), addr)
+}
+
+async fn run_scan(scan_id: String, options: ScanOptions) -> Result<ScanResult, AppError> {
+    let filter = options.target_filter()?;
+    let started = Instant::now();
+    let mut phases = PhaseTimings::default();
     let mut hosts: HashMap<String, HostInfo> = HashMap::new();
 
     // 1. Quét bằng mDNS
-    if let Ok(mdns_hosts) = scan_mdns_internal().await {
-        for host in mdns_hosts {
+    let phase = Instant::now();
+    if let Ok(mdns_hosts) = scan_mdns_internal(options.ipv6).await {
+        // mDNS là thụ động, nhưng bỏ host bị exclude để bước enrich không gửi gì tới chúng
+        for host in mdns_hosts.into_iter().filter(|h| filter.allows(&h.ip)) {
             hosts.insert(host.ip.clone(), host);
         }
     }
 
+    phases.mdns_ms = elapsed_ms(phase);
+
     // 2. Quét bằng ARP + ping verify
-    if let Ok(arp_hosts) = scan_arp_with_ping().await {
+    let phase = Instant::now();
+    if options.prime_arp {
+        prime_arp_cache(&filter, options.concurrency(), options.probe_delay_ms()).await;
+    }
+    if let Ok(arp_hosts) = scan_arp_with_ping(&filter).await {
         for host in arp_hosts {
             if !hosts.contains_key(&host.ip) {
                 hosts.insert(host.ip.clone(), host);
@@ -49,8 +587,11 @@ async fn scan_network() -> Result<Vec<HostInfo>, String> {
         }
     }
 
+    phases.arp_ms = elapsed_ms(phase);
+
     // 3. Quét toàn bộ subnet bằng TCP (Windows block ping)
-    if let Ok(tcp_hosts) = scan_subnet_tcp(&hosts).await {
+    let phase = Instant::now();
+    if let Ok(tcp_hosts) = scan_subnet_tcp(&hosts, &filter, &options).await {
         for host in tcp_hosts {
             if !hosts.contains_key(&host.ip) {
                 hosts.insert(host.ip.clone(), host);
@@ -58,217 +599,888 @@ async fn scan_network() -> Result<Vec<HostInfo>, String> {
         }
     }
 
+    phases.tcp_ms = elapsed_ms(phase);
+
+    // 4. Thăm dò UDP cho các service không dùng TCP (SNMP, mDNS, ...)
+    let phase = Instant::now();
+    if !options.udp_ports.is_empty() {
+        if let Ok(udp_hosts) = scan_subnet_udp(&hosts, &options.udp_ports, &filter).await {
+            for host in udp_hosts {
+                if !hosts.contains_key(&host.ip) {
+                    hosts.insert(host.ip.clone(), host);
+                }
+            }
+        }
+    }
+
+    phases.udp_ms = elapsed_ms(phase);
+
+    // 5. Bổ sung hostname cho các host chưa có tên
+    let phase = Instant::now();
+    enrich_hostnames(&mut hosts).await;
+    phases.enrich_ms = elapsed_ms(phase);
+
     let mut result: Vec<HostInfo> = hosts.into_values().collect();
-    result.sort_by(|a, b| {
-        let a_num: u32 = a.ip.split('.').last().unwrap_or("0").parse().unwrap_or(0);
-        let b_num: u32 = b.ip.split('.').last().unwrap_or("0").parse().unwrap_or(0);
-        a_num.cmp(&b_num)
-    });
+    result.sort_by_key(ip_sort_key);
+    mark_gateway_and_self(&mut result).await;
+    if options.exclude_self {
+        result.retain(|host| !host.is_self);
+    }
 
-    Ok(result)
+    // Đếm theo source cuối cùng sau khi đã gộp trùng
+    let mut counts = SourceCounts::default();
+    for host in &result {
+        match host.source.as_str() {
+            "mDNS" => counts.mdns += 1,
+            "ARP" => counts.arp += 1,
+            "TCP" => counts.tcp += 1,
+            "Ping" => counts.ping += 1,
+            "UDP" => counts.udp += 1,
+            _ => {}
+        }
+    }
+
+    Ok(ScanResult {
+        scan_id,
+        hosts: result,
+        scanned_at: now_millis(),
+        took_ms: elapsed_ms(started),
+        counts,
+        phases,
+    })
 }
 
-async fn scan_subnet_tcp(existing: &HashMap<String, HostInfo>) -> Result<Vec<HostInfo>, String> {
-    let local_ip = local_ip_address::local_ip().map_err(|e| e.to_string())?;
+// Các bước quét dùng JoinSet: lượt quét bị hủy thì future bị drop và task con bị abort theo
+async fn enrich_hostnames(hosts: &mut HashMap<String, HostInfo>) {
+    let mut tasks = JoinSet::new();
+
+    for host in hosts.values().filter(|h| h.hostname.is_none()) {
+        let ip = host.ip.clone();
+        tasks.spawn(async move {
+            let name = name_resolver::lookup(&ip).await;
+            (ip, name)
+        });
+    }
 
-    let subnet = match local_ip {
-        IpAddr::V4(ipv4) => {
-            let octets = ipv4.octets();
-            format!("{}.{}.{}", octets[0], octets[1], octets[2])
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok((ip, Some(name))) = joined {
+            if let Some(host) = hosts.get_mut(&ip) {
+                host.hostname = Some(name);
+            }
         }
-        _ => return Err("IPv6 not supported".to_string()),
+    }
+}
+
+async fn scan_ports(ip: &str, ports: Vec<u16>, wait: Duration, concurrency: usize) -> Vec<u16> {
+    let addr: IpAddr = match ip.parse() {
+        Ok(addr) => addr,
+        Err(_) => return vec![],
     };
 
-    let hosts: Arc<Mutex<Vec<HostInfo>>> = Arc::new(Mutex::new(Vec::new()));
-    let mut handles = vec![];
+    let results = for_each_bounded(ports, concurrency, move |port| async move {
+        match timeout(wait, TcpStream::connect(SocketAddr::new(addr, port))).await {
+            Ok(Ok(_)) => Some(port),
+            _ => None,
+        }
+    })
+    .await;
 
-    // Windows ports: 445 (SMB), 139 (NetBIOS), 135 (RPC), 3389 (RDP)
-    // Linux/Mac: 22 (SSH), 80, 443
-    // VM: 5985 (WinRM), 5986
-    let common_ports: &[u16] = &[445, 139, 135, 3389, 22, 80, 443, 5985, 8080, 3306, 5432];
+    let mut open: Vec<u16> = results.into_iter().flatten().collect();
+    open.sort_unstable();
+    open
+}
+
+async fn lookup_arp_entry(ip: &str) -> Option<String> {
+    neighbor::read_table()
+        .await
+        .into_iter()
+        .find(|(entry_ip, _, _)| entry_ip == ip)
+        .and_then(|(_, _, mac)| mac)
+}
+
+// Dựng đồ thị mạng từ kết quả quét gần nhất, không quét lại
+#[tauri::command]
+async fn get_topology() -> Result<topology::Topology, AppError> {
+    let cache = SCAN_CACHE.lock().await;
+    let cache = cache
+        .as_ref()
+        .ok_or_else(|| AppError::not_found("No scan result yet, run scan_network first"))?;
+    Ok(topology::build(&cache.result, local_ipv4().ok()))
+}
+
+// Gộp PTR, NetBIOS, ARP và quét port cho một host duy nhất
+#[tauri::command]
+async fn inspect_host(
+    ip: String,
+    options: Option<InspectOptions>,
+) -> Result<HostInfo, AppError> {
+    let options = options.unwrap_or_default();
+    ip.parse::<IpAddr>().map_err(|e| AppError::invalid_input(e.to_string()))?;
+
+    if options.port_start > options.port_end {
+        return Err(AppError::invalid_input("Invalid port range"));
+    }
+    let ports: Vec<u16> = (options.port_start..=options.port_end).collect();
+    let wait = Duration::from_millis(options.timeout_ms);
+
+    let (names, mac, open_ports) = tokio::join!(
+        name_resolver::lookup_names(&ip),
+        lookup_arp_entry(&ip),
+        scan_ports(&ip, ports, wait, options.concurrency),
+    );
+
+    // Giữ lại thông tin từ lần quét trước nếu có
+    let mut host = SCAN_CACHE
+        .lock()
+        .await
+        .as_ref()
+        .and_then(|cache| cache.result.hosts.iter().find(|h| h.ip == ip).cloned())
+        .unwrap_or_else(|| HostInfo::new(ip.clone(), None, "Inspect"));
+
+    if host.hostname.is_none() {
+        host.hostname = names.preferred();
+    }
+    host.dns_name = names.dns;
+    host.netbios_name = names.netbios;
+    if mac.is_some() {
+        host.vendor = mac.as_deref().and_then(oui::lookup_vendor);
+        host.mac = mac;
+    }
+    if options.identify_services {
+        host.services = identify_services(&ip, &open_ports, options.concurrency).await;
+    }
+    host.ports = open_ports;
+
+    Ok(host)
+}
+
+async fn identify_services(ip: &str, ports: &[u16], concurrency: usize) -> Vec<PortService> {
+    let Ok(addr) = ip.parse::<IpAddr>() else {
+        return Vec::new();
+    };
+    let wait = Duration::from_millis(SERVICE_PROBE_TIMEOUT_MS);
+    for_each_bounded(ports.to_vec(), concurrency, move |port| {
+        service_probe::identify(addr, port, wait)
+    })
+    .await
+}
+
+// Quét /24 chỉ có nghĩa với IPv4, máy chỉ có IPv6 thì báo lỗi thay vì quét nhầm
+fn local_subnet() -> Result<String, AppError> {
+    let octets = local_ipv4()?.octets();
+    Ok(format!("{}.{}.{}", octets[0], octets[1], octets[2]))
+}
+
+// Windows ports: 445 (SMB), 139 (NetBIOS), 135 (RPC), 3389 (RDP)
+// Linux/Mac: 22 (SSH), 80, 443
+// VM: 5985 (WinRM), 5986
+const COMMON_PORTS: &[u16] = &[445, 139, 135, 3389, 22, 80, 443, 5985, 8080, 3306, 5432];
+
+// Timeout mỗi probe UDP khi quét cả subnet (TCP lấy theo ScanProfile)
+const SWEEP_TIMEOUT_MS: u64 = 500;
+// Chỉ cần hệ điều hành gửi ARP request, không cần chờ kết nối xong
+const ARP_PRIME_PORT: u16 = 445;
+const ARP_PRIME_TIMEOUT_MS: u64 = 200;
+// Tổng thời gian cho một probe nhận diện service (connect + gửi + đọc)
+const SERVICE_PROBE_TIMEOUT_MS: u64 = 800;
+// Ước lượng thời gian chờ một ping không có phản hồi
+const PING_WAIT_MS: u64 = 1000;
+
+// Số probe tối đa scan_network sẽ gửi (trường hợp không host nào trả lời)
+#[derive(Serialize, Clone, Default)]
+pub struct ScanEstimate {
+    target_hosts: usize,
+    tcp_ports_per_host: usize,
+    udp_ports_per_host: usize,
+    tcp_connects: usize,
+    pings: usize,
+    udp_probes: usize,
+    estimated_ms: u64,
+}
+
+// Chỉ tính toán, không gửi gì ra mạng (không đọc bảng ARP vì `arp -a` tra DNS)
+#[tauri::command]
+fn estimate_scan(options: Option<ScanOptions>) -> Result<ScanEstimate, AppError> {
+    let options = options.unwrap_or_default();
+    let filter = options.target_filter()?;
+    let subnet = local_subnet()?;
+
+    let target_hosts = (1..=254)
+        .filter(|i| filter.allows(&format!("{}.{}", subnet, i)))
+        .count();
+    let tcp_ports_per_host = COMMON_PORTS.len();
+    let udp_ports_per_host = options.udp_ports.len();
+
+    // Host trong bảng ARP được ping thay vì quét TCP, nên mỗi host tối đa một ping
+    let prime_connects = if options.prime_arp { target_hosts } else { 0 };
+    let tcp_connects = target_hosts * tcp_ports_per_host + prime_connects;
+    let pings = target_hosts;
+    let udp_probes = target_hosts * udp_ports_per_host;
+
+    // Mỗi lượt sweep chạy song song sweep_concurrency host, thời gian ~ chuỗi probe của một host
+    let mdns_ms = MDNS_BROWSE_SECS * 1000;
+    let sweep_rounds = target_hosts.div_ceil(options.sweep_concurrency().max(1)) as u64;
+    let per_host_ms =
+        tcp_ports_per_host as u64 * options.timeout_ms() + PING_WAIT_MS + options.probe_delay_ms();
+    let tcp_ms = sweep_rounds * per_host_ms;
+    let udp_ms = if target_hosts > 0 {
+        udp_ports_per_host as u64 * SWEEP_TIMEOUT_MS
+    } else {
+        0
+    };
+    let prime_rounds = prime_connects.div_ceil(options.concurrency().max(1)) as u64;
+    let prime_ms = prime_rounds * (ARP_PRIME_TIMEOUT_MS + options.probe_delay_ms());
+
+    Ok(ScanEstimate {
+        target_hosts,
+        tcp_ports_per_host,
+        udp_ports_per_host,
+        tcp_connects,
+        pings,
+        udp_probes,
+        estimated_ms: mdns_ms + prime_ms + tcp_ms + udp_ms,
+    })
+}
+
+// Chạy f cho từng item, tối đa `limit` task cùng lúc. Kết quả giữ thứ tự của items
+async fn for_each_bounded<T, R, F, Fut>(items: Vec<T>, limit: usize, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = R> + Send + 'static,
+{
+    let semaphore = Arc::new(Semaphore::new(limit.max(1)));
+    let mut tasks = JoinSet::new();
+
+    for (index, item) in items.into_iter().enumerate() {
+        let semaphore = Arc::clone(&semaphore);
+        let fut = f(item);
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            (index, fut.await)
+        });
+    }
+
+    let mut results = Vec::with_capacity(tasks.len());
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok(result) = joined {
+            results.push(result);
+        }
+    }
+    results.sort_unstable_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+// Thử TCP trước (Windows thường block ping), sau đó fallback ping.
+// identify: nhận diện luôn giao thức trên port đầu tiên trả lời
+async fn probe_host(ip: String, wait: Duration, identify: bool) -> Option<HostInfo> {
+    let addr: IpAddr = ip.parse().ok()?;
+
+    for port in COMMON_PORTS {
+        let target = SocketAddr::new(addr, *port);
+        if let Ok(Ok(_)) = timeout(wait, TcpStream::connect(target)).await {
+            let mut host = HostInfo::new(ip, None, "TCP");
+            if identify {
+                let wait = Duration::from_millis(SERVICE_PROBE_TIMEOUT_MS);
+                host.services.push(service_probe::identify(addr, *port, wait).await);
+            }
+            return Some(host);
+        }
+    }
+
+    let reply = ping_host(&ip).await?;
+    let mut host = HostInfo::new(ip, None, "Ping");
+    host.os_guess = reply.ttl.and_then(os_guess::guess_from_ttl);
+    Some(host)
+}
+
+async fn scan_subnet_tcp(
+    existing: &HashMap<String, HostInfo>,
+    filter: &TargetFilter,
+    options: &ScanOptions,
+) -> Result<Vec<HostInfo>, AppError> {
+    let subnet = local_subnet()?;
+
+    let targets: Vec<String> = (1..=254)
+        .map(|i| format!("{}.{}", subnet, i))
+        .filter(|ip| !existing.contains_key(ip) && filter.allows(ip))
+        .collect();
+
+    let sweep_wait = Duration::from_millis(options.timeout_ms());
+    let delay_ms = options.probe_delay_ms();
+    let identify = options.identify_services;
+    let results = for_each_bounded(targets, options.sweep_concurrency(), move |ip| async move {
+        scan_profile::probe_delay(delay_ms).await;
+        probe_host(ip, sweep_wait, identify).await
+    })
+    .await;
+
+    Ok(results.into_iter().flatten().collect())
+}
+
+#[derive(Serialize, Clone)]
+pub struct TargetStatus {
+    ip: String,
+    reachable: bool,
+    host: Option<HostInfo>,
+}
+
+// Chỉ quét đúng danh sách IP người dùng đưa vào (v4 hoặc v6)
+#[tauri::command]
+async fn scan_targets(
+    ips: Vec<String>,
+    options: Option<ScanOptions>,
+) -> Result<Vec<TargetStatus>, AppError> {
+    let options = options.unwrap_or_default();
+    let filter = options.target_filter()?;
+
+    let mut targets = Vec::with_capacity(ips.len());
+    for ip in ips {
+        let addr: IpAddr = ip
+            .trim()
+            .parse()
+            .map_err(|_| AppError::invalid_input(format!("Invalid IP address: {}", ip)))?;
+        let addr = addr.to_string();
+        // IP bị loại thì bỏ qua hoàn toàn, không probe
+        if filter.allows(&addr) {
+            targets.push(addr);
+        }
+    }
+
+    let wait = Duration::from_millis(options.timeout_ms());
+    let identify = options.identify_services;
+    let results = for_each_bounded(targets, options.concurrency(), move |ip| async move {
+        let host = probe_host(ip.clone(), wait, identify).await;
+        TargetStatus {
+            ip,
+            reachable: host.is_some(),
+            host,
+        }
+    })
+    .await;
+
+    Ok(results)
+}
+
+// Chỉ probe các IP trong subnet mà lần quét trước không thấy (vd thiết bị đang khởi động),
+// host mới tìm được gộp vào danh sách cũ
+#[tauri::command]
+async fn rescan_missing(
+    previous: Vec<HostInfo>,
+    options: Option<ScanOptions>,
+) -> Result<Vec<HostInfo>, AppError> {
+    let options = options.unwrap_or_default();
+    let filter = options.target_filter()?;
+    let subnet = local_subnet()?;
+
+    let found: HashSet<&str> = previous.iter().map(|h| h.ip.as_str()).collect();
+    let missing: Vec<String> = (1..=254)
+        .map(|i| format!("{}.{}", subnet, i))
+        .filter(|ip| !found.contains(ip.as_str()) && filter.allows(ip))
+        .collect();
+
+    let exclude_self = options.exclude_self;
+    let statuses = scan_targets(missing, Some(options)).await?;
+    let mut discovered: Vec<HostInfo> = statuses.into_iter().filter_map(|s| s.host).collect();
+    mark_gateway_and_self(&mut discovered).await;
+    if exclude_self {
+        discovered.retain(|host| !host.is_self);
+    }
+
+    let mut hosts = host_merge::merge_hosts(&previous, discovered);
+    hosts.sort_by_key(ip_sort_key);
+    Ok(hosts)
+}
+
+async fn scan_subnet_udp(
+    existing: &HashMap<String, HostInfo>,
+    ports: &[u16],
+    filter: &TargetFilter,
+) -> Result<Vec<HostInfo>, AppError> {
+    let subnet = local_subnet()?;
+
+    let mut tasks = JoinSet::new();
 
     for i in 1..=254 {
         let ip = format!("{}.{}", subnet, i);
 
-        if existing.contains_key(&ip) {
+        if existing.contains_key(&ip) || !filter.allows(&ip) {
             continue;
         }
 
-        let hosts = Arc::clone(&hosts);
+        let ports = ports.to_vec();
 
-        let handle = tokio::spawn(async move {
-            // Thử TCP trước (Windows thường block ping)
-            for port in common_ports {
-                let addr = format!("{}:{}", ip, port);
-                if let Ok(Ok(_)) =
-                    timeout(Duration::from_millis(500), TcpStream::connect(&addr)).await
-                {
-                    hosts.lock().await.push(HostInfo {
-                        ip,
-                        hostname: None,
-                        source: "TCP".to_string(),
-                    });
-                    return;
+        tasks.spawn(async move {
+            for port in ports {
+                if udp_probe::probe(&ip, port, Duration::from_millis(SWEEP_TIMEOUT_MS)).await {
+                    return Some(HostInfo::new(ip, None, "UDP"));
                 }
             }
-
-            // Fallback ping
-            if ping_host(&ip).await {
-                hosts.lock().await.push(HostInfo {
-                    ip,
-                    hostname: None,
-                    source: "Ping".to_string(),
-                });
-            }
+            None
         });
-        handles.push(handle);
     }
 
-    for handle in handles {
-        let _ = handle.await;
+    let mut result = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        result.extend(joined.ok().flatten());
     }
-
-    let result = hosts.lock().await.clone();
     Ok(result)
 }
 
-async fn scan_arp_with_ping() -> Result<Vec<HostInfo>, String> {
-    let output = Command::new("arp")
-        .arg("-a")
-        .output()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut candidates: Vec<(String, Option<String>)> = Vec::new();
-
-    for line in stdout.lines() {
-        if let Some(start) = line.find('(') {
-            if let Some(end) = line.find(')') {
-                let ip = &line[start + 1..end];
-                if ip.starts_with("192.") || ip.starts_with("10.") || ip.starts_with("172.") {
-                    let hostname = if line.starts_with('?') {
-                        None
-                    } else {
-                        line.split_whitespace().next().map(|s| s.to_string())
-                    };
-                    candidates.push((ip.to_string(), hostname));
-                }
-            }
-        }
-    }
+// Kết nối thử tới mọi IP trong subnet, bỏ qua kết quả: chỉ để kernel điền bảng ARP
+async fn prime_arp_cache(filter: &TargetFilter, concurrency: usize, delay_ms: u64) {
+    let Ok(subnet) = local_subnet() else { return };
+    let targets: Vec<IpAddr> = (1..=254)
+        .map(|i| format!("{}.{}", subnet, i))
+        .filter(|ip| filter.allows(ip))
+        .filter_map(|ip| ip.parse().ok())
+        .collect();
 
-    let hosts: Arc<Mutex<Vec<HostInfo>>> = Arc::new(Mutex::new(Vec::new()));
-    let mut handles = vec![];
+    let wait = Duration::from_millis(ARP_PRIME_TIMEOUT_MS);
+    for_each_bounded(targets, concurrency, move |addr| async move {
+        scan_profile::probe_delay(delay_ms).await;
+        let target = SocketAddr::new(addr, ARP_PRIME_PORT);
+        let _ = timeout(wait, TcpStream::connect(target)).await;
+    })
+    .await;
+}
 
-    for (ip, hostname) in candidates {
-        let hosts = Arc::clone(&hosts);
+async fn scan_arp_with_ping(filter: &TargetFilter) -> Result<Vec<HostInfo>, String> {
+    let candidates: Vec<_> = neighbor::read_table()
+        .await
+        .into_iter()
+        .filter(|(ip, _, _)| filter.allows(ip))
+        .collect();
 
-        let handle = tokio::spawn(async move {
-            if ping_host(&ip).await {
-                hosts.lock().await.push(HostInfo {
-                    ip,
-                    hostname,
-                    source: "ARP".to_string(),
-                });
-            }
+    let mut tasks = JoinSet::new();
+
+    for (ip, hostname, mac) in candidates {
+        tasks.spawn(async move {
+            let reply = ping_host(&ip).await?;
+            let mut host = HostInfo::new(ip, hostname, "ARP");
+            host.os_guess = reply.ttl.and_then(os_guess::guess_from_ttl);
+            host.vendor = mac.as_deref().and_then(oui::lookup_vendor);
+            host.mac = mac;
+            Some(host)
         });
-        handles.push(handle);
     }
 
-    for handle in handles {
-        let _ = handle.await;
+    let mut result = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        result.extend(joined.ok().flatten());
     }
-
-    let result = hosts.lock().await.clone();
     Ok(result)
 }
 
-async fn ping_host(ip: &str) -> bool {
-    let output = Command::new("ping")
-        .args(["-c", "1", "-W", "500", ip])
-        .output()
-        .await;
+async fn ping_host(ip: &str) -> Option<PingReply> {
+    let reply = ping::ping(ip, 1).await.ok()?;
+    reply.success.then_some(reply)
+}
+
+// Gửi `count` gói ping để đo độ trễ thật trước khi mở phiên xem
+#[tauri::command]
+async fn ping_latency(ip: String, count: u32) -> Result<PingLatency, AppError> {
+    ip.parse::<IpAddr>().map_err(|e| AppError::invalid_input(e.to_string()))?;
+    let count = count.clamp(1, ping::MAX_PING_COUNT);
+
+    let reply = match timeout(ping::deadline(count), ping::ping(&ip, count)).await {
+        Ok(reply) => reply?,
+        Err(_) => ping::unreachable(),
+    };
+    Ok(ping::summarize(count, &reply))
+}
+
+const SCREEN_PROBE_TIMEOUT_MS: u64 = 1500;
+const RECOMMEND_PING_COUNT: u32 = 3;
+const RECOMMEND_CONCURRENCY: usize = 16;
+
+// Host có đang chạy screen server của app (ws://ip:port) không, port mặc định 9000
+#[tauri::command]
+async fn probe_screen_host(ip: String, port: Option<u16>) -> Result<bool, AppError> {
+    let addr = ip.parse::<IpAddr>().map_err(|e| AppError::invalid_input(e.to_string()))?;
+    let port = port.unwrap_or(screen_probe::DEFAULT_SCREEN_PORT);
+    let wait = Duration::from_millis(SCREEN_PROBE_TIMEOUT_MS);
+    Ok(screen_probe::probe(addr, port, wait).await.is_some())
+}
+
+const MIN_THROUGHPUT_BYTES: usize = 64 * 1024;
+// Cả lần đo (kết nối + truyền) không quá chừng này dù link chậm
+const THROUGHPUT_TIMEOUT_SECS: u64 = 30;
+// Chỉ dùng chừng này phần băng thông đo được cho stream, chừa cho dao động và traffic khác
+const THROUGHPUT_HEADROOM: f64 = 0.7;
+const MIN_SUGGESTED_FPS: u32 = 5;
+
+#[derive(Serialize, Clone)]
+pub struct Throughput {
+    bytes: usize,
+    elapsed_ms: f64,
+    mbps: f64,
+    // Kích thước frame gần đây theo tier do server báo
+    frame_sizes: Vec<TierFrameSize>,
+    // Gợi ý quality/fps cho handshake của phiên xem, theo băng thông đo được và frame_sizes.
+    // null khi server chưa encode frame nào để so
+    suggested_quality: Option<u8>,
+    suggested_fps: Option<u32>,
+}
+
+lazy_static::lazy_static! {
+    static ref THROUGHPUT_CANCEL: Notify = Notify::new();
+}
+
+static THROUGHPUTS_RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+// Trong các tier server báo kích thước, tier cao nhất vẫn đạt MIN_SUGGESTED_FPS. Link quá chậm
+// thì tier nhỏ nhất với fps làm được, None khi không có kích thước nào
+fn suggest_stream(mbps: f64, frame_sizes: &[TierFrameSize]) -> Option<(QualityTier, u32)> {
+    let max_fps = (1000 / screen_share::FRAME_INTERVAL_MS) as u32;
+    let budget_bytes = mbps * 1_000_000.0 / 8.0 * THROUGHPUT_HEADROOM;
+    let fps_for = |size: &TierFrameSize| {
+        ((budget_bytes / size.avg_bytes.max(1) as f64) as u32).min(max_fps)
+    };
+    let mut sizes = frame_sizes.to_vec();
+    sizes.sort_by_key(|size| std::cmp::Reverse(size.tier.jpeg_quality()));
+    let smallest = sizes.iter().min_by_key(|size| size.avg_bytes)?;
+    let suggested = sizes
+        .iter()
+        .map(|size| (size.tier, fps_for(size)))
+        .find(|&(_, fps)| fps >= MIN_SUGGESTED_FPS)
+        .unwrap_or((smallest.tier, fps_for(smallest).max(1)));
+    Some(suggested)
+}
+
+// Đo băng thông tới screen server của máy kia (ws://ip:port) trước khi mở phiên xem.
+// bytes trong khoảng 64 KB - 16 MB (vd 8 MB); cancel_throughput dừng mọi lần đo đang chạy
+#[tauri::command]
+async fn measure_throughput(ip: String, port: u16, bytes: usize) -> Result<Throughput, AppError> {
+    let addr = ip.parse::<IpAddr>().map_err(|e| AppError::invalid_input(e.to_string()))?;
+    let bytes = bytes.clamp(MIN_THROUGHPUT_BYTES, MAX_THROUGHPUT_BYTES);
+    let wait = Duration::from_secs(THROUGHPUT_TIMEOUT_SECS);
+
+    // Đăng ký chờ cancel trước khi tăng bộ đếm: notify_waiters không giữ lại thông báo, nên
+    // cancel_throughput thấy lần đo này đang chạy thì chắc chắn đánh thức được nó
+    let cancelled = THROUGHPUT_CANCEL.notified();
+    tokio::pin!(cancelled);
+    cancelled.as_mut().enable();
+    THROUGHPUTS_RUNNING.fetch_add(1, Ordering::SeqCst);
+    let result = tokio::select! {
+        result = timeout(wait, screen_probe::throughput(addr, port, bytes)) => result
+            .unwrap_or_else(|_| Err(AppError::new("Timeout", "Throughput test timed out", true))),
+        _ = cancelled => {
+            Err(AppError::new("Cancelled", "Throughput test was cancelled", false))
+        }
+    };
+    THROUGHPUTS_RUNNING.fetch_sub(1, Ordering::SeqCst);
+
+    let sample = result?;
+    let secs = sample.elapsed.as_secs_f64().max(f64::EPSILON);
+    let mbps = sample.bytes as f64 * 8.0 / secs / 1_000_000.0;
+    let suggested = suggest_stream(mbps, &sample.frame_sizes);
+    Ok(Throughput {
+        bytes: sample.bytes,
+        elapsed_ms: secs * 1000.0,
+        mbps,
+        frame_sizes: sample.frame_sizes,
+        suggested_quality: suggested.map(|(tier, _)| tier.jpeg_quality()),
+        suggested_fps: suggested.map(|(_, fps)| fps),
+    })
+}
+
+// false = không có lần đo nào đang chạy
+#[tauri::command]
+fn cancel_throughput() -> bool {
+    let running = THROUGHPUTS_RUNNING.load(Ordering::SeqCst) > 0;
+    THROUGHPUT_CANCEL.notify_waiters();
+    running
+}
+
+const PORT_CHECK_TIMEOUT_MS: u64 = 2000;
+
+#[derive(Serialize, Clone)]
+pub struct PortReachability {
+    reachable: bool,
+    // "open", "refused" (không có gì nghe ở port), "timeout" (thường do firewall chặn
+    // không trả lời) hoặc "error"
+    status: &'static str,
+    elapsed_ms: f64,
+    error: Option<String>,
+}
 
-    match output {
-        Ok(o) => o.status.success(),
-        Err(_) => false,
+// Thử TCP connect tới ip:port để biết port có qua được firewall không.
+// Chạy trên chính host thì hệ điều hành có thể không lọc kết nối tới IP của mình,
+// nên kết quả chắc chắn nhất khi chạy từ máy khác trong mạng
+#[tauri::command]
+async fn check_port_reachable(
+    ip: String,
+    port: u16,
+    timeout_ms: Option<u64>,
+) -> Result<PortReachability, AppError> {
+    let addr = ip.parse::<IpAddr>().map_err(|e| AppError::invalid_input(e.to_string()))?;
+    if port == 0 {
+        return Err(AppError::invalid_input("Port must be between 1 and 65535"));
     }
+    let wait = Duration::from_millis(timeout_ms.unwrap_or(PORT_CHECK_TIMEOUT_MS).max(1));
+
+    let started = Instant::now();
+    let result = timeout(wait, TcpStream::connect(SocketAddr::new(addr, port))).await;
+    let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+    let (status, error) = match result {
+        Ok(Ok(_)) => ("open", None),
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+            ("refused", Some(e.to_string()))
+        }
+        Ok(Err(e)) => ("error", Some(e.to_string())),
+        Err(_) => ("timeout", None),
+    };
+    Ok(PortReachability {
+        reachable: status == "open",
+        status,
+        elapsed_ms,
+        error,
+    })
 }
 
-async fn scan_mdns_internal() -> Result<Vec<HostInfo>, String> {
+// Trong các host đang chạy screen server, chọn host có độ trễ thấp nhất. None = không có host nào
+#[tauri::command]
+async fn recommend_host(hosts: Vec<HostInfo>, port: Option<u16>) -> Option<String> {
+    let port = port.unwrap_or(screen_probe::DEFAULT_SCREEN_PORT);
+    let candidates: Vec<IpAddr> = hosts
+        .iter()
+        .filter(|h| !h.is_self)
+        .filter_map(|h| h.ip.parse().ok())
+        .collect();
+
+    let results = for_each_bounded(candidates, RECOMMEND_CONCURRENCY, move |ip| async move {
+        let wait = Duration::from_millis(SCREEN_PROBE_TIMEOUT_MS);
+        let (latency, handshake) = tokio::join!(
+            ping_latency(ip.to_string(), RECOMMEND_PING_COUNT),
+            screen_probe::probe(ip, port, wait),
+        );
+        let handshake = handshake?;
+        // Host chặn ICMP mà vẫn chạy service thì lấy thời gian handshake làm độ trễ
+        let latency_ms = latency
+            .ok()
+            .and_then(|l| l.avg_ms())
+            .unwrap_or(handshake.as_secs_f64() * 1000.0);
+        Some((ip, latency_ms))
+    })
+    .await;
+
+    results
+        .into_iter()
+        .flatten()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(ip, _)| ip.to_string())
+}
+
+const MDNS_SERVICE_TYPES: &[&str] = &[
+    "_http._tcp.local.",
+    "_https._tcp.local.",
+    "_ssh._tcp.local.",
+    "_smb._tcp.local.",
+    "_workstation._tcp.local.",
+    "_device-info._tcp.local.",
+    "_googlecast._tcp.local.",
+    "_airplay._tcp.local.",
+    "_raop._tcp.local.",
+    "_printer._tcp.local.",
+    "_ipp._tcp.local.",
+];
+const MDNS_BROWSE_SECS: u64 = 2;
+
+// Ưu tiên địa chỉ global, link-local (fe80::) cần scope id nên chỉ dùng khi không còn gì khác
+fn pick_ipv6<'a>(addrs: impl Iterator<Item = &'a IpAddr>) -> Option<IpAddr> {
+    let v6: Vec<IpAddr> = addrs.filter(|a| a.is_ipv6()).copied().collect();
+    let is_link_local = |a: &IpAddr| matches!(a, IpAddr::V6(v6) if v6.segments()[0] == 0xfe80);
+    v6.iter().find(|a| !is_link_local(a)).or(v6.first()).copied()
+}
+
+async fn scan_mdns_internal(include_v6: bool) -> Result<Vec<HostInfo>, String> {
     let mdns = ServiceDaemon::new().map_err(|e| e.to_string())?;
 
-    let service_types = vec![
-        "_http._tcp.local.",
-        "_https._tcp.local.",
-        "_ssh._tcp.local.",
-        "_smb._tcp.local.",
-        "_workstation._tcp.local.",
-        "_device-info._tcp.local.",
-        "_googlecast._tcp.local.",
-        "_airplay._tcp.local.",
-        "_raop._tcp.local.",
-        "_printer._tcp.local.",
-        "_ipp._tcp.local.",
-    ];
+    // Browse mọi service type cùng lúc trên một daemon, chung một cửa sổ thời gian
+    let receivers: Vec<_> = MDNS_SERVICE_TYPES
+        .iter()
+        .filter_map(|service_type| mdns.browse(service_type).ok().map(|r| (*service_type, r)))
+        .collect();
 
     let mut hosts: HashMap<String, HostInfo> = HashMap::new();
+    let deadline = std::time::Instant::now() + Duration::from_secs(MDNS_BROWSE_SECS);
 
-    for service_type in &service_types {
-        if let Ok(receiver) = mdns.browse(service_type) {
-            let timeout_duration = Duration::from_secs(2);
-            let start = std::time::Instant::now();
-
-            while start.elapsed() < timeout_duration {
-                match receiver.recv_timeout(Duration::from_millis(100)) {
-                    Ok(ServiceEvent::ServiceResolved(info)) => {
-                        for addr in info.get_addresses() {
-                            if let IpAddr::V4(ipv4) = addr {
-                                let ip = ipv4.to_string();
-                                if !hosts.contains_key(&ip) {
-                                    let hostname = info
-                                        .get_fullname()
-                                        .split('.')
-                                        .next()
-                                        .map(|s| s.to_string());
-
-                                    hosts.insert(
-                                        ip.clone(),
-                                        HostInfo {
-                                            ip,
-                                            hostname,
-                                            source: "mDNS".to_string(),
-                                        },
-                                    );
-                                }
-                            }
+    while std::time::Instant::now() < deadline {
+        for (_, receiver) in &receivers {
+            while let Ok(event) = receiver.try_recv() {
+                let ServiceEvent::ServiceResolved(info) = event else { continue };
+                let hostname = info.get_fullname().split('.').next().map(|s| s.to_string());
+                let v6 = pick_ipv6(info.get_addresses().iter()).map(|a| a.to_string());
+                for addr in info.get_addresses() {
+                    if let IpAddr::V4(ipv4) = addr {
+                        let ip = ipv4.to_string();
+                        let host = hosts
+                            .entry(ip.clone())
+                            .or_insert_with(|| HostInfo::new(ip, hostname.clone(), "mDNS"));
+                        if host.ipv6.is_none() {
+                            host.ipv6 = v6.clone();
                         }
                     }
-                    _ => continue,
+                }
+                // Dual-stack thì giữ IPv4 làm địa chỉ chính, bỏ bản ghi chỉ-IPv6 trước đó
+                let Some(v6) = v6 else { continue };
+                let has_v4 = info.get_addresses().iter().any(|a| a.is_ipv4());
+                if has_v4 {
+                    hosts.remove(&v6);
+                } else if include_v6
+                    && !hosts.values().any(|h| h.ipv6.as_deref() == Some(v6.as_str()))
+                {
+                    let mut host = HostInfo::new(v6.clone(), hostname, "mDNS");
+                    host.is_ipv6 = true;
+                    hosts.entry(v6).or_insert(host);
                 }
             }
-            let _ = mdns.stop_browse(service_type);
         }
+        tokio::time::sleep(Duration::from_millis(100)).await;
     }
 
+    for (service_type, _) in &receivers {
+        let _ = mdns.stop_browse(service_type);
+    }
     let _ = mdns.shutdown();
     Ok(hosts.into_values().collect())
 }
 
+// Đã bắt đầu dọn dẹp khi thoát, ExitRequested lần sau (từ app.exit) cho thoát luôn
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    logging::init();
+    health::mark_started();
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
             get_local_ip,
             scan_network,
+            cancel_scan,
+            clear_scan_cache,
+            clear_name_cache,
+            start_scan_watch,
+            stop_scan_watch,
+            save_hosts,
+            load_hosts,
+            export_scan,
+            inspect_host,
+            scan_targets,
+            rescan_missing,
+            estimate_scan,
+            ping_latency,
+            probe_screen_host,
+            check_port_reachable,
+            measure_throughput,
+            cancel_throughput,
+            get_topology,
+            list_connections,
+            drop_connection,
+            recommend_host,
             start_screen_server,
             stop_screen_server,
             is_server_running,
+            get_server_load,
+            get_server_status,
+            start_health_server,
+            stop_health_server,
+            get_capture_stats,
+            benchmark_capture,
+            capture_screenshot_png,
+            capture_all_monitors,
+            list_monitors,
+            list_windows,
+            set_capture_monitor,
+            set_client_cap,
+            start_local_preview,
+            stop_local_preview,
+            start_recording,
+            stop_recording,
             start_signaling_server,
-            stop_signaling_server
+            stop_signaling_server,
+            set_remote_control,
+            room_stats,
+            close_room,
+            viewer_displays,
+            run_diagnostics,
+            shutdown_all
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        // Chỉ cửa sổ chính mới làm app thoát, dọn dẹp chạy ở ExitRequested
+        .on_window_event(|window, event| {
+            if window.label() == "main" && matches!(event, tauri::WindowEvent::Destroyed) {
+                window.app_handle().exit(0);
+            }
+        })
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            // Lần đầu thì hoãn thoát, dừng server/task nền ở task riêng để event loop không bị chặn
+            if let tauri::RunEvent::ExitRequested { api, .. } = event {
+                if !SHUTTING_DOWN.swap(true, Ordering::SeqCst) {
+                    api.prevent_exit();
+                    let app = app.clone();
+                    tauri::async_runtime::spawn(async move {
+                        shutdown_all().await;
+                        app.exit(0);
+                    });
+                }
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(tier: QualityTier, kb: usize) -> TierFrameSize {
+        TierFrameSize {
+            tier,
+            avg_bytes: kb * 1000,
+        }
+    }
+
+    #[test]
+    fn suggestion_needs_reported_frame_sizes() {
+        assert_eq!(suggest_stream(100.0, &[]), None);
+    }
+
+    #[test]
+    fn suggestion_picks_the_highest_tier_that_keeps_min_fps() {
+        let sizes = [size(QualityTier::Low, 20), size(QualityTier::High, 200)];
+        let max_fps = (1000 / screen_share::FRAME_INTERVAL_MS) as u32;
+        // 10 Mbps * 0.7 = 875 KB/s: High chỉ được 4 fps, Low đủ fps tối đa
+        assert_eq!(suggest_stream(10.0, &sizes), Some((QualityTier::Low, max_fps)));
+        assert_eq!(suggest_stream(100.0, &sizes), Some((QualityTier::High, max_fps)));
+    }
+
+    #[test]
+    fn slow_link_falls_back_to_the_smallest_frames() {
+        let sizes = [size(QualityTier::Medium, 60), size(QualityTier::Low, 30)];
+        assert_eq!(suggest_stream(0.1, &sizes), Some((QualityTier::Low, 1)));
+    }
+
+    // Server nhận kết nối rồi im lặng, lần đo chỉ kết thúc được nhờ cancel
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn cancel_reaches_a_throughput_test_as_soon_as_it_counts_as_running() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let _held = tokio::spawn(async move {
+            let mut streams = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+
+        let run = tokio::spawn(measure_throughput("127.0.0.1".into(), port, 0));
+        while THROUGHPUTS_RUNNING.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert!(cancel_throughput());
+        let result = timeout(Duration::from_secs(2), run).await.unwrap().unwrap();
+        assert_eq!(result.err().map(|e| e.code).as_deref(), Some("Cancelled"));
+    }
 }
@@ -0,0 +1,28 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+// Mọi module ghi log qua macro của crate log (log::warn!, log::info!...), chỉ chỗ này quyết định
+// ghi ra đâu. Hiện ghi stderr dạng "LEVEL target: message", đổi sang file hay plugin chỉ sửa ở đây
+struct StderrLogger;
+
+static LOGGER: StderrLogger = StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Info
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{} {}: {}", record.level(), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+// Gọi một lần khi mở app. Đã có logger khác (vd test) thì giữ logger đó
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
@@ -0,0 +1,149 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::broadcast;
+
+use crate::close_code::CloseReason;
+use crate::screen_share::Frame;
+
+// Trình duyệt chỉ cần <img src="http://host:port/stream.mjpg">
+pub const STREAM_PATH: &str = "/stream.mjpg";
+const BOUNDARY: &str = "frame";
+const MAX_REQUEST_BYTES: usize = 8192;
+
+// Đọc tới hết header, trả về path của request GET
+pub(crate) async fn read_request_path<S>(stream: &mut S, wait: Duration) -> Option<String>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    let read_head = async {
+        while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+            let n = stream.read(&mut buf).await.ok()?;
+            if n == 0 || head.len() + n > MAX_REQUEST_BYTES {
+                return None;
+            }
+            head.extend_from_slice(&buf[..n]);
+        }
+        Some(())
+    };
+    tokio::time::timeout(wait, read_head).await.ok()??;
+
+    let head = String::from_utf8_lossy(&head);
+    let mut parts = head.lines().next()?.split_whitespace();
+    if parts.next()? != "GET" {
+        return None;
+    }
+    // Bỏ query string, vd /stream.mjpg?t=123 để tránh cache
+    let target = parts.next()?;
+    Some(target.split('?').next().unwrap_or(target).to_string())
+}
+
+async fn write_status<S>(stream: &mut S, status: &str, extra_headers: &str)
+where
+    S: AsyncWrite + Unpin,
+{
+    let response = format!(
+        "HTTP/1.1 {}\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n",
+        status, extra_headers
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+// Đã đủ client: 503 kèm Retry-After giống retry-after của Close frame WebSocket
+pub async fn reject<S>(mut stream: S, reason: CloseReason, wait: Duration)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    if read_request_path(&mut stream, wait).await.is_none() {
+        return;
+    }
+    let retry_after = reason
+        .retry_after_secs()
+        .map(|secs| format!("Retry-After: {}\r\n", secs))
+        .unwrap_or_default();
+    write_status(&mut stream, "503 Service Unavailable", &retry_after).await;
+}
+
+async fn write_part<S>(stream: &mut S, frame: &Frame) -> std::io::Result<usize>
+where
+    S: AsyncWrite + Unpin,
+{
+    let header = format!(
+        "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+        BOUNDARY,
+        frame.jpeg.len()
+    );
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(&frame.jpeg).await?;
+    stream.write_all(b"\r\n").await?;
+    stream.flush().await?;
+    Ok(header.len() + frame.jpeg.len() + 2)
+}
+
+// Gửi multipart/x-mixed-replace cho tới khi trình duyệt đóng kết nối hoặc server dừng.
+// allow được hỏi trước mỗi part (false = bỏ frame vì giới hạn băng thông), on_sent nhận số byte
+// mỗi part để tính vào băng thông chung.
+pub async fn serve<S>(
+    mut stream: S,
+    mut frames: broadcast::Receiver<Arc<Frame>>,
+    latest: Option<Arc<Frame>>,
+    mut shutdown_rx: broadcast::Receiver<()>,
+    wait: Duration,
+    allow: impl Fn(usize) -> bool,
+    on_sent: impl Fn(usize),
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let Some(path) = read_request_path(&mut stream, wait).await else {
+        write_status(&mut stream, "400 Bad Request", "").await;
+        return;
+    };
+    if path != STREAM_PATH {
+        write_status(&mut stream, "404 Not Found", "").await;
+        return;
+    }
+
+    let header = format!(
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: multipart/x-mixed-replace; boundary={}\r\n\
+         Cache-Control: no-cache, no-store\r\n\
+         Pragma: no-cache\r\n\
+         Connection: close\r\n\r\n",
+        BOUNDARY
+    );
+    if stream.write_all(header.as_bytes()).await.is_err() {
+        return;
+    }
+
+    // Gửi ngay frame mới nhất để ảnh hiện lên trước tick kế tiếp
+    if let Some(frame) = latest.filter(|f| allow(f.jpeg.len())) {
+        match write_part(&mut stream, &frame).await {
+            Ok(bytes) => on_sent(bytes),
+            Err(_) => return,
+        }
+    }
+
+    loop {
+        tokio::select! {
+            frame = frames.recv() => {
+                let frame = match frame {
+                    Ok(frame) => frame,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(_) => break,
+                };
+                if !allow(frame.jpeg.len()) {
+                    continue;
+                }
+                match write_part(&mut stream, &frame).await {
+                    Ok(bytes) => on_sent(bytes),
+                    Err(_) => break,
+                }
+            }
+            _ = shutdown_rx.recv() => break,
+        }
+    }
+    let _ = stream.shutdown().await;
+}
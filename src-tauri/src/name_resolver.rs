@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OnceCell, Semaphore};
+use tokio::time::timeout;
+
+use crate::netbios;
+
+const NAME_TTL: Duration = Duration::from_secs(600);
+// Host chưa có tên thì hỏi lại sớm hơn, máy có thể vừa đăng ký DNS hoặc bật NetBIOS
+const MISSING_NAME_TTL: Duration = Duration::from_secs(60);
+const MAX_CONCURRENT_LOOKUPS: usize = 32;
+
+// Tên riêng từ từng nguồn, inspect_host hiển thị cả hai
+#[derive(Clone, Default)]
+pub struct HostNames {
+    pub dns: Option<String>,
+    pub netbios: Option<String>,
+}
+
+impl HostNames {
+    // Ưu tiên PTR, máy Windows trong workgroup thường chỉ có tên NetBIOS
+    pub fn preferred(&self) -> Option<String> {
+        self.dns.clone().or_else(|| self.netbios.clone())
+    }
+}
+
+struct Resolved {
+    names: HostNames,
+    at: Instant,
+}
+
+impl Resolved {
+    fn fresh(&self) -> bool {
+        let ttl = if self.names.preferred().is_some() {
+            NAME_TTL
+        } else {
+            MISSING_NAME_TTL
+        };
+        self.at.elapsed() < ttl
+    }
+}
+
+// Cache tên theo IP dùng chung giữa các lần quét. Nhiều lần quét hỏi cùng một IP thì chỉ
+// một truy vấn DNS/NBNS thực sự chạy, các lần khác chờ chung kết quả.
+struct NameResolver {
+    entries: Mutex<HashMap<String, Arc<OnceCell<Resolved>>>>,
+    permits: Semaphore,
+}
+
+impl NameResolver {
+    fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            permits: Semaphore::new(MAX_CONCURRENT_LOOKUPS),
+        }
+    }
+
+    async fn names(&self, ip: &str) -> HostNames {
+        let cell = {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            let entry = entries.entry(ip.to_string()).or_default();
+            if entry.get().is_some_and(|resolved| !resolved.fresh()) {
+                *entry = Arc::default();
+            }
+            Arc::clone(entry)
+        };
+        let resolved = cell
+            .get_or_init(|| async {
+                let _permit = self.permits.acquire().await.ok();
+                Resolved {
+                    names: resolve_names(ip).await,
+                    at: Instant::now(),
+                }
+            })
+            .await;
+        resolved.names.clone()
+    }
+
+    fn clear(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.drain().count()
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref NAME_RESOLVER: NameResolver = NameResolver::new();
+}
+
+pub async fn lookup(ip: &str) -> Option<String> {
+    NAME_RESOLVER.names(ip).await.preferred()
+}
+
+pub async fn lookup_names(ip: &str) -> HostNames {
+    NAME_RESOLVER.names(ip).await
+}
+
+// Trả về số IP đã xoá khỏi cache
+#[tauri::command]
+pub fn clear_name_cache() -> usize {
+    NAME_RESOLVER.clear()
+}
+
+// Hỏi song song nên host chỉ có NetBIOS không phải chờ DNS hết timeout
+async fn resolve_names(ip: &str) -> HostNames {
+    let (dns, netbios) =
+        tokio::join!(reverse_dns(ip), netbios::query_name(ip, Duration::from_millis(500)));
+    HostNames { dns, netbios }
+}
+
+async fn reverse_dns(ip: &str) -> Option<String> {
+    let addr: IpAddr = ip.parse().ok()?;
+    let lookup = tokio::task::spawn_blocking(move || dns_lookup::lookup_addr(&addr));
+    let name = timeout(Duration::from_secs(1), lookup).await.ok()?.ok()?.ok()?;
+
+    // Không có bản ghi PTR thì lookup_addr trả lại chính IP
+    if name == ip {
+        None
+    } else {
+        Some(name)
+    }
+}
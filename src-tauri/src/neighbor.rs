@@ -0,0 +1,224 @@
+use std::io::ErrorKind;
+use tokio::process::Command;
+
+use crate::oui;
+
+// (ip, hostname, mac) cho các host trong bảng ARP/neighbor của hệ điều hành
+pub type NeighborEntry = (String, Option<String>, Option<String>);
+
+// Chỉ lấy dải IP private, bỏ multicast/broadcast và các mạng lạ
+fn is_lan_ip(ip: &str) -> bool {
+    ip.starts_with("192.") || ip.starts_with("10.") || ip.starts_with("172.")
+}
+
+// Entry broadcast của subnet (x.x.x.255) không phải host
+fn is_host_mac(mac: &str) -> bool {
+    mac != "ff:ff:ff:ff:ff:ff"
+}
+
+// macOS/BSD và net-tools trên Linux: "host (192.168.1.2) at aa:bb:cc:dd:ee:ff on en0".
+// Entry chưa hoàn tất in "(incomplete)" hoặc "<incomplete>" thay cho MAC nên bị bỏ
+fn parse_arp_bsd(output: &str) -> Vec<NeighborEntry> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let start = line.find('(')?;
+            let end = line.find(')')?;
+            let ip = line.get(start + 1..end)?;
+            if !is_lan_ip(ip) {
+                return None;
+            }
+            let hostname = if line.starts_with('?') {
+                None
+            } else {
+                line.split_whitespace().next().map(|s| s.to_string())
+            };
+            let mut parts = line.split_whitespace();
+            let mac = parts
+                .find(|p| *p == "at")
+                .and(parts.next())
+                .and_then(oui::normalize_mac)?;
+            is_host_mac(&mac).then(|| (ip.to_string(), hostname, Some(mac)))
+        })
+        .collect()
+}
+
+// Windows: "  192.168.1.1           aa-bb-cc-dd-ee-ff     dynamic"
+fn parse_arp_windows(output: &str) -> Vec<NeighborEntry> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let [ip, mac, ..] = parts.as_slice() else {
+                return None;
+            };
+            if !is_lan_ip(ip) || ip.parse::<std::net::IpAddr>().is_err() {
+                return None;
+            }
+            let mac = oui::normalize_mac(mac).filter(|m| is_host_mac(m));
+            mac.map(|mac| (ip.to_string(), None, Some(mac)))
+        })
+        .collect()
+}
+
+// iproute2: "192.168.1.1 dev wlan0 lladdr aa:bb:cc:dd:ee:ff REACHABLE".
+// Entry INCOMPLETE/FAILED không có lladdr nên bị bỏ
+fn parse_ip_neigh(output: &str) -> Vec<NeighborEntry> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let ip = parts.next()?;
+            if !is_lan_ip(ip) {
+                return None;
+            }
+            let mac = parts
+                .find(|p| *p == "lladdr")
+                .and(parts.next())
+                .and_then(oui::normalize_mac)?;
+            is_host_mac(&mac).then(|| (ip.to_string(), None, Some(mac)))
+        })
+        .collect()
+}
+
+// /proc/net/arp: "192.168.1.1  0x1  0x2  aa:bb:cc:dd:ee:ff  *  wlan0", dòng đầu là header.
+// Flags 0x0 = entry chưa hoàn tất
+fn parse_proc_net_arp(content: &str) -> Vec<NeighborEntry> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let [ip, _, flags, mac, ..] = parts.as_slice() else {
+                return None;
+            };
+            if !is_lan_ip(ip) || *flags == "0x0" {
+                return None;
+            }
+            let mac = oui::normalize_mac(mac).filter(|m| is_host_mac(m));
+            mac.map(|mac| (ip.to_string(), None, Some(mac)))
+        })
+        .collect()
+}
+
+// None = không có lệnh này trên máy, để thử công cụ kế tiếp
+async fn run(program: &str, args: &[&str]) -> Option<String> {
+    match Command::new(program).args(args).output().await {
+        Ok(output) => Some(String::from_utf8_lossy(&output.stdout).to_string()),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(_) => Some(String::new()),
+    }
+}
+
+// Thử lần lượt `arp -a`, `ip neigh show` rồi /proc/net/arp (Linux tối giản không có net-tools).
+// Windows luôn có arp (getmac chỉ liệt kê card mạng của chính máy nên không dùng được).
+// Không có công cụ nào thì trả về rỗng để các bước quét khác vẫn chạy.
+pub async fn read_table() -> Vec<NeighborEntry> {
+    if let Some(output) = run("arp", &["-a"]).await {
+        return if cfg!(target_os = "windows") {
+            parse_arp_windows(&output)
+        } else {
+            parse_arp_bsd(&output)
+        };
+    }
+    if !cfg!(target_os = "linux") {
+        return Vec::new();
+    }
+    if let Some(output) = run("ip", &["neigh", "show"]).await {
+        return parse_ip_neigh(&output);
+    }
+    match tokio::fs::read_to_string("/proc/net/arp").await {
+        Ok(content) => parse_proc_net_arp(&content),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(ip: &str, hostname: Option<&str>, mac: &str) -> NeighborEntry {
+        (ip.to_string(), hostname.map(str::to_string), Some(mac.to_string()))
+    }
+
+    #[test]
+    fn arp_bsd_macos() {
+        let output = "\
+? (192.168.1.1) at a4:2b:b0:12:34:56 on en0 ifscope [ethernet]
+nas.lan (192.168.1.20) at 0:11:32:a:b:c on en0 ifscope [ethernet]
+? (192.168.1.77) at (incomplete) on en0 ifscope [ethernet]
+? (192.168.1.255) at ff:ff:ff:ff:ff:ff on en0 ifscope [ethernet]
+? (224.0.0.251) at 1:0:5e:0:0:fb on en0 ifscope permanent [ethernet]
+";
+        assert_eq!(
+            parse_arp_bsd(output),
+            vec![
+                entry("192.168.1.1", None, "a4:2b:b0:12:34:56"),
+                entry("192.168.1.20", Some("nas.lan"), "00:11:32:0a:0b:0c"),
+            ]
+        );
+    }
+
+    #[test]
+    fn arp_bsd_net_tools() {
+        let output = "\
+_gateway (10.0.0.1) at 52:54:00:12:35:02 [ether] on eth0
+? (10.0.0.9) at <incomplete> on eth0
+";
+        assert_eq!(
+            parse_arp_bsd(output),
+            vec![entry("10.0.0.1", Some("_gateway"), "52:54:00:12:35:02")]
+        );
+    }
+
+    #[test]
+    fn arp_windows() {
+        let output = "\r
+Interface: 192.168.1.10 --- 0xb\r
+  Internet Address      Physical Address      Type\r
+  192.168.1.1           a4-2b-b0-12-34-56     dynamic\r
+  192.168.1.255         ff-ff-ff-ff-ff-ff     static\r
+  224.0.0.22            01-00-5e-00-00-16     static\r
+  239.255.255.250       01-00-5e-7f-ff-fa     static\r
+";
+        assert_eq!(
+            parse_arp_windows(output),
+            vec![entry("192.168.1.1", None, "a4:2b:b0:12:34:56")]
+        );
+    }
+
+    #[test]
+    fn ip_neigh() {
+        let output = "\
+192.168.1.1 dev wlan0 lladdr a4:2b:b0:12:34:56 REACHABLE
+192.168.1.20 dev wlan0 lladdr 00:11:32:0a:0b:0c STALE
+192.168.1.77 dev wlan0 INCOMPLETE
+192.168.1.78 dev wlan0 FAILED
+fe80::1 dev wlan0 lladdr a4:2b:b0:12:34:56 router REACHABLE
+";
+        assert_eq!(
+            parse_ip_neigh(output),
+            vec![
+                entry("192.168.1.1", None, "a4:2b:b0:12:34:56"),
+                entry("192.168.1.20", None, "00:11:32:0a:0b:0c"),
+            ]
+        );
+    }
+
+    #[test]
+    fn proc_net_arp() {
+        let content = "\
+IP address       HW type     Flags       HW address            Mask     Device
+192.168.1.1      0x1         0x2         a4:2b:b0:12:34:56     *        wlan0
+192.168.1.77     0x1         0x0         00:00:00:00:00:00     *        wlan0
+172.17.0.2       0x1         0x2         02:42:ac:11:00:02     *        docker0
+";
+        assert_eq!(
+            parse_proc_net_arp(content),
+            vec![
+                entry("192.168.1.1", None, "a4:2b:b0:12:34:56"),
+                entry("172.17.0.2", None, "02:42:ac:11:00:02"),
+            ]
+        );
+    }
+}
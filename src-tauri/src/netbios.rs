@@ -0,0 +1,81 @@
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+const NBNS_PORT: u16 = 137;
+
+// Node status request (NBSTAT) cho tên "*"
+fn node_status_request(transaction_id: u16) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(50);
+    packet.extend_from_slice(&transaction_id.to_be_bytes());
+    // flags = 0, 1 question
+    packet.extend_from_slice(&[0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+    // Tên 16 byte ("*" + padding 0x00) mã hoá first-level: mỗi nibble + 'A'
+    packet.push(0x20);
+    let mut name = [0u8; 16];
+    name[0] = b'*';
+    for byte in name {
+        packet.push(b'A' + (byte >> 4));
+        packet.push(b'A' + (byte & 0x0f));
+    }
+    packet.push(0x00);
+
+    // Type NBSTAT, class IN
+    packet.extend_from_slice(&[0x00, 0x21, 0x00, 0x01]);
+    packet
+}
+
+// Lấy tên máy (suffix 0x00, không phải group) từ NBSTAT response
+fn parse_node_status(data: &[u8]) -> Option<String> {
+    let mut pos = 12;
+
+    // Bỏ qua tên trong answer (pointer nén hoặc chuỗi label)
+    if *data.get(pos)? & 0xc0 == 0xc0 {
+        pos += 2;
+    } else {
+        while *data.get(pos)? != 0 {
+            pos += *data.get(pos)? as usize + 1;
+        }
+        pos += 1;
+    }
+
+    // type(2) class(2) ttl(4) rdlength(2)
+    pos += 10;
+    let count = *data.get(pos)? as usize;
+    pos += 1;
+
+    for _ in 0..count {
+        let entry = data.get(pos..pos + 18)?;
+        let suffix = entry[15];
+        let flags = u16::from_be_bytes([entry[16], entry[17]]);
+        let is_group = flags & 0x8000 != 0;
+
+        if suffix == 0x00 && !is_group {
+            let name = String::from_utf8_lossy(&entry[..15]).trim_end().to_string();
+            if !name.is_empty() {
+                return Some(name);
+            }
+        }
+        pos += 18;
+    }
+
+    None
+}
+
+pub async fn query_name(ip: &str, wait: Duration) -> Option<String> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await.ok()?;
+    socket.connect((ip, NBNS_PORT)).await.ok()?;
+
+    let transaction_id = std::process::id() as u16;
+    socket.send(&node_status_request(transaction_id)).await.ok()?;
+
+    let mut buf = [0u8; 1024];
+    let n = timeout(wait, socket.recv(&mut buf)).await.ok()?.ok()?;
+    let data = &buf[..n];
+
+    if data.len() < 2 || data[..2] != transaction_id.to_be_bytes() {
+        return None;
+    }
+    parse_node_status(data)
+}
@@ -0,0 +1,24 @@
+// Đoán hệ điều hành từ TTL của ping reply.
+// TTL ban đầu thường là 64 (Linux/macOS/Android), 128 (Windows) hoặc 255 (router, switch);
+// mỗi hop giảm 1 nên lấy mốc nhỏ nhất >= TTL nhận được.
+const TTL_HINTS: &[(u8, &str)] = &[(64, "Linux/macOS"), (128, "Windows"), (255, "Network device")];
+
+// Linux/macOS: "64 bytes from 192.168.1.1: icmp_seq=1 ttl=64 time=0.5 ms"
+// Windows:     "Reply from 192.168.1.1: bytes=32 time<1ms TTL=128"
+pub fn parse_ttl(output: &str) -> Option<u8> {
+    output.split_whitespace().find_map(|token| {
+        let (key, value) = token.split_once('=')?;
+        if key.eq_ignore_ascii_case("ttl") {
+            value.parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+pub fn guess_from_ttl(ttl: u8) -> Option<String> {
+    TTL_HINTS
+        .iter()
+        .find(|(initial, _)| ttl <= *initial)
+        .map(|(_, os)| os.to_string())
+}
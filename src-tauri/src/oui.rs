@@ -0,0 +1,66 @@
+// Bảng OUI rút gọn cho các hãng hay gặp trong mạng LAN
+const OUI_VENDORS: &[(&str, &str)] = &[
+    ("00:00:0c", "Cisco"),
+    ("00:03:93", "Apple"),
+    ("00:0c:29", "VMware"),
+    ("00:11:32", "Synology"),
+    ("00:14:22", "Dell"),
+    ("00:15:5d", "Microsoft Hyper-V"),
+    ("00:16:6c", "Samsung"),
+    ("00:1b:21", "Intel"),
+    ("00:1b:78", "HP"),
+    ("00:50:56", "VMware"),
+    ("00:e0:4c", "Realtek"),
+    ("08:00:27", "VirtualBox"),
+    ("24:a4:3c", "Ubiquiti"),
+    ("50:c7:bf", "TP-Link"),
+    ("52:54:00", "QEMU/KVM"),
+    ("64:09:80", "Xiaomi"),
+    ("b8:27:eb", "Raspberry Pi"),
+    ("dc:a6:32", "Raspberry Pi"),
+    ("e4:5f:01", "Raspberry Pi"),
+    ("f4:f5:d8", "Google"),
+    ("fc:ec:da", "Ubiquiti"),
+];
+
+// macOS in "0:c:29:..." còn Windows dùng "-", đưa về dạng "00:0c:29:..."
+pub fn normalize_mac(mac: &str) -> Option<String> {
+    let octets: Vec<String> = mac
+        .split([':', '-'])
+        .map(|o| format!("{:0>2}", o.to_lowercase()))
+        .collect();
+
+    let valid = octets.len() == 6
+        && octets
+            .iter()
+            .all(|o| o.len() == 2 && o.chars().all(|c| c.is_ascii_hexdigit()));
+    if valid {
+        Some(octets.join(":"))
+    } else {
+        None
+    }
+}
+
+pub fn lookup_vendor(mac: &str) -> Option<String> {
+    let normalized = normalize_mac(mac)?;
+    let prefix = &normalized[..8];
+
+    // Chỉ tra bảng, không lọc bit locally-administered trước: 52:54:00 của QEMU/KVM có bit
+    // này, còn MAC ngẫu nhiên (privacy) vốn không trùng OUI nào trong bảng
+    OUI_VENDORS
+        .iter()
+        .find(|(oui, _)| *oui == prefix)
+        .map(|(_, vendor)| vendor.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qemu_prefix_is_found_despite_the_locally_administered_bit() {
+        assert_eq!(lookup_vendor("52:54:00:12:34:56").as_deref(), Some("QEMU/KVM"));
+        assert_eq!(lookup_vendor("00-0C-29-AB-CD-EF").as_deref(), Some("VMware"));
+        assert_eq!(lookup_vendor("da:a1:19:00:00:01"), None);
+    }
+}
@@ -0,0 +1,98 @@
+use serde::Serialize;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::os_guess;
+
+// Chờ mỗi reply tối đa chừng này (ms)
+const REPLY_WAIT_MS: u64 = 500;
+// iputils trên Linux nhận -W theo giây (làm tròn lên), Windows -w và macOS -W theo ms
+const REPLY_WAIT_SECS_LINUX: u64 = REPLY_WAIT_MS.div_ceil(1000);
+// ping gửi 1 gói/giây, cộng thêm thời gian chờ gói cuối
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+pub const MAX_PING_COUNT: u32 = 20;
+
+pub struct PingReply {
+    pub success: bool,
+    pub ttl: Option<u8>,
+    // Round-trip của từng reply nhận được
+    pub rtts_ms: Vec<f64>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct PingLatency {
+    reachable: bool,
+    avg_ms: Option<f64>,
+    loss_pct: f64,
+}
+
+impl PingLatency {
+    pub fn avg_ms(&self) -> Option<f64> {
+        self.avg_ms
+    }
+}
+
+// Linux/macOS: "... ttl=64 time=0.512 ms"
+// Windows:     "... bytes=32 time=12ms TTL=128" hoặc "time<1ms"
+pub fn parse_rtts(output: &str) -> Vec<f64> {
+    output
+        .split_whitespace()
+        .filter_map(|token| {
+            let lower = token.to_ascii_lowercase();
+            let value = lower.strip_prefix("time=").or_else(|| lower.strip_prefix("time<"))?;
+            value.trim_end_matches("ms").parse().ok()
+        })
+        .collect()
+}
+
+fn reply_wait() -> Duration {
+    if cfg!(target_os = "linux") {
+        Duration::from_secs(REPLY_WAIT_SECS_LINUX)
+    } else {
+        Duration::from_millis(REPLY_WAIT_MS)
+    }
+}
+
+// Hết thời gian mà ping chưa xong thì coi như mất hết gói
+pub fn unreachable() -> PingReply {
+    PingReply {
+        success: false,
+        ttl: None,
+        rtts_ms: Vec::new(),
+    }
+}
+
+// kill_on_drop để ping bị timeout hoặc lượt quét bị hủy không còn chạy nền
+pub async fn ping(ip: &str, count: u32) -> std::io::Result<PingReply> {
+    let count = count.to_string();
+    let args = if cfg!(target_os = "windows") {
+        ["-n", &count, "-w", &REPLY_WAIT_MS.to_string(), ip]
+    } else if cfg!(target_os = "linux") {
+        ["-c", &count, "-W", &REPLY_WAIT_SECS_LINUX.to_string(), ip]
+    } else {
+        ["-c", &count, "-W", &REPLY_WAIT_MS.to_string(), ip]
+    };
+    let output = Command::new("ping").args(args).kill_on_drop(true).output().await?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(PingReply {
+        success: output.status.success(),
+        ttl: os_guess::parse_ttl(&stdout),
+        rtts_ms: parse_rtts(&stdout),
+    })
+}
+
+// Tổng thời gian tối đa cho `count` gói, để lệnh ping treo cũng không chặn UI
+pub fn deadline(count: u32) -> Duration {
+    PING_INTERVAL * count + reply_wait() + Duration::from_secs(1)
+}
+
+pub fn summarize(count: u32, reply: &PingReply) -> PingLatency {
+    let received = reply.rtts_ms.len().min(count as usize);
+    let avg_ms = (received > 0).then(|| reply.rtts_ms.iter().sum::<f64>() / received as f64);
+    PingLatency {
+        reachable: received > 0,
+        avg_ms,
+        loss_pct: (count as usize - received) as f64 * 100.0 / count.max(1) as f64,
+    }
+}
@@ -0,0 +1,242 @@
+use serde::Serialize;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, ChildStderr, Command};
+use tokio::sync::{broadcast, oneshot, watch, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+use crate::error::AppError;
+use crate::screen_share::{subscribe_frames, QualityTier, FRAME_INTERVAL_MS};
+
+// -loglevel error nên stderr chỉ có lỗi, vài dòng cuối là đủ biết lý do
+const STDERR_TAIL_BYTES: usize = 4096;
+// ffmpeg đã thoát thì stderr đóng gần như ngay
+const STDERR_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+struct ActiveRecording {
+    path: String,
+    child: Child,
+    stop_tx: oneshot::Sender<()>,
+    task: JoinHandle<u64>,
+    started: Instant,
+    stderr: StderrTail,
+}
+
+// Phần cuối stderr của ffmpeg, đọc suốt phiên để pipe không bị đầy
+#[derive(Clone)]
+struct StderrTail {
+    bytes: Arc<std::sync::Mutex<Vec<u8>>>,
+    // true khi đã đọc tới EOF (ffmpeg đã thoát)
+    done: watch::Receiver<bool>,
+}
+
+impl StderrTail {
+    fn collect(mut stderr: ChildStderr) -> Self {
+        let bytes = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let (done_tx, done) = watch::channel(false);
+        let buffer = Arc::clone(&bytes);
+        tokio::spawn(async move {
+            let mut chunk = [0u8; 1024];
+            while let Ok(n @ 1..) = stderr.read(&mut chunk).await {
+                let mut buffer = buffer.lock().unwrap_or_else(|e| e.into_inner());
+                buffer.extend_from_slice(&chunk[..n]);
+                let excess = buffer.len().saturating_sub(STDERR_TAIL_BYTES);
+                buffer.drain(..excess);
+            }
+            let _ = done_tx.send(true);
+        });
+        Self { bytes, done }
+    }
+
+    async fn text(&mut self) -> String {
+        let _ = timeout(STDERR_DRAIN_TIMEOUT, self.done.wait_for(|done| *done)).await;
+        let bytes = self.bytes.lock().unwrap_or_else(|e| e.into_inner());
+        String::from_utf8_lossy(&bytes).trim().to_string()
+    }
+
+    async fn error(&mut self, message: String) -> AppError {
+        let stderr = self.text().await;
+        let message = if stderr.is_empty() {
+            message
+        } else {
+            format!("{}: {}", message, stderr)
+        };
+        AppError::new("Ffmpeg", message, false)
+    }
+}
+
+// ffmpeg thoát giữa chừng (hết dung lượng, thiếu encoder...), không phải chờ tới stop_recording
+#[derive(Serialize, Clone)]
+struct RecordingFailed {
+    path: String,
+    error: AppError,
+}
+
+#[derive(Serialize, Clone)]
+pub struct RecordingSummary {
+    path: String,
+    size_bytes: u64,
+    duration_ms: u64,
+    frames: u64,
+}
+
+lazy_static::lazy_static! {
+    static ref RECORDING: Mutex<Option<ActiveRecording>> = Mutex::new(None);
+}
+
+// Chọn codec theo đuôi file: .webm dùng VP9, còn lại H.264 trong MP4
+fn codec_args(path: &str) -> &'static [&'static str] {
+    let is_webm = Path::new(path)
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("webm"))
+        .unwrap_or(false);
+
+    if is_webm {
+        &["-c:v", "libvpx-vp9", "-b:v", "0", "-crf", "32"]
+    } else {
+        &["-c:v", "libx264", "-preset", "veryfast", "-pix_fmt", "yuv420p"]
+    }
+}
+
+// Ghi luồng capture của screen server `id`, bỏ trống = "default"
+#[tauri::command]
+pub async fn start_recording(
+    app: AppHandle,
+    path: String,
+    id: Option<String>,
+) -> Result<(), AppError> {
+    let mut recording = RECORDING.lock().await;
+    if recording.is_some() {
+        return Err(AppError::new(
+            "AlreadyRunning",
+            "Recording already in progress",
+            false,
+        ));
+    }
+
+    let framerate = (1000 / FRAME_INTERVAL_MS).to_string();
+
+    // Pipe từng frame JPEG vào ffmpeg
+    let mut child = Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error", "-f", "image2pipe"])
+        .args(["-framerate", &framerate, "-c:v", "mjpeg", "-i", "-"])
+        .args(codec_args(&path))
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| AppError::new("Ffmpeg", format!("Failed to start ffmpeg: {}", e), false))?;
+
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| AppError::new("Ffmpeg", "Failed to open ffmpeg stdin", true))?;
+    let stderr = child
+        .stderr
+        .take()
+        .map(StderrTail::collect)
+        .ok_or_else(|| AppError::new("Ffmpeg", "Failed to open ffmpeg stderr", true))?;
+    let mut task_stderr = stderr.clone();
+    let task_path = path.clone();
+    let mut frames = subscribe_frames(id.as_deref(), QualityTier::High);
+    let (stop_tx, mut stop_rx) = oneshot::channel::<()>();
+
+    let task = tokio::spawn(async move {
+        let mut count = 0u64;
+        // ffmpeg nhận đúng -framerate frame mỗi giây theo đồng hồ thật: màn hình đứng yên
+        // (capture_on_change, idle pause) không có frame mới thì ghi lại frame cũ, video không
+        // bị nén thời gian. Ghi chậm thì tick dồn lại gửi bù
+        let mut ticker = tokio::time::interval(Duration::from_millis(FRAME_INTERVAL_MS));
+        let mut last = None;
+        loop {
+            tokio::select! {
+                _ = &mut stop_rx => break,
+                frame = frames.recv() => {
+                    match frame {
+                        Ok(frame) => last = Some(frame),
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(_) => break,
+                    }
+                }
+                _ = ticker.tick() => {
+                    let Some(frame) = &last else {
+                        continue;
+                    };
+                    if stdin.write_all(&frame.jpeg).await.is_err() {
+                        let message = "ffmpeg stopped while recording".to_string();
+                        let error = task_stderr.error(message).await;
+                        let failed = RecordingFailed { path: task_path, error };
+                        let _ = app.emit("recording-failed", failed);
+                        break;
+                    }
+                    count += 1;
+                }
+            }
+        }
+        // Đóng stdin để ffmpeg ghi nốt và kết thúc file
+        let _ = stdin.shutdown().await;
+        count
+    });
+
+    *recording = Some(ActiveRecording {
+        path,
+        child,
+        stop_tx,
+        task,
+        started: Instant::now(),
+        stderr,
+    });
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn stop_recording() -> Result<RecordingSummary, AppError> {
+    let active = RECORDING
+        .lock()
+        .await
+        .take()
+        .ok_or_else(|| AppError::not_found("No recording in progress"))?;
+
+    let ActiveRecording {
+        path,
+        mut child,
+        stop_tx,
+        task,
+        started,
+        mut stderr,
+    } = active;
+
+    let _ = stop_tx.send(());
+    let frames = task.await.unwrap_or(0);
+
+    match timeout(Duration::from_secs(10), child.wait()).await {
+        Ok(Ok(status)) if status.success() => {}
+        Ok(Ok(status)) => {
+            return Err(stderr.error(format!("ffmpeg exited with {}", status)).await);
+        }
+        Ok(Err(e)) => return Err(e.into()),
+        Err(_) => {
+            let _ = child.kill().await;
+            return Err(AppError::new("Ffmpeg", "ffmpeg did not finish in time", true));
+        }
+    }
+
+    let size_bytes = tokio::fs::metadata(&path).await?.len();
+
+    // Frame được ghi theo đồng hồ thật nên độ dài video là thời gian ghi
+    let duration_ms = started.elapsed().as_millis() as u64;
+
+    Ok(RecordingSummary {
+        path,
+        size_bytes,
+        duration_ms,
+        frames,
+    })
+}
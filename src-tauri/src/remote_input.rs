@@ -0,0 +1,184 @@
+use enigo::{Button, Coordinate, Direction, Enigo, Key, Keyboard, Mouse, Settings};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::sync::mpsc;
+
+use crate::screen_share::frame_geometry;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MouseAction {
+    Move,
+    Down,
+    Up,
+    Click,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyAction {
+    Down,
+    Up,
+    Press,
+}
+
+#[derive(Debug, Clone)]
+pub enum InputEvent {
+    Mouse {
+        x: f64,
+        y: f64,
+        button: Option<MouseButton>,
+        action: MouseAction,
+    },
+    Key {
+        code: String,
+        action: KeyAction,
+    },
+}
+
+lazy_static::lazy_static! {
+    // Enigo không phải lúc nào cũng Send nên giữ nó trên một thread riêng
+    static ref INPUT_TX: mpsc::Sender<InputEvent> = spawn_input_worker();
+}
+
+fn spawn_input_worker() -> mpsc::Sender<InputEvent> {
+    let (tx, rx) = mpsc::channel::<InputEvent>();
+
+    std::thread::spawn(move || {
+        let mut enigo = match Enigo::new(&Settings::default()) {
+            Ok(enigo) => enigo,
+            Err(e) => {
+                log::warn!("remote input disabled: {}", e);
+                return;
+            }
+        };
+
+        while let Ok(event) = rx.recv() {
+            if let Err(e) = apply_event(&mut enigo, event) {
+                log::warn!("remote input error: {}", e);
+            }
+        }
+    });
+
+    tx
+}
+
+pub fn inject(event: InputEvent) {
+    let _ = INPUT_TX.send(event);
+}
+
+thread_local! {
+    // Mỗi thread capture tự giữ một Enigo chỉ để đọc vị trí con trỏ
+    static CURSOR_READER: RefCell<Option<Enigo>> = const { RefCell::new(None) };
+}
+
+// Toạ độ desktop của con trỏ, None nếu hệ thống không cho đọc (vd Wayland hạn chế)
+pub(crate) fn cursor_position() -> Option<(i32, i32)> {
+    CURSOR_READER.with(|reader| {
+        let mut reader = reader.borrow_mut();
+        if reader.is_none() {
+            *reader = Enigo::new(&Settings::default()).ok();
+        }
+        reader.as_ref()?.location().ok()
+    })
+}
+
+fn apply_event(enigo: &mut Enigo, event: InputEvent) -> Result<(), String> {
+    match event {
+        InputEvent::Mouse { x, y, button, action } => {
+            // Viewer gửi toạ độ theo frame đã resize, chia lại và cộng gốc vùng capture
+            // (monitor phụ hoặc cửa sổ) để ra toạ độ desktop thật
+            let (screen_x, screen_y) = frame_geometry().to_desktop(x, y);
+            enigo
+                .move_mouse(screen_x, screen_y, Coordinate::Abs)
+                .map_err(|e| e.to_string())?;
+
+            let direction = match action {
+                MouseAction::Move => return Ok(()),
+                MouseAction::Down => Direction::Press,
+                MouseAction::Up => Direction::Release,
+                MouseAction::Click => Direction::Click,
+            };
+            let button = match button.unwrap_or(MouseButton::Left) {
+                MouseButton::Left => Button::Left,
+                MouseButton::Right => Button::Right,
+                MouseButton::Middle => Button::Middle,
+            };
+            enigo.button(button, direction).map_err(|e| e.to_string())
+        }
+        InputEvent::Key { code, action } => {
+            let key = map_key(&code).ok_or(format!("Unsupported key code: {}", code))?;
+            let direction = match action {
+                KeyAction::Down => Direction::Press,
+                KeyAction::Up => Direction::Release,
+                KeyAction::Press => Direction::Click,
+            };
+            enigo.key(key, direction).map_err(|e| e.to_string())
+        }
+    }
+}
+
+// Map theo KeyboardEvent.code của trình duyệt
+fn map_key(code: &str) -> Option<Key> {
+    if let Some(letter) = code.strip_prefix("Key") {
+        let c = letter.chars().next()?;
+        return Some(Key::Unicode(c.to_ascii_lowercase()));
+    }
+    if let Some(digit) = code.strip_prefix("Digit") {
+        return digit.chars().next().map(Key::Unicode);
+    }
+
+    let key = match code {
+        "Enter" | "NumpadEnter" => Key::Return,
+        "Escape" => Key::Escape,
+        "Backspace" => Key::Backspace,
+        "Tab" => Key::Tab,
+        "Space" => Key::Space,
+        "Delete" => Key::Delete,
+        "Home" => Key::Home,
+        "End" => Key::End,
+        "PageUp" => Key::PageUp,
+        "PageDown" => Key::PageDown,
+        "ArrowLeft" => Key::LeftArrow,
+        "ArrowRight" => Key::RightArrow,
+        "ArrowUp" => Key::UpArrow,
+        "ArrowDown" => Key::DownArrow,
+        "ShiftLeft" | "ShiftRight" => Key::Shift,
+        "ControlLeft" | "ControlRight" => Key::Control,
+        "AltLeft" | "AltRight" => Key::Alt,
+        "MetaLeft" | "MetaRight" => Key::Meta,
+        "F1" => Key::F1,
+        "F2" => Key::F2,
+        "F3" => Key::F3,
+        "F4" => Key::F4,
+        "F5" => Key::F5,
+        "F6" => Key::F6,
+        "F7" => Key::F7,
+        "F8" => Key::F8,
+        "F9" => Key::F9,
+        "F10" => Key::F10,
+        "F11" => Key::F11,
+        "F12" => Key::F12,
+        "Minus" => Key::Unicode('-'),
+        "Equal" => Key::Unicode('='),
+        "Comma" => Key::Unicode(','),
+        "Period" => Key::Unicode('.'),
+        "Slash" => Key::Unicode('/'),
+        "Semicolon" => Key::Unicode(';'),
+        "Quote" => Key::Unicode('\''),
+        "BracketLeft" => Key::Unicode('['),
+        "BracketRight" => Key::Unicode(']'),
+        "Backslash" => Key::Unicode('\\'),
+        "Backquote" => Key::Unicode('`'),
+        _ => return None,
+    };
+    Some(key)
+}
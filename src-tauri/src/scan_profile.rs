@@ -0,0 +1,60 @@
+use serde::Deserialize;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+// Một nút chỉnh cho người dùng: nhanh ở nhà, nhẹ nhàng ở mạng công ty có IDS
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanProfile {
+    Polite,
+    #[default]
+    Normal,
+    Aggressive,
+}
+
+pub struct ProfilePreset {
+    // Số probe cùng lúc cho ARP priming và scan_targets
+    pub concurrency: usize,
+    // Số host được quét TCP cùng lúc khi sweep subnet
+    pub sweep_concurrency: usize,
+    pub timeout_ms: u64,
+    // Chờ ngẫu nhiên 0..=probe_delay_ms trước mỗi probe, 0 = không chờ
+    pub probe_delay_ms: u64,
+}
+
+impl ScanProfile {
+    // Normal giữ nguyên giá trị trước khi có profile (sweep cả /24 cùng lúc)
+    pub fn preset(self) -> ProfilePreset {
+        match self {
+            ScanProfile::Polite => ProfilePreset {
+                concurrency: 8,
+                sweep_concurrency: 8,
+                timeout_ms: 800,
+                probe_delay_ms: 200,
+            },
+            ScanProfile::Normal => ProfilePreset {
+                concurrency: 64,
+                sweep_concurrency: 254,
+                timeout_ms: 500,
+                probe_delay_ms: 0,
+            },
+            ScanProfile::Aggressive => ProfilePreset {
+                concurrency: 256,
+                sweep_concurrency: 254,
+                timeout_ms: 250,
+                probe_delay_ms: 0,
+            },
+        }
+    }
+}
+
+// Giãn probe ra để không tạo burst đều đặn dễ bị IDS nhận ra
+pub async fn probe_delay(max_ms: u64) {
+    if max_ms == 0 {
+        return;
+    }
+    // RandomState mới có key ngẫu nhiên, đủ dùng mà không cần thêm crate rand
+    let jitter = RandomState::new().build_hasher().finish() % (max_ms + 1);
+    tokio::time::sleep(Duration::from_millis(jitter)).await;
+}
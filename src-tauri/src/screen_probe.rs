@@ -0,0 +1,101 @@
+use futures_util::{SinkExt, StreamExt};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::time::{timeout, Instant};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::error::AppError;
+use crate::stream_handshake::TierFrameSize;
+
+// Port mặc định frontend dùng khi mở screen server
+pub const DEFAULT_SCREEN_PORT: u16 = 9000;
+
+// Kết nối ws://, gửi handshake rỗng và chờ server trả {"type":"handshake"} (frame server gửi
+// trước handshake thì bỏ qua).
+// Trả về thời gian từ lúc gửi tới lúc nhận, dùng thay ping khi host chặn ICMP.
+// Server bật TLS (wss://) chưa được nhận ra.
+pub async fn probe(ip: IpAddr, port: u16, wait: Duration) -> Option<Duration> {
+    let url = format!("ws://{}/", SocketAddr::new(ip, port));
+    let exchange = async {
+        let (mut ws, _) = connect_async(url.as_str()).await.ok()?;
+        let sent_at = Instant::now();
+        let request = serde_json::json!({ "type": "handshake" });
+        ws.send(Message::Text(request.to_string())).await.ok()?;
+        loop {
+            if let Message::Text(text) = ws.next().await?.ok()? {
+                let reply = serde_json::from_str::<serde_json::Value>(&text).unwrap_or_default();
+                if reply["type"] == "handshake" {
+                    let rtt = sent_at.elapsed();
+                    let _ = ws.close(None).await;
+                    return Some(rtt);
+                }
+            }
+        }
+    };
+    timeout(wait, exchange).await.ok()?
+}
+
+fn network_error(e: impl std::fmt::Display) -> AppError {
+    AppError::new("Network", e.to_string(), true)
+}
+
+// Kết quả một lần đo: byte thực sự đã nhận (server chặn theo giới hạn của nó), thời gian nhận
+// và kích thước frame gần đây server báo trong handshake
+pub struct ThroughputSample {
+    pub bytes: usize,
+    pub elapsed: Duration,
+    pub frame_sizes: Vec<TierFrameSize>,
+}
+
+// Xin screen server gửi `bytes` byte ngay sau handshake và đếm thời gian nhận, tính từ lúc
+// nhận handshake
+pub async fn throughput(ip: IpAddr, port: u16, bytes: usize) -> Result<ThroughputSample, AppError> {
+    let url = format!("ws://{}/", SocketAddr::new(ip, port));
+    let (mut ws, _) = connect_async(url.as_str()).await.map_err(network_error)?;
+    let request = serde_json::json!({ "type": "handshake", "throughput_bytes": bytes });
+    ws.send(Message::Text(request.to_string())).await.map_err(network_error)?;
+    // Bỏ qua frame server gửi trước khi nhận handshake
+    let accepted = loop {
+        match ws.next().await {
+            Some(Ok(Message::Text(text))) => {
+                let reply = serde_json::from_str::<serde_json::Value>(&text).unwrap_or_default();
+                if reply["type"] == "handshake" {
+                    break reply;
+                }
+            }
+            // Server đầy hoặc đang giới hạn kết nối
+            Some(Ok(Message::Close(frame))) => {
+                let reason = frame.map(|f| f.reason.to_string()).unwrap_or_default();
+                return Err(network_error(format!("Screen server refused the test: {}", reason)));
+            }
+            Some(Ok(_)) => continue,
+            _ => break serde_json::Value::Null,
+        }
+    };
+    let frame_sizes = serde_json::from_value(accepted["frame_sizes"].clone()).unwrap_or_default();
+    let Some(expected) = accepted["throughput_bytes"].as_u64().map(|b| b as usize) else {
+        let message = "Screen server does not support throughput tests";
+        return Err(AppError::new("Unsupported", message, false));
+    };
+
+    let started = Instant::now();
+    let mut received = 0;
+    while received < expected {
+        match ws.next().await {
+            Some(Ok(Message::Binary(data))) => received += data.len(),
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+            Some(Ok(_)) => continue,
+        }
+    }
+    let elapsed = started.elapsed();
+    let _ = ws.close(None).await;
+    if received < expected {
+        return Err(network_error("Connection closed before the throughput test finished"));
+    }
+    Ok(ThroughputSample {
+        bytes: received,
+        elapsed,
+        frame_sizes,
+    })
+}
@@ -1,149 +1,2751 @@
 use base64::{engine::general_purpose::STANDARD, Engine};
+use fast_image_resize::{FilterType, ResizeAlg, ResizeOptions, Resizer};
 use futures_util::{SinkExt, StreamExt};
 use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, PngEncoder};
+use jpeg_encoder::{ColorType, Encoder, SamplingFactor};
+use image::{ImageEncoder, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::io::Cursor;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, Notify, OwnedSemaphorePermit, Semaphore};
+use tokio_rustls::TlsAcceptor;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
 use tokio_tungstenite::{accept_async, tungstenite::Message};
-use xcap::Monitor;
+use xcap::{Monitor, Window};
 
-static SERVER_RUNNING: AtomicBool = AtomicBool::new(false);
+use crate::activity::{ActivityTracker, ChangeBaseline};
+use crate::bind_addr::{bind_listener, resolve_bind_ip};
+use crate::buffer_pool::{BufferPool, Pooled};
+use crate::capture_source::{monitor_by_id, CaptureBackend, SourceKind};
+use crate::close_code::CloseReason;
+use crate::connections::Registration;
+use crate::error::{AppError, ServerError};
+use crate::follow_cursor::{CursorFollower, FollowCursor};
+use crate::frame_compression;
+use crate::heartbeat::{self, HeartbeatEvent};
+use crate::mjpeg;
+use crate::stream_handshake::{
+    ClientMessage, FrameHeader, HandshakeRequest, HandshakeResponse, OutputResolution,
+    ServerLimits, TierFrameSize,
+};
+use crate::tiles::{TileGrid, TileTracker};
+use crate::tls::{load_acceptor, ClientStream};
+use crate::watermark::{Corner, Watermark};
+
+// Tổng client của mọi server, cho health/status
+static ACTIVE_CLIENTS: AtomicUsize = AtomicUsize::new(0);
+
+// Không truyền id thì dùng server này, giữ cách dùng một server như trước
+const DEFAULT_SERVER_ID: &str = "default";
+
+// Frame gửi đi được thu nhỏ theo hệ số này
+pub(crate) const DOWNSCALE_FACTOR: u32 = 2;
+pub(crate) const FRAME_INTERVAL_MS: u64 = 100;
+// Cạnh dài nhất không quá mức này thì gửi nguyên kích thước (màn hình dọc cũng tính)
+const SKIP_RESIZE_MAX_EDGE: u32 = 1280;
+
+const DEFAULT_MAX_CLIENTS: usize = 8;
+const DEFAULT_QUEUE_SIZE: usize = 4;
+const DEFAULT_MAX_ACCEPTS_PER_SEC: u32 = 20;
+// Từ chối cũng tốn một handshake (TLS + WebSocket, tối đa HANDSHAKE_TIMEOUT), nên mỗi server chỉ
+// xử lý bấy nhiêu kết nối bị từ chối cùng lúc. Quá thì đóng TCP ngay, không spawn task
+const MAX_PENDING_REJECTS: usize = 16;
+// Client trong hàng đợi quá lâu thì bị từ chối
+const QUEUE_WAIT: Duration = Duration::from_secs(10);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+// Khối dữ liệu gửi cho client đo băng thông
+const THROUGHPUT_CHUNK: usize = 64 * 1024;
+// Mỗi IP chỉ được đo băng thông lại sau chừng này
+const THROUGHPUT_COOLDOWN: Duration = Duration::from_secs(10);
+// Nhịp thử lại khi lần đo đang chạm max_kbps
+const THROUGHPUT_PACE: Duration = Duration::from_millis(20);
+// Trọng số của frame mới trong trung bình kích thước frame theo tier
+const FRAME_SIZE_WEIGHT: f64 = 0.2;
+// Gửi chậm liên tiếp bấy nhiêu frame thì xuống tier, ổn định bấy lâu thì thử lên lại
+const SLOW_SENDS_BEFORE_DOWNGRADE: u32 = 3;
+const TIER_UPGRADE_AFTER: Duration = Duration::from_secs(10);
+// Thời gian chờ task gửi kết thúc frame đang dở và gửi Close trước khi abort
+const CLOSE_GRACE: Duration = Duration::from_secs(1);
+// Cửa sổ trượt để đo băng thông
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+const MAX_CAPTURE_JITTER: f64 = 0.5;
+// Nhịp kiểm tra thay đổi khi bật capture_on_change. Mỗi lần vẫn là một lần chụp đầy đủ, chỉ
+// bỏ được resize/encode
+const CHANGE_POLL_INTERVAL_MS: u64 = 50;
+// Mặc định của idle_threshold_pct: con trỏ nhấp nháy, đồng hồ nhảy số không tính là thay đổi
+const DEFAULT_IDLE_THRESHOLD_PCT: f64 = 0.5;
+// Event "activity" vài lần mỗi giây là đủ cho badge active/idle
+const ACTIVITY_EVENT_INTERVAL: Duration = Duration::from_millis(500);
+// Frame dùng xong là bỏ, client chậm chỉ cần frame mới nhất nên channel giữ rất ít frame.
+// Mỗi frame giữ vài trăm KB (JPEG + base64), channel lớn chỉ làm client chậm tốn RAM hơn
+const FRAME_CHANNEL_CAPACITY: usize = 2;
+// Frame còn sống cùng lúc: các slot của channel và frame mới nhất, cho cả ba tier
+const FRAME_POOL_SIZE: usize = (FRAME_CHANNEL_CAPACITY + 2) * 3;
+// Ping định kỳ để giữ kết nối và đo RTT qua Pong
+const PING_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeFilter {
+    // Nhanh nhất, chất lượng thấp
+    Nearest,
+    #[default]
+    Triangle,
+    // Nét nhất nhưng chậm
+    Lanczos3,
+}
+
+impl ResizeFilter {
+    fn resize_alg(self) -> ResizeAlg {
+        match self {
+            ResizeFilter::Nearest => ResizeAlg::Nearest,
+            ResizeFilter::Triangle => ResizeAlg::Convolution(FilterType::Bilinear),
+            ResizeFilter::Lanczos3 => ResizeAlg::Convolution(FilterType::Lanczos3),
+        }
+    }
+}
+
+// Preset theo loại nội dung, chỉnh quality, filter và chroma (xem ChromaSubsampling):
+//   text     - quality tier +15 (tối đa 95), filter Lanczos3, chroma 444: chữ nét, frame lớn hơn
+//   balanced - quality tier giữ nguyên, filter và chroma theo tuỳ chọn
+//   video    - quality tier -15 (tối thiểu 20), filter Triangle, chroma theo tuỳ chọn: frame
+//              nhỏ, chịu được mờ
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentMode {
+    Text,
+    #[default]
+    Balanced,
+    Video,
+}
+
+impl ContentMode {
+    fn jpeg_quality(self, tier: QualityTier) -> u8 {
+        let base = tier.jpeg_quality();
+        match self {
+            ContentMode::Text => base.saturating_add(15).min(95),
+            ContentMode::Balanced => base,
+            ContentMode::Video => base.saturating_sub(15).max(20),
+        }
+    }
+
+    fn filter(self, configured: ResizeFilter) -> ResizeFilter {
+        match self {
+            ContentMode::Text => ResizeFilter::Lanczos3,
+            ContentMode::Balanced => configured,
+            ContentMode::Video => ResizeFilter::Triangle,
+        }
+    }
+
+    fn chroma(self, configured: ChromaSubsampling) -> ChromaSubsampling {
+        match self {
+            ContentMode::Text => ChromaSubsampling::Full,
+            ContentMode::Balanced | ContentMode::Video => configured,
+        }
+    }
+}
+
+// Lấy mẫu chroma khi encode JPEG (jpeg-encoder):
+//   444 (mặc định) - chữ màu, icon nhỏ trên nền màu giữ nét, JPEG lớn hơn
+//   420            - mỗi khối 2x2 chung một mẫu màu (Cb/Cr), file chỉ mang 1/4 dữ liệu màu:
+//                    viền chữ màu hơi nhoè, JPEG nhỏ hơn rõ với nội dung nhiều màu
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChromaSubsampling {
+    #[default]
+    #[serde(rename = "444")]
+    Full,
+    #[serde(rename = "420")]
+    Quarter,
+}
+
+impl ChromaSubsampling {
+    fn sampling_factor(self) -> SamplingFactor {
+        match self {
+            ChromaSubsampling::Full => SamplingFactor::R_4_4_4,
+            ChromaSubsampling::Quarter => SamplingFactor::R_4_2_0,
+        }
+    }
+}
+
+// Encode RGBA thành JPEG vào cuối `out` với lấy mẫu chroma đã chọn
+pub(crate) fn encode_jpeg(
+    out: &mut Vec<u8>,
+    img: &RgbaImage,
+    quality: u8,
+    chroma: ChromaSubsampling,
+) -> Result<(), ServerError> {
+    let too_large = || ServerError::Capture(format!("Frame too large: {:?}", img.dimensions()));
+    let width = u16::try_from(img.width()).map_err(|_| too_large())?;
+    let height = u16::try_from(img.height()).map_err(|_| too_large())?;
+    let mut encoder = Encoder::new(out, quality.clamp(1, 100));
+    // jpeg-encoder tự chọn 4:2:0 khi quality < 90 nếu không đặt
+    encoder.set_sampling_factor(chroma.sampling_factor());
+    encoder.encode(img.as_raw(), width, height, ColorType::Rgba)?;
+    Ok(())
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ScreenServerOptions {
+    // Tên để chạy nhiều server cùng lúc, bỏ trống = "default"
+    id: Option<String>,
+    filter: ResizeFilter,
+    // Viewer có allow_content_mode (set_client_cap) đổi được sau khi kết nối bằng
+    // {"content_mode": "text"}
+    content_mode: ContentMode,
+    // "444" hoặc "420", áp dụng với content_mode balanced và video (text luôn 444)
+    chroma_subsampling: ChromaSubsampling,
+    // Thu nhỏ sao cho cạnh dài nhất không quá N px (vd 1280), bỏ trống = giảm 50% như cũ
+    max_dimension: Option<u32>,
+    // Đưa về độ phân giải logic (chia cho scale factor của màn hình) trước khi áp
+    // max_dimension/50%, để màn hình HiDPI không gửi gấp đôi số pixel màn hình thường
+    logical_resolution: bool,
+    // Màn hình (mặc định màn hình chính), một cửa sổ hoặc một vùng của desktop ảo
+    source: SourceKind,
+    // "xcap", "command" (lệnh chụp có sẵn của hệ điều hành) hoặc "auto" (mặc định): xcap,
+    // không thấy màn hình nào thì chuyển sang lệnh chụp
+    capture_backend: CaptureBackend,
+    // Tổng băng thông tối đa cho mọi client của server, vượt thì client đang gửi bỏ frame đó
+    // (recording và preview không bị ảnh hưởng). Bỏ trống = không giới hạn
+    max_kbps: Option<u32>,
+    // Chỉ nghe trên 127.0.0.1 để host tự xem, không lộ ra mạng
+    loopback_only: bool,
+    // IP của một interface trên máy, bỏ trống = mọi interface
+    bind_ip: Option<String>,
+    // Có đủ cả hai (file PEM) thì phục vụ wss://, bỏ trống = ws:// cho LAN tin cậy
+    tls_cert: Option<String>,
+    tls_key: Option<String>,
+    // Port HTTP phục vụ MJPEG tại /stream.mjpg cho trình duyệt, dùng chung bind/TLS/giới hạn
+    // client với WebSocket. Bỏ trống = tắt
+    mjpeg_port: Option<u16>,
+    // Lệch ngẫu nhiên nhịp capture (0.1 = ±10%) để nhiều luồng capture không dồn cùng lúc
+    capture_jitter: f64,
+    // Chụp với nhịp nhanh hơn (50 ms), chỉ resize/encode khi hash toàn frame khác tick trước
+    capture_on_change: bool,
+    // Màn hình gần như đứng yên (phần trăm khối 16 px thay đổi <= idle_threshold_pct) liên tục
+    // bấy nhiêu giây thì ngừng gửi frame, kết nối vẫn giữ bằng ping. Bỏ trống hoặc 0 = tắt
+    idle_pause_secs: Option<u64>,
+    idle_threshold_pct: f64,
+    // Chỉ gửi vùng width x height quanh con trỏ, vùng trượt theo con trỏ. Bỏ trống = tắt
+    follow_cursor: Option<FollowCursor>,
+    // Chữ vẽ đè lên mọi frame (vd "CONFIDENTIAL" hoặc tên viewer), bỏ trống = tắt
+    watermark: Option<String>,
+    watermark_corner: Corner,
+    // Mức nén zstd 1-19 cho client bật tiles + binary và báo hỗ trợ nén trong handshake
+    compression_level: u32,
+    max_clients: usize,
+    // Số kết nối được chờ slot trống, 0 = từ chối ngay khi đầy
+    queue_size: usize,
+    max_accepts_per_sec: u32,
+}
+
+impl Default for ScreenServerOptions {
+    fn default() -> Self {
+        Self {
+            id: None,
+            filter: ResizeFilter::default(),
+            content_mode: ContentMode::default(),
+            chroma_subsampling: ChromaSubsampling::default(),
+            max_dimension: None,
+            logical_resolution: false,
+            source: SourceKind::default(),
+            capture_backend: CaptureBackend::default(),
+            max_kbps: None,
+            loopback_only: false,
+            bind_ip: None,
+            tls_cert: None,
+            tls_key: None,
+            mjpeg_port: None,
+            capture_jitter: 0.0,
+            capture_on_change: false,
+            idle_pause_secs: None,
+            idle_threshold_pct: DEFAULT_IDLE_THRESHOLD_PCT,
+            follow_cursor: None,
+            watermark: None,
+            watermark_corner: Corner::default(),
+            compression_level: frame_compression::DEFAULT_LEVEL,
+            max_clients: DEFAULT_MAX_CLIENTS,
+            queue_size: DEFAULT_QUEUE_SIZE,
+            max_accepts_per_sec: DEFAULT_MAX_ACCEPTS_PER_SEC,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Default)]
+pub struct ServerLoad {
+    running: bool,
+    active: usize,
+    queued: usize,
+    max_clients: usize,
+    queue_size: usize,
+}
+
+// Mỗi tick encode một bản JPEG cho từng mức mà ít nhất một client đang nhận
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum QualityTier {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+const QUALITY_TIERS: [QualityTier; 3] = [QualityTier::Low, QualityTier::Medium, QualityTier::High];
+
+impl QualityTier {
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    pub(crate) fn jpeg_quality(self) -> u8 {
+        match self {
+            QualityTier::Low => 30,
+            QualityTier::Medium => 50,
+            QualityTier::High => 80,
+        }
+    }
+
+    // Client xin quality bất kỳ thì làm tròn về tier gần nhất
+    pub(crate) fn from_quality(quality: u8) -> Self {
+        match quality {
+            0..=40 => QualityTier::Low,
+            41..=65 => QualityTier::Medium,
+            _ => QualityTier::High,
+        }
+    }
+
+    fn lower(self) -> Option<Self> {
+        QUALITY_TIERS.get(self.index().checked_sub(1)?).copied()
+    }
+
+    fn higher(self) -> Option<Self> {
+        QUALITY_TIERS.get(self.index() + 1).copied()
+    }
+}
+
+// Host giới hạn riêng một viewer theo client_token trong handshake, vd cho viewer chính đủ fps
+// còn lại 2 fps. Viewer không có giới hạn nhận như nhau.
+#[derive(Deserialize, Clone, Copy, Default, Debug)]
+#[serde(default)]
+pub struct ClientCap {
+    max_fps: Option<u32>,
+    max_quality: Option<QualityTier>,
+    // content_mode đổi preset cho cả server (mọi viewer), nên chỉ token host cho phép mới được
+    allow_content_mode: bool,
+}
+
+impl ClientCap {
+    fn max_fps(self, server_max: u32) -> u32 {
+        match self.max_fps.filter(|fps| *fps > 0) {
+            Some(fps) => fps.min(server_max),
+            None => server_max,
+        }
+    }
+
+    fn min_interval(self, negotiated: Duration) -> Duration {
+        match self.max_fps.filter(|fps| *fps > 0) {
+            Some(fps) => negotiated.max(Duration::from_millis(1000 / fps as u64)),
+            None => negotiated,
+        }
+    }
+
+    fn tier(self, wanted: QualityTier) -> QualityTier {
+        match self.max_quality {
+            Some(max) if max.index() < wanted.index() => max,
+            _ => wanted,
+        }
+    }
+}
+
+// Tra mỗi frame để giới hạn host vừa đổi áp dụng ngay cho client đang xem
+fn client_cap(token: Option<&str>) -> ClientCap {
+    token
+        .and_then(|token| CLIENT_CAPS.lock().ok()?.get(token).copied())
+        .unwrap_or_default()
+}
+
+#[derive(Clone, Copy, Default)]
+struct CaptureConfig {
+    filter: ResizeFilter,
+    content_mode: ContentMode,
+    chroma: ChromaSubsampling,
+    source: SourceKind,
+    backend: CaptureBackend,
+    max_kbps: Option<u32>,
+    max_dimension: Option<u32>,
+    logical_resolution: bool,
+    jitter: f64,
+    capture_on_change: bool,
+    idle_pause: Option<IdlePause>,
+    follow_cursor: Option<FollowCursor>,
+    compression_level: u32,
+}
+
+#[derive(Clone, Copy)]
+struct IdlePause {
+    after: Duration,
+    threshold_pct: f64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct MonitorInfo {
+    id: u32,
+    name: String,
+    width: u32,
+    height: u32,
+    scale_factor: f32,
+    is_primary: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct WindowInfo {
+    id: u32,
+    title: String,
+    app_name: String,
+    width: u32,
+    height: u32,
+    is_minimized: bool,
+}
+
+#[derive(Serialize, Clone)]
+struct MonitorEvent {
+    // null = màn hình chính (source không chọn id)
+    monitor_id: Option<u32>,
+}
+
+#[derive(Serialize, Clone)]
+struct WindowEvent {
+    window_id: u32,
+}
+
+#[derive(Serialize, Clone)]
+struct LatencyEvent {
+    frame_id: u64,
+    // Từ lúc gửi tới lúc client echo lại
+    rtt_ms: f64,
+    // Từ lúc capture tới lúc client echo lại
+    latency_ms: f64,
+}
+
+// Frame đã gửi gần đây, để tra khi client echo id
+struct SentFrame {
+    id: u64,
+    captured_at: Instant,
+    sent_at: Instant,
+}
+
+const SENT_FRAME_HISTORY: usize = 64;
+
+#[derive(Serialize, Clone)]
+struct ActivityEvent {
+    percent: f64,
+}
+
+// Event "stream-idle": paused = true khi ngừng gửi vì màn hình đứng yên, false khi gửi lại
+#[derive(Serialize, Clone)]
+struct IdleEvent {
+    paused: bool,
+}
+
+// Event "stream-client-lagging", mỗi client tối đa một lần mỗi RATE_WINDOW
+#[derive(Serialize, Clone)]
+struct LaggingEvent {
+    client_token: Option<String>,
+    skipped: u64,
+    // Tier client được chuyển xuống (hoặc giữ nguyên nếu đã thấp nhất)
+    tier: QualityTier,
+}
+
+#[derive(Serialize, Clone)]
+struct PingEvent {
+    rtt_ms: f64,
+}
+
+#[derive(Serialize, Clone)]
+struct ServerReadyEvent {
+    id: String,
+    address: String,
+    // true = client phải kết nối bằng wss:// (và https:// cho MJPEG)
+    secure: bool,
+    // Địa chỉ luồng MJPEG, chỉ có khi bật mjpeg_port
+    mjpeg_url: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+struct BandwidthLimitedEvent {
+    max_kbps: u32,
+    current_kbps: f64,
+    dropped_frames: u64,
+}
+
+// Đếm số byte đã gửi trong cửa sổ trượt RATE_WINDOW
+struct RateAccountant {
+    window: Duration,
+    samples: VecDeque<(Instant, usize)>,
+    total: usize,
+}
+
+impl RateAccountant {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+            total: 0,
+        }
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some(&(at, bytes)) = self.samples.front() {
+            if now.duration_since(at) < self.window {
+                break;
+            }
+            self.total -= bytes;
+            self.samples.pop_front();
+        }
+    }
+
+    fn record(&mut self, bytes: usize) {
+        self.record_at(bytes, Instant::now());
+    }
+
+    fn record_at(&mut self, bytes: usize, now: Instant) {
+        self.prune(now);
+        self.samples.push_back((now, bytes));
+        self.total += bytes;
+    }
+
+    fn kbps(&mut self) -> f64 {
+        self.prune(Instant::now());
+        self.total as f64 * 8.0 / 1000.0 / self.window.as_secs_f64()
+    }
+
+    // Gửi thêm `bytes` có vượt `max_kbps` trong cửa sổ hiện tại không
+    fn would_exceed(&mut self, bytes: usize, max_kbps: u32) -> bool {
+        self.would_exceed_at(bytes, max_kbps, Instant::now())
+    }
+
+    fn would_exceed_at(&mut self, bytes: usize, max_kbps: u32, now: Instant) -> bool {
+        self.prune(now);
+        let budget = max_kbps as f64 * 1000.0 / 8.0 * self.window.as_secs_f64();
+        (self.total + bytes) as f64 > budget
+    }
+}
+
+// Bộ đếm riêng của từng server
+#[derive(Clone, Default)]
+struct ClientCounters {
+    active: Arc<AtomicUsize>,
+    queued: Arc<AtomicUsize>,
+}
 
 pub struct ScreenServer {
-    shutdown_tx: Option<broadcast::Sender<()>>,
+    shutdown_tx: broadcast::Sender<()>,
+    bound_addr: SocketAddr,
+    max_clients: usize,
+    queue_size: usize,
+    counters: ClientCounters,
+    started_at: Instant,
+}
+
+impl ScreenServer {
+    fn load(&self) -> ServerLoad {
+        ServerLoad {
+            running: true,
+            active: self.counters.active.load(Ordering::SeqCst),
+            queued: self.counters.queued.load(Ordering::SeqCst),
+            max_clients: self.max_clients,
+            queue_size: self.queue_size,
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct StartedServer {
+    // Truyền lại cho stop_screen_server / is_server_running / get_server_load
+    id: String,
+    address: String,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct ScreenStatus {
+    running: bool,
+    // Port/địa chỉ của server chạy lâu nhất khi có nhiều server
+    port: Option<u16>,
+    bound_addr: Option<String>,
+    client_count: usize,
+    server_count: usize,
+    // "xcap" hoặc "command", None khi chưa capture được frame nào
+    capture_backend: Option<&'static str>,
+    output: Option<OutputResolution>,
+}
+
+// Đếm theo cửa sổ 1 giây
+struct AcceptLimiter {
+    max_per_sec: u32,
+    window_start: Instant,
+    count: u32,
+}
+
+impl AcceptLimiter {
+    fn new(max_per_sec: u32) -> Self {
+        Self {
+            max_per_sec,
+            window_start: Instant::now(),
+            count: 0,
+        }
+    }
+
+    fn allow(&mut self) -> bool {
+        if self.window_start.elapsed() >= Duration::from_secs(1) {
+            self.window_start = Instant::now();
+            self.count = 0;
+        }
+        self.count += 1;
+        self.max_per_sec == 0 || self.count <= self.max_per_sec
+    }
+}
+
+// Giảm bộ đếm khi client rời đi, kể cả khi task bị huỷ
+struct CountGuard<C: std::ops::Deref<Target = AtomicUsize>>(C);
+
+impl<C: std::ops::Deref<Target = AtomicUsize>> CountGuard<C> {
+    fn new(counter: C) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+impl<C: std::ops::Deref<Target = AtomicUsize>> Drop for CountGuard<C> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+lazy_static::lazy_static! {
+    // Các server chạy cùng lúc (vd một port chỉ LAN, một port loopback), theo id
+    static ref SCREEN_SERVERS: std::sync::Mutex<HashMap<String, ScreenServer>> =
+        std::sync::Mutex::new(HashMap::new());
+    // Mỗi server một luồng capture riêng theo id server (nguồn, cấu hình, frame, thống kê).
+    // Hai server cùng một màn hình thì chụp hai lần, đổi lại cấu hình không giẫm lên nhau.
+    // Recording, preview và benchmark chọn luồng theo id, bỏ trống = "default"
+    static ref CAPTURES: std::sync::Mutex<HashMap<String, Arc<SharedCapture>>> =
+        std::sync::Mutex::new(HashMap::new());
+    // Lần đo băng thông gần nhất theo IP viewer, xem THROUGHPUT_COOLDOWN
+    static ref THROUGHPUT_TESTS: std::sync::Mutex<HashMap<IpAddr, Instant>> =
+        std::sync::Mutex::new(HashMap::new());
+    static ref LOCAL_PREVIEW: tokio::sync::Mutex<Option<tokio::task::JoinHandle<()>>> =
+        tokio::sync::Mutex::new(None);
+    static ref CLIENT_CAPS: std::sync::Mutex<HashMap<String, ClientCap>> =
+        std::sync::Mutex::new(HashMap::new());
+}
+
+pub(crate) struct Frame {
+    // Tăng dần theo tick capture, các tier của cùng tick dùng chung id
+    pub id: u64,
+    pub captured_at: Instant,
+    // Unix millis lúc capture, gửi trong header cho client
+    pub timestamp_ms: u64,
+    // Quay về pool của luồng capture khi frame cuối cùng bị drop
+    pub jpeg: Pooled<Vec<u8>>,
+    pub base64: Pooled<String>,
+    pub stats: CaptureStats,
+    pub geometry: FrameGeometry,
+    // Phần trăm điểm mẫu (lưới thưa) thay đổi so với tick trước, chỉ cho badge active/idle
+    pub activity_pct: f64,
+    // Phần trăm khối 16 px thay đổi so với frame broadcast gần nhất, tính trên mọi pixel. Dùng
+    // để quyết định bỏ qua frame và tạm dừng khi đứng yên
+    pub change_pct: f64,
+    // Chỉ có khi đang có client nhận dạng ô
+    pub tiles: Option<Arc<TileGrid>>,
+}
+
+// Tỉ lệ frame so với nguồn theo từng chiều và góc trên trái của nguồn trên desktop (monitor,
+// cửa sổ hoặc vùng). Lưu cả cụm theo frame để remote input không ghép scale của nguồn này với
+// origin của nguồn khác khi đổi màn hình giữa chừng
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct FrameGeometry {
+    pub scale_x: f64,
+    pub scale_y: f64,
+    pub origin: (i32, i32),
+}
+
+impl Default for FrameGeometry {
+    fn default() -> Self {
+        let scale = 1.0 / DOWNSCALE_FACTOR as f64;
+        Self {
+            scale_x: scale,
+            scale_y: scale,
+            origin: (0, 0),
+        }
+    }
+}
+
+impl FrameGeometry {
+    fn new(frame: (u32, u32), source: (u32, u32), origin: (i32, i32)) -> Self {
+        Self {
+            scale_x: frame.0 as f64 / source.0.max(1) as f64,
+            scale_y: frame.1 as f64 / source.1.max(1) as f64,
+            origin,
+        }
+    }
+
+    // Toạ độ trên frame viewer thấy -> toạ độ desktop thật
+    pub fn to_desktop(self, x: f64, y: f64) -> (i32, i32) {
+        let x = self.origin.0 + (x / self.scale_x).round() as i32;
+        let y = self.origin.1 + (y / self.scale_y).round() as i32;
+        (x, y)
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Default)]
+pub struct CaptureStats {
+    capture_ms: f64,
+    resize_ms: f64,
+    encode_ms: f64,
+}
+
+#[derive(Serialize, Clone, Copy, Default)]
+pub struct BenchmarkResult {
+    frames: u32,
+    avg_capture_ms: f64,
+    avg_resize_ms: f64,
+    avg_encode_ms: f64,
+    // 1000 / tổng thời gian trung bình một frame, chưa tính gửi qua mạng
+    achievable_fps: f64,
+    // Tổng byte sau nén / trước nén của các message tiles binary dựng từ chính các frame
+    // vừa chụp, với compression_level hiện tại. null = không frame nào ra message
+    tile_compression_ratio: Option<f64>,
+}
+
+#[derive(Serialize, Clone, Copy, Default)]
+pub struct CaptureStatsSummary {
+    frames: u64,
+    last: CaptureStats,
+    avg: CaptureStats,
+    // Tổng frame client WebSocket bị bỏ vì không theo kịp broadcast
+    lagged_frames: u64,
+}
+
+impl CaptureStatsSummary {
+    fn record(&mut self, stats: CaptureStats) {
+        self.frames += 1;
+        let n = self.frames as f64;
+        self.avg.capture_ms += (stats.capture_ms - self.avg.capture_ms) / n;
+        self.avg.resize_ms += (stats.resize_ms - self.avg.resize_ms) / n;
+        self.avg.encode_ms += (stats.encode_ms - self.avg.encode_ms) / n;
+        self.last = stats;
+    }
+}
+
+// Một vòng capture dùng chung cho mọi client của một server (và recording), mỗi tier một channel
+pub(crate) struct SharedCapture {
+    // Server sở hữu luồng này, để dừng đúng server khi cửa sổ đang chia sẻ bị đóng
+    server_id: String,
+    tiers: [broadcast::Sender<Arc<Frame>>; 3],
+    running: AtomicBool,
+    // Đánh thức vòng capture đang tạm dừng khi có subscriber mới
+    wake: Notify,
+    stats: std::sync::Mutex<CaptureStatsSummary>,
+    config: std::sync::RwLock<CaptureConfig>,
+    // Hình học của frame broadcast gần nhất
+    geometry: std::sync::Mutex<FrameGeometry>,
+    next_frame_id: AtomicU64,
+    activity: std::sync::Mutex<ActivityTracker>,
+    baseline: std::sync::Mutex<ChangeBaseline>,
+    // Để phát event "monitor-lost" / "monitor-restored"
+    app: std::sync::Mutex<Option<AppHandle>>,
+    // Byte thực sự đã gửi tới các client
+    sent: std::sync::Mutex<RateAccountant>,
+    // Số lần client bỏ frame vì max_kbps, và lúc báo UI gần nhất
+    throttled: std::sync::Mutex<(u64, Option<Instant>)>,
+    // Frame mới nhất của từng tier, gửi ngay cho client vừa kết nối
+    latest: std::sync::Mutex<[Option<Arc<Frame>>; 3]>,
+    // Buffer JPEG/base64 của các frame đã hết người giữ, tick sau encode thẳng vào đó
+    jpeg_pool: Arc<BufferPool<Vec<u8>>>,
+    base64_pool: Arc<BufferPool<String>>,
+    resize: std::sync::Mutex<ResizeBuffers>,
+    watermark: std::sync::RwLock<Option<Arc<Watermark>>>,
+    follower: std::sync::Mutex<CursorFollower>,
+    // Lưới ô của frame trước theo tier, để dùng lại JPEG của ô không đổi
+    tile_grids: std::sync::Mutex<[Option<Arc<TileGrid>>; 3]>,
+    // Backend của lần capture thành công gần nhất
+    active_backend: std::sync::Mutex<Option<&'static str>>,
+    // Kích thước frame của lần capture thành công gần nhất
+    output: std::sync::Mutex<Option<OutputResolution>>,
+    // Client nhận dạng ô, bằng 0 thì không chia lưới
+    tile_clients: Arc<AtomicUsize>,
+    // Trung bình byte JPEG mỗi frame theo tier, None khi tier chưa encode lần nào
+    frame_sizes: std::sync::Mutex<[Option<f64>; 3]>,
+}
+
+impl SharedCapture {
+    fn new(server_id: &str) -> Self {
+        Self {
+            server_id: server_id.to_string(),
+            tiers: std::array::from_fn(|_| broadcast::channel(FRAME_CHANNEL_CAPACITY).0),
+            running: AtomicBool::new(false),
+            wake: Notify::new(),
+            stats: std::sync::Mutex::new(CaptureStatsSummary::default()),
+            config: std::sync::RwLock::new(CaptureConfig::default()),
+            geometry: std::sync::Mutex::new(FrameGeometry::default()),
+            next_frame_id: AtomicU64::new(1),
+            activity: std::sync::Mutex::new(ActivityTracker::default()),
+            baseline: std::sync::Mutex::new(ChangeBaseline::default()),
+            app: std::sync::Mutex::new(None),
+            sent: std::sync::Mutex::new(RateAccountant::new(RATE_WINDOW)),
+            throttled: std::sync::Mutex::new((0, None)),
+            latest: std::sync::Mutex::new(Default::default()),
+            jpeg_pool: BufferPool::new(FRAME_POOL_SIZE),
+            base64_pool: BufferPool::new(FRAME_POOL_SIZE),
+            resize: std::sync::Mutex::new(ResizeBuffers::default()),
+            watermark: std::sync::RwLock::new(None),
+            follower: std::sync::Mutex::new(CursorFollower::default()),
+            tile_grids: std::sync::Mutex::new(Default::default()),
+            active_backend: std::sync::Mutex::new(None),
+            output: std::sync::Mutex::new(None),
+            tile_clients: Arc::new(AtomicUsize::new(0)),
+            frame_sizes: std::sync::Mutex::new([None; 3]),
+        }
+    }
+
+    fn app(&self) -> Option<AppHandle> {
+        self.app.lock().ok().and_then(|app| app.clone())
+    }
+
+    fn content_mode(&self) -> ContentMode {
+        self.config.read().map(|c| c.content_mode).unwrap_or_default()
+    }
+
+    fn geometry(&self) -> FrameGeometry {
+        self.geometry.lock().map(|g| *g).unwrap_or_default()
+    }
+
+    // Chỉ vẽ lại overlay khi chữ hoặc góc đổi
+    fn set_watermark(&self, text: Option<&str>, corner: Corner) {
+        let text = text.map(str::trim).filter(|t| !t.is_empty());
+        let Ok(mut watermark) = self.watermark.write() else {
+            return;
+        };
+        let unchanged = match (text, watermark.as_ref()) {
+            (Some(text), Some(current)) => current.matches(text, corner),
+            (None, None) => true,
+            _ => false,
+        };
+        if !unchanged {
+            *watermark = text.map(|text| Arc::new(Watermark::new(text, corner)));
+        }
+    }
+
+    fn encode_tiles(
+        &self,
+        tier: QualityTier,
+        img: &RgbaImage,
+        quality: u8,
+        chroma: ChromaSubsampling,
+    ) -> Result<Arc<TileGrid>, ServerError> {
+        let mut grids = self.tile_grids.lock().unwrap_or_else(|e| e.into_inner());
+        let previous = grids[tier.index()].take();
+        let grid = Arc::new(TileGrid::encode(img, quality, chroma, previous.as_deref())?);
+        grids[tier.index()] = Some(Arc::clone(&grid));
+        Ok(grid)
+    }
+
+    fn record_frame_size(&self, tier: QualityTier, bytes: usize) {
+        if let Ok(mut sizes) = self.frame_sizes.lock() {
+            let avg = &mut sizes[tier.index()];
+            *avg = Some(match *avg {
+                Some(avg) => avg + (bytes as f64 - avg) * FRAME_SIZE_WEIGHT,
+                None => bytes as f64,
+            });
+        }
+    }
+
+    fn frame_sizes(&self) -> Vec<TierFrameSize> {
+        let Ok(sizes) = self.frame_sizes.lock() else {
+            return Vec::new();
+        };
+        QUALITY_TIERS
+            .iter()
+            .filter_map(|&tier| {
+                let avg = sizes[tier.index()]?;
+                Some(TierFrameSize {
+                    tier,
+                    avg_bytes: avg.round() as usize,
+                })
+            })
+            .collect()
+    }
+
+    fn latest_frame(&self, tier: QualityTier) -> Option<Arc<Frame>> {
+        self.latest.lock().ok().and_then(|f| f[tier.index()].clone())
+    }
+
+    fn receiver_count(&self) -> usize {
+        self.tiers.iter().map(|tx| tx.receiver_count()).sum()
+    }
+
+    fn record_lagged(&self, skipped: u64) {
+        if let Ok(mut stats) = self.stats.lock() {
+            stats.lagged_frames += skipped;
+        }
+    }
+
+    fn record_sent(&self, bytes: usize) {
+        if let Ok(mut sent) = self.sent.lock() {
+            sent.record(bytes);
+        }
+    }
+
+    // Kiểm tra ngay trước khi gửi cho một client: thêm `bytes` có làm tổng băng thông của
+    // server vượt max_kbps không. Vượt thì client đó bỏ frame này (giảm fps), vòng capture
+    // vẫn broadcast bình thường cho recording/preview
+    fn within_budget(&self, bytes: usize) -> bool {
+        let Some(max_kbps) = self.config.read().ok().and_then(|c| c.max_kbps) else {
+            return true;
+        };
+        let current_kbps = {
+            let Ok(mut sent) = self.sent.lock() else {
+                return true;
+            };
+            if !sent.would_exceed(bytes, max_kbps) {
+                return true;
+            }
+            sent.kbps()
+        };
+        let Ok(mut throttled) = self.throttled.lock() else {
+            return false;
+        };
+        let (dropped_frames, last_event) = &mut *throttled;
+        *dropped_frames += 1;
+        // Báo UI tối đa mỗi giây một lần
+        if last_event.is_none_or(|at| at.elapsed() >= RATE_WINDOW) {
+            *last_event = Some(Instant::now());
+            if let Some(app) = self.app() {
+                let event = BandwidthLimitedEvent {
+                    max_kbps,
+                    current_kbps,
+                    dropped_frames: *dropped_frames,
+                };
+                let _ = app.emit("stream-bandwidth-limited", event);
+            }
+        }
+        false
+    }
+
+    // Như within_budget nhưng không tính là bỏ frame, cho lần đo băng thông chờ tới lượt
+    fn fits_budget(&self, bytes: usize) -> bool {
+        let Some(max_kbps) = self.config.read().ok().and_then(|c| c.max_kbps) else {
+            return true;
+        };
+        self.sent.lock().map(|mut sent| !sent.would_exceed(bytes, max_kbps)).unwrap_or(true)
+    }
+
+    // Khối nhỏ hơn 1/10 ngân sách max_kbps của một cửa sổ, để lần đo luôn có lúc gửi được
+    fn throughput_chunk(&self) -> usize {
+        let Some(max_kbps) = self.config.read().ok().and_then(|c| c.max_kbps) else {
+            return THROUGHPUT_CHUNK;
+        };
+        let window_bytes = max_kbps as f64 * 125.0 * RATE_WINDOW.as_secs_f64();
+        ((window_bytes / 10.0) as usize).clamp(1, THROUGHPUT_CHUNK)
+    }
+
+    // Vòng capture chỉ spawn một lần, sau đó tạm dừng/chạy lại theo số subscriber.
+    // notify_one giữ permit nên subscriber đến đúng lúc vòng lặp sắp chờ cũng không bị lỡ
+    fn subscribe(self: &Arc<Self>, tier: QualityTier) -> broadcast::Receiver<Arc<Frame>> {
+        let rx = self.tiers[tier.index()].subscribe();
+        if !self.running.swap(true, Ordering::SeqCst) {
+            tokio::spawn(Arc::clone(self).run());
+        }
+        self.wake.notify_one();
+        rx
+    }
+
+    fn emit_monitor_event(&self, event: &str, monitor_id: Option<u32>) {
+        if let Ok(app) = self.app.lock() {
+            if let Some(app) = app.as_ref() {
+                let _ = app.emit(event, MonitorEvent { monitor_id });
+            }
+        }
+    }
+
+    async fn run(self: Arc<Self>) {
+        // Màn hình đã chọn (hoặc màn hình chính) bị rút ra thì báo một lần,
+        // cắm lại thì tự chạy tiếp
+        let mut lost_monitor: Option<Option<u32>> = None;
+        let mut closed_window: Option<u32> = None;
+        let mut last_activity_event: Option<Instant> = None;
+        // Mốc bắt đầu chuỗi frame dưới ngưỡng idle, và đang tạm ngừng gửi hay không
+        let mut idle_since: Option<Instant> = None;
+        let mut idle_paused = false;
+        // Tick đầu tiên chạy ngay nên viewer đầu tiên không phải chờ
+        let mut ticker = tokio::time::interval(Duration::from_millis(FRAME_INTERVAL_MS));
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut jitter_seed = crate::now_millis() | 1;
+        loop {
+            // Không còn ai nhận frame (viewer, recording, preview) thì tạm dừng capture,
+            // chỉ chờ notify từ subscribe() chứ không chụp gì
+            if self.receiver_count() == 0 {
+                // Frame cũ không còn đúng khi capture chạy lại
+                if let Ok(mut latest) = self.latest.lock() {
+                    *latest = Default::default();
+                }
+                if let Ok(mut grids) = self.tile_grids.lock() {
+                    *grids = Default::default();
+                }
+                if let Ok(mut baseline) = self.baseline.lock() {
+                    baseline.reset();
+                }
+                (idle_since, idle_paused) = (None, false);
+                while self.receiver_count() == 0 {
+                    self.wake.notified().await;
+                }
+                // Tick đang trễ chạy ngay nên viewer vừa vào không phải chờ
+                ticker.reset_immediately();
+            }
+
+            let config = self.config.read().map(|c| *c).unwrap_or_default();
+            // Mặc định giữ nguyên nhịp cố định của ticker
+            if config.jitter > 0.0 || config.capture_on_change {
+                tokio::time::sleep(capture_delay(&config, &mut jitter_seed)).await;
+            } else {
+                ticker.tick().await;
+            }
+
+            let wanted: Vec<QualityTier> = QUALITY_TIERS
+                .into_iter()
+                .filter(|t| self.tiers[t.index()].receiver_count() > 0)
+                .collect();
+            // Tier mới chưa có frame nào thì phải encode dù màn hình đứng yên
+            let all_ready = wanted.iter().all(|t| self.latest_frame(*t).is_some());
+            let skip_below = match (all_ready, config.idle_pause) {
+                (false, _) => None,
+                (true, Some(pause)) if idle_paused => Some(pause.threshold_pct),
+                (true, _) => config.capture_on_change.then_some(0.0),
+            };
+            let capture = Arc::clone(&self);
+            let frames = match tokio::task::spawn_blocking(move || {
+                capture_frame(&capture, config, &wanted, skip_below)
+            })
+            .await
+            {
+                Ok(Ok(frames)) => frames,
+                Ok(Err(ServerError::NoMonitor)) => {
+                    // Tick sau thử lại, nên màn hình cắm vào sau (kể cả trên máy
+                    // ban đầu không có màn hình nào) được dùng ngay
+                    if let (SourceKind::Monitor { id }, None) = (config.source, lost_monitor) {
+                        lost_monitor = Some(id);
+                        self.emit_monitor_event("monitor-lost", id);
+                    }
+                    continue;
+                }
+                // Cửa sổ đã đóng: báo UI và dừng server của luồng này, không tự chuyển sang chia
+                // sẻ cả màn hình. Server khác vẫn chạy
+                Ok(Err(ServerError::WindowClosed)) => {
+                    if let (SourceKind::Window { id }, None) = (config.source, closed_window) {
+                        closed_window = Some(id);
+                        if let Some(app) = self.app() {
+                            let _ = app.emit("window-closed", WindowEvent { window_id: id });
+                        }
+                        let server_id = self.server_id.clone();
+                        tokio::spawn(async move {
+                            let _ = stop_screen_server(Some(server_id)).await;
+                        });
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+
+            if let Some(id) = lost_monitor.take() {
+                if config.source == (SourceKind::Monitor { id }) {
+                    self.emit_monitor_event("monitor-restored", id);
+                }
+            }
+            let Some((_, first)) = frames.first() else { continue };
+            if let Ok(mut stats) = self.stats.lock() {
+                stats.record(first.stats);
+            }
+            if let Ok(mut geometry) = self.geometry.lock() {
+                *geometry = first.geometry;
+            }
+            if last_activity_event.is_none_or(|at| at.elapsed() >= ACTIVITY_EVENT_INTERVAL) {
+                last_activity_event = Some(Instant::now());
+                if let Some(app) = self.app() {
+                    let event = ActivityEvent {
+                        percent: first.activity_pct,
+                    };
+                    let _ = app.emit("activity", event);
+                }
+            }
+
+            // Đứng yên đủ lâu thì ngừng gửi, frame đầu tiên vượt ngưỡng được gửi ngay như bình
+            // thường. Viewer vừa vào vẫn nhận frame mới nhất lúc kết nối
+            let idle = config.idle_pause.filter(|p| first.change_pct <= p.threshold_pct);
+            let was_paused = idle_paused;
+            match idle {
+                Some(pause) => {
+                    let since = *idle_since.get_or_insert_with(Instant::now);
+                    idle_paused = idle_paused || since.elapsed() >= pause.after;
+                }
+                None => (idle_since, idle_paused) = (None, false),
+            }
+            if idle_paused != was_paused {
+                if let Some(app) = self.app() {
+                    let _ = app.emit("stream-idle", IdleEvent { paused: idle_paused });
+                }
+            }
+            if idle_paused && all_ready {
+                continue;
+            }
+
+            if let Ok(mut baseline) = self.baseline.lock() {
+                baseline.mark_broadcast();
+            }
+            for (tier, frame) in frames {
+                let frame = Arc::new(frame);
+                self.record_frame_size(tier, frame.jpeg.len());
+                if let Ok(mut latest) = self.latest.lock() {
+                    latest[tier.index()] = Some(Arc::clone(&frame));
+                }
+                let _ = self.tiers[tier.index()].send(frame);
+            }
+        }
+    }
+}
+
+// Luồng capture của server, tạo khi cần (recording có thể chạy trước khi server mở)
+fn capture_for(server_id: &str) -> Arc<SharedCapture> {
+    let mut captures = CAPTURES.lock().unwrap_or_else(|e| e.into_inner());
+    let capture = captures
+        .entry(server_id.to_string())
+        .or_insert_with(|| Arc::new(SharedCapture::new(server_id)));
+    Arc::clone(capture)
+}
+
+// Remote input qua signaling không gắn với server nào: dùng luồng của server chạy lâu nhất
+// (thường là server duy nhất), chưa có server thì "default"
+fn primary_capture() -> Arc<SharedCapture> {
+    let servers = SCREEN_SERVERS.lock().unwrap_or_else(|e| e.into_inner());
+    let oldest = servers.iter().min_by_key(|(_, server)| server.started_at);
+    let id = oldest.map(|(id, _)| id.clone()).unwrap_or_else(|| server_id(None));
+    drop(servers);
+    capture_for(&id)
+}
+
+pub(crate) fn subscribe_frames(
+    server: Option<&str>,
+    tier: QualityTier,
+) -> broadcast::Receiver<Arc<Frame>> {
+    capture_for(&server_id(server)).subscribe(tier)
+}
+
+pub(crate) fn frame_geometry() -> FrameGeometry {
+    primary_capture().geometry()
+}
+
+fn select_monitor(index: usize) -> Result<Monitor, ServerError> {
+    Monitor::all()?
+        .into_iter()
+        .nth(index)
+        .ok_or(ServerError::NoMonitor)
+}
+
+// Chia làm tròn thay vì cắt, và không bao giờ ra 0 (resize sẽ panic với kích thước 0)
+fn downscaled(dim: u32) -> u32 {
+    ((dim + DOWNSCALE_FACTOR / 2) / DOWNSCALE_FACTOR).max(1)
+}
+
+// Giữ tỉ lệ khung hình, None nếu ảnh đã nằm gọn trong giới hạn
+fn fit_within(width: u32, height: u32, max_dimension: u32) -> Option<(u32, u32)> {
+    let longest = width.max(height);
+    if longest <= max_dimension {
+        return None;
+    }
+    let ratio = max_dimension as f64 / longest as f64;
+    let fit = |dim: u32| ((dim as f64 * ratio).round() as u32).max(1);
+    Some((fit(width), fit(height)))
+}
+
+// Resizer (giữ bộ đệm trung gian) và ảnh đích đã encode xong của tick trước, để resize mỗi
+// frame không cấp phát lại như image::imageops::resize
+#[derive(Default)]
+struct ResizeBuffers {
+    resizer: Resizer,
+    spare: Option<RgbaImage>,
+}
+
+impl ResizeBuffers {
+    fn resize(
+        &mut self,
+        img: RgbaImage,
+        width: u32,
+        height: u32,
+        filter: ResizeFilter,
+    ) -> Result<RgbaImage, ServerError> {
+        let (width, height) = (width.max(1), height.max(1));
+        if width == img.width() && height == img.height() {
+            return Ok(img);
+        }
+        let mut pixels = self.spare.take().map(RgbaImage::into_raw).unwrap_or_default();
+        pixels.resize(width as usize * height as usize * 4, 0);
+        let mut target = RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| ServerError::Capture("Invalid resize target".to_string()))?;
+        // Ảnh chụp màn hình không có alpha, bỏ bước nhân/chia alpha
+        let options = ResizeOptions::new().resize_alg(filter.resize_alg()).use_alpha(false);
+        self.resizer
+            .resize(&img, &mut target, &options)
+            .map_err(|e| ServerError::Capture(format!("Resize failed: {}", e)))?;
+        Ok(target)
+    }
+
+    // Trả ảnh đã dùng xong để tick sau resize vào đó
+    fn recycle(&mut self, img: RgbaImage) {
+        self.spare = Some(img);
+    }
+}
+
+// Resize một lần (screenshot), không giữ buffer
+fn resize_image(
+    img: RgbaImage,
+    width: u32,
+    height: u32,
+    filter: ResizeFilter,
+) -> Result<RgbaImage, ServerError> {
+    ResizeBuffers::default().resize(img, width, height, filter)
+}
+
+// Nhịp capture kế tiếp, lệch ngẫu nhiên trong khoảng ±jitter (xorshift, không cần RNG tốt)
+fn capture_delay(config: &CaptureConfig, seed: &mut u64) -> Duration {
+    let base_ms = if config.capture_on_change {
+        CHANGE_POLL_INTERVAL_MS
+    } else {
+        FRAME_INTERVAL_MS
+    };
+    *seed ^= *seed << 13;
+    *seed ^= *seed >> 7;
+    *seed ^= *seed << 17;
+    let unit = (*seed % 10_000) as f64 / 10_000.0;
+    let factor = 1.0 + config.jitter * (unit * 2.0 - 1.0);
+    Duration::from_millis(base_ms).mul_f64(factor)
+}
+
+// Resize để giảm bandwidth: theo max_dimension nếu có, không thì 50% kích thước
+// (màn hình nhỏ thì bỏ qua). Bật logical_resolution thì tính trên kích thước logic
+fn target_size(
+    config: &CaptureConfig,
+    width: u32,
+    height: u32,
+    scale_factor: f32,
+) -> Option<(u32, u32)> {
+    let (base_width, base_height) = if config.logical_resolution && scale_factor > 1.0 {
+        let logical = |px: u32| ((px as f32 / scale_factor).round() as u32).max(1);
+        (logical(width), logical(height))
+    } else {
+        (width, height)
+    };
+    let scaled = match config.max_dimension {
+        Some(max_dimension) => fit_within(base_width, base_height, max_dimension),
+        None if base_width.max(base_height) > SKIP_RESIZE_MAX_EDGE => {
+            Some((downscaled(base_width), downscaled(base_height)))
+        }
+        None => None,
+    };
+    scaled.or(((base_width, base_height) != (width, height)).then_some((base_width, base_height)))
+}
+
+// Cắt vùng quanh con trỏ (kích thước theo pixel ảnh chụp). Trả về thêm kích thước và góc
+// trên trái của vùng theo toạ độ desktop, để frame_geometry vẫn đúng cho remote input
+fn crop_to_cursor(
+    capture: &SharedCapture,
+    img: RgbaImage,
+    follow: FollowCursor,
+    origin: (i32, i32),
+    source_width: u32,
+) -> (RgbaImage, (u32, u32), (i32, i32)) {
+    // Màn hình HiDPI chụp ra nhiều pixel hơn toạ độ desktop
+    let ratio = img.width() as f64 / source_width.max(1) as f64;
+    let cursor = crate::remote_input::cursor_position()
+        .map(|(x, y)| ((x - origin.0) as f64 * ratio, (y - origin.1) as f64 * ratio));
+    let (x, y, width, height) = match capture.follower.lock() {
+        Ok(mut follower) => follower.region(cursor, follow, (img.width(), img.height())),
+        Err(_) => {
+            let source_height = (img.height() as f64 / ratio).round() as u32;
+            return (img, (source_width, source_height), origin);
+        }
+    };
+
+    let cropped = image::imageops::crop_imm(&img, x, y, width, height).to_image();
+    let to_desktop = |px: u32| (px as f64 / ratio).round() as i32;
+    let region_origin = (origin.0 + to_desktop(x), origin.1 + to_desktop(y));
+    let region = (to_desktop(width).max(1) as u32, to_desktop(height).max(1) as u32);
+    (cropped, region, region_origin)
+}
+
+// Capture và resize một lần, encode riêng cho từng tier được yêu cầu.
+// skip_below: phần trăm khối đổi so với frame broadcast gần nhất không vượt mức này thì trả về
+// rỗng, bỏ qua resize/encode (0 = chỉ bỏ khi không pixel nào đổi)
+fn capture_frame(
+    capture: &SharedCapture,
+    config: CaptureConfig,
+    tiers: &[QualityTier],
+    skip_below: Option<f64>,
+) -> Result<Vec<(QualityTier, Frame)>, ServerError> {
+    let source = config.source.open(config.backend)?;
+    let started = Instant::now();
+    let img = source.capture()?;
+    if let Ok(mut active) = capture.active_backend.lock() {
+        *active = Some(source.backend());
+    }
+    let (mut source_size, mut origin) = ((source.width(), source.height()), source.origin());
+    let img = match config.follow_cursor {
+        Some(follow) => {
+            let (img, region_size, region_origin) =
+                crop_to_cursor(capture, img, follow, origin, source_size.0);
+            (source_size, origin) = (region_size, region_origin);
+            img
+        }
+        None => img,
+    };
+    let activity_pct = capture
+        .activity
+        .lock()
+        .map(|mut tracker| tracker.update(&img))
+        .unwrap_or(0.0);
+    let change_pct = capture
+        .baseline
+        .lock()
+        .map(|mut baseline| baseline.compare(&img))
+        .unwrap_or(100.0);
+    if skip_below.is_some_and(|min| change_pct <= min) {
+        return Ok(Vec::new());
+    }
+    let id = capture.next_frame_id.fetch_add(1, Ordering::SeqCst);
+    let timestamp_ms = crate::now_millis();
+    let captured = Instant::now();
+
+    let scale_factor = source.scale_factor();
+    let mut buffers = capture.resize.lock().unwrap_or_else(|e| e.into_inner());
+    let mut resized = match target_size(&config, img.width(), img.height(), scale_factor) {
+        Some((width, height)) => {
+            buffers.resize(img, width, height, config.content_mode.filter(config.filter))?
+        }
+        None => img,
+    };
+    let chroma = config.content_mode.chroma(config.chroma);
+    // Vẽ sau resize để chữ giữ nguyên độ nét, activity đã tính trên ảnh gốc nên không bị ảnh hưởng
+    if let Some(watermark) = capture.watermark.read().ok().and_then(|w| w.clone()) {
+        watermark.apply(&mut resized);
+    }
+    let resized_at = Instant::now();
+    let geometry = FrameGeometry::new(resized.dimensions(), source_size, origin);
+    if let Ok(mut output) = capture.output.lock() {
+        *output = Some(OutputResolution {
+            width: resized.width(),
+            height: resized.height(),
+            scale_factor,
+        });
+    }
+
+    // Encode thẳng vào buffer lấy từ pool, buffer quay lại pool khi frame hết người giữ
+    let mut encoded = Vec::with_capacity(tiers.len());
+    let tiled = capture.tile_clients.load(Ordering::SeqCst) > 0;
+    for tier in tiers {
+        let quality = config.content_mode.jpeg_quality(*tier);
+        let (jpeg, base64) = encode_pooled(capture, &resized, quality, chroma)?;
+        let tiles = if tiled {
+            Some(capture.encode_tiles(*tier, &resized, quality, chroma)?)
+        } else {
+            None
+        };
+        encoded.push((*tier, jpeg, base64, tiles));
+    }
+    buffers.recycle(resized);
+    drop(buffers);
+
+    // encode_ms là tổng thời gian của mọi tier
+    let stats = CaptureStats {
+        capture_ms: (captured - started).as_secs_f64() * 1000.0,
+        resize_ms: (resized_at - captured).as_secs_f64() * 1000.0,
+        encode_ms: resized_at.elapsed().as_secs_f64() * 1000.0,
+    };
+
+    Ok(encoded
+        .into_iter()
+        .map(|(tier, jpeg, base64, tiles)| {
+            let frame = Frame {
+                id,
+                captured_at: started,
+                timestamp_ms,
+                jpeg,
+                base64,
+                stats,
+                geometry,
+                activity_pct,
+                change_pct,
+                tiles,
+            };
+            (tier, frame)
+        })
+        .collect())
+}
+
+// JPEG và base64 của một tier, encode thẳng vào buffer lấy từ pool
+fn encode_pooled(
+    capture: &SharedCapture,
+    img: &RgbaImage,
+    quality: u8,
+    chroma: ChromaSubsampling,
+) -> Result<(Pooled<Vec<u8>>, Pooled<String>), ServerError> {
+    let mut jpeg = capture.jpeg_pool.take();
+    encode_jpeg(&mut jpeg, img, quality, chroma)?;
+    let mut base64 = capture.base64_pool.take();
+    STANDARD.encode_string(&*jpeg, &mut base64);
+    Ok((jpeg, base64))
+}
+
+// Một frame đi qua resize + encode như vòng capture (ảnh đích và buffer dùng lại), để
+// tests/frame_allocations.rs đếm cấp phát trong binary riêng với allocator đếm
+#[doc(hidden)]
+pub struct FramePipeline(SharedCapture);
+
+#[doc(hidden)]
+impl FramePipeline {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self(SharedCapture::new("frame-pipeline"))
+    }
+
+    // Số byte JPEG, None khi resize/encode lỗi
+    pub fn process(&self, input: RgbaImage, width: u32, height: u32, quality: u8) -> Option<usize> {
+        let mut buffers = self.0.resize.lock().ok()?;
+        let resized = buffers.resize(input, width, height, ResizeFilter::Triangle).ok()?;
+        let encoded = encode_pooled(&self.0, &resized, quality, ChromaSubsampling::Quarter);
+        buffers.recycle(resized);
+        encoded.ok().map(|(jpeg, _)| jpeg.len())
+    }
 }
 
-impl ScreenServer {
-    pub fn new() -> Self {
-        Self { shutdown_tx: None }
+fn latency_for(sent: &std::sync::Mutex<VecDeque<SentFrame>>, id: u64) -> Option<LatencyEvent> {
+    let history = sent.lock().ok()?;
+    let frame = history.iter().find(|f| f.id == id)?;
+    Some(LatencyEvent {
+        frame_id: id,
+        rtt_ms: frame.sent_at.elapsed().as_secs_f64() * 1000.0,
+        latency_ms: frame.captured_at.elapsed().as_secs_f64() * 1000.0,
+    })
+}
+
+fn ping_rtt(connected_at: Instant, payload: &[u8]) -> Option<PingEvent> {
+    let sent_ms = u64::from_be_bytes(payload.try_into().ok()?);
+    let now_ms = connected_at.elapsed().as_millis() as u64;
+    Some(PingEvent {
+        rtt_ms: now_ms.checked_sub(sent_ms)? as f64,
+    })
+}
+
+// Slot được quyết định ngay lúc accept, handshake TLS (nếu có) chạy trong task của client
+enum Admission {
+    Serve(OwnedSemaphorePermit),
+    Queue(Arc<Semaphore>),
+    // Permit của MAX_PENDING_REJECTS, giữ tới khi gửi xong Close
+    Reject(CloseReason, OwnedSemaphorePermit),
+}
+
+async fn admit(
+    stream: TcpStream,
+    tls: Option<TlsAcceptor>,
+    admission: Admission,
+    shutdown_rx: broadcast::Receiver<()>,
+    counters: ClientCounters,
+    capture: Arc<SharedCapture>,
+    registration: Registration,
+) {
+    let Some(stream) = ClientStream::accept(stream, tls.as_ref(), HANDSHAKE_TIMEOUT).await else {
+        return;
+    };
+    match admission {
+        Admission::Serve(permit) => {
+            handle_client(stream, shutdown_rx, permit, counters, capture, registration).await
+        }
+        Admission::Queue(slots) => {
+            wait_for_slot(stream, slots, shutdown_rx, counters, capture, registration).await
+        }
+        Admission::Reject(reason, _rejecting) => reject(stream, reason).await,
     }
 }
 
-lazy_static::lazy_static! {
-    static ref SCREEN_SERVER: Arc<tokio::sync::Mutex<ScreenServer>> =
-        Arc::new(tokio::sync::Mutex::new(ScreenServer::new()));
+// Viewer MJPEG chiếm slot và tính băng thông như một client WebSocket
+async fn admit_mjpeg(
+    stream: TcpStream,
+    tls: Option<TlsAcceptor>,
+    admission: Result<OwnedSemaphorePermit, (CloseReason, OwnedSemaphorePermit)>,
+    shutdown_rx: broadcast::Receiver<()>,
+    counters: ClientCounters,
+    capture: Arc<SharedCapture>,
+) {
+    let Some(stream) = ClientStream::accept(stream, tls.as_ref(), HANDSHAKE_TIMEOUT).await else {
+        return;
+    };
+    let _permit = match admission {
+        Ok(permit) => permit,
+        Err((reason, _rejecting)) => return mjpeg::reject(stream, reason, HANDSHAKE_TIMEOUT).await,
+    };
+    let _active = CountGuard::new(&ACTIVE_CLIENTS);
+    let _server_active = CountGuard::new(counters.active);
+    let tier = QualityTier::default();
+    let frames = capture.subscribe(tier);
+    let latest = capture.latest_frame(tier);
+    let allow = |bytes| capture.within_budget(bytes);
+    let on_sent = |bytes| capture.record_sent(bytes);
+    mjpeg::serve(stream, frames, latest, shutdown_rx, HANDSHAKE_TIMEOUT, allow, on_sent).await;
 }
 
-fn capture_screen_base64(quality: u8) -> Result<String, String> {
-    let monitors = Monitor::all().map_err(|e| e.to_string())?;
-    let monitor = monitors.first().ok_or("No monitor found")?;
+// Hoàn tất handshake rồi đóng ngay kèm mã/retry-after để client biết khi nào thử lại
+async fn reject(stream: ClientStream, reason: CloseReason) {
+    if let Ok(Ok(mut ws)) = tokio::time::timeout(HANDSHAKE_TIMEOUT, accept_async(stream)).await {
+        let _ = ws.close(Some(reason.frame())).await;
+    }
+}
 
-    let img = monitor.capture_image().map_err(|e| e.to_string())?;
+async fn wait_for_slot(
+    stream: ClientStream,
+    slots: Arc<Semaphore>,
+    mut shutdown_rx: broadcast::Receiver<()>,
+    counters: ClientCounters,
+    capture: Arc<SharedCapture>,
+    registration: Registration,
+) {
+    registration.set_role("queued");
+    let drop_signal = registration.drop_signal();
+    let permit = {
+        let _queued = CountGuard::new(Arc::clone(&counters.queued));
+        tokio::select! {
+            permit = tokio::time::timeout(QUEUE_WAIT, slots.acquire_owned()) => permit,
+            _ = shutdown_rx.recv() => return,
+            _ = drop_signal.notified() => return reject(stream, CloseReason::Dropped).await,
+        }
+    };
 
-    // Resize để giảm bandwidth (50% kích thước)
-    let resized = image::imageops::resize(
-        &img,
-        img.width() / 2,
-        img.height() / 2,
-        image::imageops::FilterType::Triangle,
-    );
+    match permit {
+        Ok(Ok(permit)) => {
+            handle_client(stream, shutdown_rx, permit, counters, capture, registration).await
+        }
+        _ => reject(stream, CloseReason::Capacity).await,
+    }
+}
 
-    // Encode JPEG
-    let mut buffer = Cursor::new(Vec::new());
-    let mut encoder = JpegEncoder::new_with_quality(&mut buffer, quality);
-    encoder
-        .encode_image(&resized)
-        .map_err(|e| e.to_string())?;
+async fn send_frame<S>(
+    write: &mut S,
+    capture: &SharedCapture,
+    frame: &Frame,
+    binary: bool,
+    timestamps: bool,
+) -> bool
+where
+    S: SinkExt<Message> + Unpin,
+{
+    let size = if binary { frame.jpeg.len() } else { frame.base64.len() };
+    if !capture.within_budget(size) {
+        return true;
+    }
+    if timestamps {
+        let header = FrameHeader {
+            kind: "frame",
+            id: frame.id,
+            timestamp: frame.timestamp_ms,
+        };
+        let header = serde_json::to_string(&header).unwrap();
+        if write.send(Message::Text(header)).await.is_err() {
+            return false;
+        }
+    }
+    let (msg, bytes) = if binary {
+        (Message::Binary(frame.jpeg.to_vec()), frame.jpeg.len())
+    } else {
+        (Message::Text(frame.base64.to_string()), frame.base64.len())
+    };
+    if write.send(msg).await.is_err() {
+        return false;
+    }
+    capture.record_sent(bytes);
+    true
+}
 
-    Ok(STANDARD.encode(buffer.into_inner()))
+// Client bật tiles chỉ nhận message "tiles". Frame encode trước khi client vào chưa có lưới
+// thì bỏ qua, tick sau sẽ có. compression = mức nén khi client đã nhận nén trong handshake
+async fn send_tiles<S>(
+    write: &mut S,
+    capture: &SharedCapture,
+    frame: &Frame,
+    tracker: &mut TileTracker,
+    binary: bool,
+    keyframe: bool,
+    compression: Option<u32>,
+) -> bool
+where
+    S: SinkExt<Message> + Unpin,
+{
+    let Some(grid) = &frame.tiles else {
+        return true;
+    };
+    // Ước lượng bằng cả frame trước khi tracker ghi nhận ô đã gửi, bỏ qua thì lần sau các ô đổi
+    // vẫn còn trong message
+    let size = if binary { frame.jpeg.len() } else { frame.base64.len() };
+    if !capture.within_budget(size) {
+        return true;
+    }
+    let Some((msg, bytes)) = tracker.message(frame.id, frame.timestamp_ms, grid, keyframe, binary)
+    else {
+        return true;
+    };
+    let (msg, bytes) = match (msg, compression) {
+        (Message::Binary(data), Some(level)) => match frame_compression::compress(&data, level) {
+            Some(compressed) => {
+                let len = compressed.len();
+                (Message::Binary(compressed), len)
+            }
+            None => (Message::Binary(data), bytes),
+        },
+        (msg, _) => (msg, bytes),
+    };
+    if write.send(msg).await.is_err() {
+        return false;
+    }
+    capture.record_sent(bytes);
+    true
+}
+
+// Cấu hình luồng của một viewer. Client cũ không gửi handshake nên luồng bắt đầu ngay với cấu
+// hình mặc định, handshake tới sau thì thay bằng cấu hình đã thoả thuận
+struct ViewerStream {
+    accepted: HandshakeResponse,
+    token: Option<String>,
+    wanted_tier: QualityTier,
+    min_interval: Duration,
+    timestamps: bool,
+    binary: bool,
+    tiles: Option<TileTracker>,
+    _tiled: Option<CountGuard<Arc<AtomicUsize>>>,
+    // Mức nén khi client nhận nén
+    compression: Option<u32>,
+}
+
+impl ViewerStream {
+    fn negotiate(capture: &SharedCapture, request: &HandshakeRequest) -> Self {
+        let wanted_tier = request.quality.map(QualityTier::from_quality).unwrap_or_default();
+        let cap = client_cap(request.client_token.as_deref());
+        let limits = ServerLimits {
+            quality: capture.content_mode().jpeg_quality(cap.tier(wanted_tier)),
+            scale: capture.geometry().scale_x,
+            max_fps: cap.max_fps((1000 / FRAME_INTERVAL_MS) as u32),
+            output: capture.output.lock().ok().and_then(|output| *output),
+            frame_sizes: capture.frame_sizes(),
+        };
+        let accepted = HandshakeResponse::negotiate(request, limits);
+        let compression = accepted.compression.map(|_| {
+            capture
+                .config
+                .read()
+                .map(|c| c.compression_level)
+                .unwrap_or(frame_compression::DEFAULT_LEVEL)
+        });
+        Self {
+            token: request.client_token.clone(),
+            wanted_tier,
+            min_interval: Duration::from_millis(1000 / accepted.fps.max(1) as u64),
+            timestamps: accepted.timestamps,
+            binary: accepted.binary,
+            tiles: accepted.tiles.then(TileTracker::default),
+            _tiled: accepted.tiles.then(|| CountGuard::new(Arc::clone(&capture.tile_clients))),
+            compression,
+            accepted,
+        }
+    }
+
+    fn tier(&self) -> QualityTier {
+        client_cap(self.token.as_deref()).tier(self.wanted_tier)
+    }
+
+    // false khi mất kết nối
+    async fn send<S>(
+        &mut self,
+        write: &mut S,
+        capture: &SharedCapture,
+        frame: &Frame,
+        keyframe: bool,
+    ) -> bool
+    where
+        S: SinkExt<Message> + Unpin,
+    {
+        match self.tiles.as_mut() {
+            Some(tracker) => {
+                let (binary, compression) = (self.binary, self.compression);
+                send_tiles(write, capture, frame, tracker, binary, keyframe, compression).await
+            }
+            None => send_frame(write, capture, frame, self.binary, self.timestamps).await,
+        }
+    }
 }
 
-async fn handle_client(stream: TcpStream, mut shutdown_rx: broadcast::Receiver<()>) {
-    let ws_stream = match accept_async(stream).await {
-        Ok(ws) => ws,
-        Err(_) => return,
+// Mỗi IP một lần trong THROUGHPUT_COOLDOWN, để không ai bắt server gửi dữ liệu đo liên tục
+fn throughput_allowed(ip: Option<IpAddr>) -> bool {
+    let Ok(mut tests) = THROUGHPUT_TESTS.lock() else {
+        return false;
+    };
+    let now = Instant::now();
+    tests.retain(|_, at| now.duration_since(*at) < THROUGHPUT_COOLDOWN);
+    let ip = ip.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    if tests.contains_key(&ip) {
+        return false;
+    }
+    tests.insert(ip, now);
+    true
+}
+
+// Message binary đủ `bytes` byte cho client đo băng thông. Tính chung max_kbps và thống kê
+// băng thông với frame, nên lần đo không lấn phần của viewer đang xem
+async fn send_throughput<S>(write: &mut S, capture: &SharedCapture, bytes: usize)
+where
+    S: SinkExt<Message> + Unpin,
+{
+    let chunk_size = capture.throughput_chunk();
+    let chunk = vec![0u8; chunk_size];
+    let mut remaining = bytes;
+    while remaining > 0 {
+        let n = remaining.min(chunk_size);
+        while !capture.fits_budget(n) {
+            tokio::time::sleep(THROUGHPUT_PACE).await;
+        }
+        if write.send(Message::Binary(chunk[..n].to_vec())).await.is_err() {
+            return;
+        }
+        capture.record_sent(n);
+        remaining -= n;
+    }
+}
+
+async fn handle_client(
+    stream: ClientStream,
+    mut shutdown_rx: broadcast::Receiver<()>,
+    _permit: OwnedSemaphorePermit,
+    counters: ClientCounters,
+    capture: Arc<SharedCapture>,
+    registration: Registration,
+) {
+    registration.set_role("viewer");
+    let drop_signal = registration.drop_signal();
+    let _active = CountGuard::new(&ACTIVE_CLIENTS);
+    let _server_active = CountGuard::new(counters.active);
+
+    let ws_stream = match tokio::time::timeout(HANDSHAKE_TIMEOUT, accept_async(stream)).await {
+        Ok(Ok(ws)) => ws,
+        _ => return,
     };
 
     let (mut write, mut read) = ws_stream.split();
 
+    let sent_frames = Arc::new(std::sync::Mutex::new(VecDeque::<SentFrame>::new()));
+    let sent_history = Arc::clone(&sent_frames);
+    let remember_sent = move |frame: &Frame| {
+        if let Ok(mut history) = sent_history.lock() {
+            if history.len() >= SENT_FRAME_HISTORY {
+                history.pop_front();
+            }
+            history.push_back(SentFrame {
+                id: frame.id,
+                captured_at: frame.captured_at,
+                sent_at: Instant::now(),
+            });
+        }
+    };
+
+    // Task gửi chỉ dừng giữa hai frame, không cắt ngang frame đang ghi
+    let (stop_tx, mut stop_rx) = tokio::sync::oneshot::channel::<()>();
+    // JPEG frame nào cũng là frame đầy đủ, nên keyframe chỉ cần gửi ngay frame kế tiếp
+    // thay vì bỏ qua vì giới hạn fps. Client nhận dạng ô thì frame đó gửi đủ mọi ô
+    let keyframe_requested = Arc::new(AtomicBool::new(false));
+    let keyframe_flag = Arc::clone(&keyframe_requested);
+    // Payload của Ping là số ms tính từ mốc này, Pong trả lại nguyên payload
+    let connected_at = Instant::now();
+    let task_capture = Arc::clone(&capture);
+    let peer_ip = registration.remote_ip();
+    // Reader chuyển handshake sang task gửi, task gửi giữ write nên tự trả lời
+    let (handshake_tx, mut handshake_rx) = tokio::sync::mpsc::channel::<HandshakeRequest>(1);
+
     // Gửi frame liên tục
-    let send_task = tokio::spawn(async move {
-        loop {
+    let mut send_task = tokio::spawn(async move {
+        let capture = task_capture;
+        let mut stream = ViewerStream::negotiate(&capture, &HandshakeRequest::default());
+        let mut tier = stream.tier();
+        let mut frames = capture.subscribe(tier);
+        // Có frame sẵn thì gửi luôn, không bắt viewer chờ handshake hay lượt broadcast tiếp theo
+        if let Some(frame) = capture.latest_frame(tier) {
+            if !stream.send(&mut write, &capture, &frame, true).await {
+                return;
+            }
+        }
+        let mut last_sent = Instant::now();
+        let mut slow_sends = 0u32;
+        let mut last_switch = Instant::now();
+        let mut last_lag_event: Option<Instant> = None;
+        let mut ping_ticker =
+            tokio::time::interval_at(tokio::time::Instant::now() + PING_INTERVAL, PING_INTERVAL);
+        let plain = |code| CloseFrame {
+            code,
+            reason: "".into(),
+        };
+        let close_frame = loop {
             tokio::select! {
-                _ = shutdown_rx.recv() => break,
-                _ = tokio::time::sleep(tokio::time::Duration::from_millis(100)) => {
-                    match capture_screen_base64(50) {
+                _ = shutdown_rx.recv() => break plain(CloseCode::Away),
+                _ = &mut stop_rx => break plain(CloseCode::Normal),
+                _ = drop_signal.notified() => break CloseReason::Dropped.frame(),
+                Some(request) = handshake_rx.recv() => {
+                    let next = ViewerStream::negotiate(&capture, &request);
+                    if next.accepted.throughput_bytes.is_some() && !throughput_allowed(peer_ip) {
+                        break CloseReason::Busy.frame();
+                    }
+                    let reply = serde_json::to_string(&next.accepted).unwrap();
+                    if write.send(Message::Text(reply)).await.is_err() {
+                        return;
+                    }
+                    // Client chỉ đo băng thông (measure_throughput): gửi đủ số byte rồi đóng
+                    if let Some(bytes) = next.accepted.throughput_bytes {
+                        tokio::select! {
+                            _ = send_throughput(&mut write, &capture, bytes) => {}
+                            _ = shutdown_rx.recv() => {}
+                            _ = drop_signal.notified() => {}
+                        }
+                        break plain(CloseCode::Normal);
+                    }
+                    stream = next;
+                    tier = stream.tier();
+                    frames = capture.subscribe(tier);
+                    slow_sends = 0;
+                    last_switch = Instant::now();
+                    // Frame đầy đủ theo cấu hình mới, client đổi định dạng không phải chờ tick sau
+                    if let Some(frame) = capture.latest_frame(tier) {
+                        if !stream.send(&mut write, &capture, &frame, true).await {
+                            return;
+                        }
+                        if stream.timestamps {
+                            remember_sent(&frame);
+                        }
+                        last_sent = Instant::now();
+                    }
+                }
+                _ = ping_ticker.tick() => {
+                    let payload = (connected_at.elapsed().as_millis() as u64).to_be_bytes();
+                    if write.send(Message::Ping(payload.to_vec())).await.is_err() {
+                        return;
+                    }
+                }
+                frame = frames.recv() => {
+                    match frame {
                         Ok(frame) => {
-                            if write.send(Message::Text(frame)).await.is_err() {
-                                break;
+                            // Bớt frame theo fps đã thoả thuận hoặc giới hạn host đặt cho client
+                            // này (chừa 10% sai lệch của tick)
+                            let cap = client_cap(stream.token.as_deref());
+                            let interval = cap.min_interval(stream.min_interval);
+                            let keyframe = keyframe_flag.swap(false, Ordering::SeqCst);
+                            if !keyframe && last_sent.elapsed() < interval.mul_f64(0.9) {
+                                continue;
+                            }
+                            let started = Instant::now();
+                            if !stream.send(&mut write, &capture, &frame, keyframe).await {
+                                return;
+                            }
+                            if stream.timestamps {
+                                remember_sent(&frame);
+                            }
+                            last_sent = Instant::now();
+
+                            // Gửi chậm hơn nhịp frame liên tục thì xuống tier, ổn định lâu thì lên lại
+                            if started.elapsed() > interval {
+                                slow_sends += 1;
+                            } else {
+                                slow_sends = 0;
                             }
+                            let target = cap.tier(stream.wanted_tier);
+                            let next = if tier.index() > target.index() {
+                                Some(target)
+                            } else if slow_sends >= SLOW_SENDS_BEFORE_DOWNGRADE {
+                                tier.lower()
+                            } else if tier != target && last_switch.elapsed() > TIER_UPGRADE_AFTER {
+                                tier.higher()
+                            } else {
+                                None
+                            };
+                            if let Some(next) = next {
+                                // Mỗi lần đổi tier (lên hay xuống) đều đợi thêm một chu kỳ
+                                last_switch = Instant::now();
+                                slow_sends = 0;
+                                tier = next;
+                                frames = capture.subscribe(tier);
+                            }
+                        }
+                        // Không theo kịp broadcast: bỏ hết frame cũ còn trong channel, frame kế
+                        // tiếp gửi ngay như keyframe. Đây cũng là dấu hiệu thiếu băng thông
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            capture.record_lagged(skipped);
+                            if let Some(lower) = tier.lower() {
+                                tier = lower;
+                                last_switch = Instant::now();
+                            }
+                            frames = capture.subscribe(tier);
+                            keyframe_flag.store(true, Ordering::SeqCst);
+                            if last_lag_event.is_none_or(|at| at.elapsed() >= RATE_WINDOW) {
+                                last_lag_event = Some(Instant::now());
+                                let event = LaggingEvent {
+                                    client_token: stream.token.clone(),
+                                    skipped,
+                                    tier,
+                                };
+                                if let Some(app) = capture.app() {
+                                    let _ = app.emit("stream-client-lagging", event);
+                                }
+                            }
+                            continue;
                         }
-                        Err(_) => break,
+                        Err(_) => break plain(CloseCode::Away),
                     }
                 }
             }
-        }
+        };
+        let _ = write.send(Message::Close(Some(close_frame))).await;
+        let _ = write.close().await;
     });
 
-    // Đọc message từ client (để detect disconnect)
-    while let Some(msg) = read.next().await {
-        if msg.is_err() {
-            break;
+    // Đọc message điều khiển từ client và detect disconnect
+    let mut send_done = false;
+    let mut last_latency_event: Option<Instant> = None;
+    let mut handshake_tx = Some(handshake_tx);
+    let mut control_token = None;
+    loop {
+        tokio::select! {
+            msg = read.next() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    // Message text đầu tiên có thể là handshake, sau đó chỉ còn điều khiển
+                    let first = handshake_tx.is_some();
+                    let control = match ClientMessage::parse(&text, first) {
+                        Some(ClientMessage::Handshake(request)) => {
+                            control_token = request.client_token.clone();
+                            if let Some(tx) = handshake_tx.take() {
+                                let _ = tx.try_send(request);
+                            }
+                            continue;
+                        }
+                        Some(ClientMessage::Control(control)) => control,
+                        None => continue,
+                    };
+                    handshake_tx = None;
+                    if control.request_keyframe {
+                        keyframe_requested.store(true, Ordering::SeqCst);
+                    }
+                    // Áp dụng cho luồng capture của server này, mọi viewer của nó cùng nhận preset,
+                    // nên viewer không được host cho phép thì bỏ qua
+                    let allowed = client_cap(control_token.as_deref()).allow_content_mode;
+                    if let (Some(mode), true) = (control.content_mode, allowed) {
+                        if let Ok(mut config) = capture.config.write() {
+                            config.content_mode = mode;
+                        }
+                    }
+                    // Báo UI tối đa mỗi giây một lần cho mỗi client
+                    let due = last_latency_event.is_none_or(|at| at.elapsed() >= RATE_WINDOW);
+                    if let (Some(id), true) = (control.ack, due) {
+                        if let Some(event) = latency_for(&sent_frames, id) {
+                            last_latency_event = Some(Instant::now());
+                            if let Some(app) = capture.app() {
+                                let _ = app.emit("stream-latency", event);
+                            }
+                        }
+                    }
+                }
+                // Client không gửi dữ liệu binary nào có nghĩa
+                Some(Ok(Message::Binary(_))) => {}
+                // tungstenite tự xếp Pong và gửi ở lần đọc/ghi kế tiếp
+                Some(Ok(Message::Ping(_))) => {}
+                Some(Ok(Message::Pong(payload))) => {
+                    if let Some(event) = ping_rtt(connected_at, &payload) {
+                        if let Some(app) = capture.app() {
+                            let _ = app.emit("stream-ping", event);
+                        }
+                    }
+                }
+                Some(Ok(Message::Close(_))) => break,
+                Some(Ok(Message::Frame(_))) => {}
+                Some(Err(_)) | None => break,
+            },
+            // Server dừng: task gửi đã gửi Close
+            _ = &mut send_task => {
+                send_done = true;
+                break;
+            }
+        }
+    }
+
+    if !send_done {
+        let _ = stop_tx.send(());
+        if tokio::time::timeout(CLOSE_GRACE, &mut send_task).await.is_err() {
+            send_task.abort();
+        }
+    }
+}
+
+// Dùng cho run_diagnostics: chụp thử một frame màn hình chính
+pub(crate) fn test_capture() -> Result<String, ServerError> {
+    let monitor = monitor_by_id(None)?;
+    let img = monitor.capture_image()?;
+    Ok(format!("{} ({}x{})", monitor.name(), img.width(), img.height()))
+}
+
+// Ảnh PNG không nén mất dữ liệu, dùng để chụp màn hình đọc chữ nhỏ / lấy màu chính xác.
+// Kích thước lớn hơn JPEG nhiều lần nên không dùng cho stream trực tiếp.
+fn capture_png(monitor_index: usize, scale: f64) -> Result<String, ServerError> {
+    if !(scale > 0.0 && scale <= 1.0) {
+        return Err(ServerError::Capture(format!("Invalid scale: {}", scale)));
+    }
+
+    let monitor = select_monitor(monitor_index)?;
+    let img = monitor.capture_image()?;
+
+    let width = (img.width() as f64 * scale).round() as u32;
+    let height = (img.height() as f64 * scale).round() as u32;
+    let img = resize_image(img, width, height, ResizeFilter::Lanczos3)?;
+
+    let mut buffer = Vec::new();
+    PngEncoder::new_with_quality(&mut buffer, CompressionType::Best, Default::default()).write_image(
+        img.as_raw(),
+        img.width(),
+        img.height(),
+        image::ExtendedColorType::Rgba8,
+    )?;
+
+    Ok(STANDARD.encode(&buffer))
+}
+
+// Kích thước tối đa của ảnh ghép để tránh cấp phát quá lớn với bố cục màn hình lạ
+const MAX_STITCHED_DIMENSION: u32 = 16384;
+
+// Ghép mọi màn hình theo toạ độ desktop ảo. Mỗi ảnh được đưa về kích thước theo toạ độ
+// (HiDPI chụp ra nhiều pixel hơn), chỗ trống giữa các màn hình để đen, chồng nhau thì
+// màn hình sau đè lên.
+fn capture_stitched(scale: f64, quality: u8) -> Result<String, ServerError> {
+    if !(scale > 0.0 && scale <= 1.0) {
+        return Err(ServerError::Capture(format!("Invalid scale: {}", scale)));
+    }
+
+    let monitors = Monitor::all()?;
+    if monitors.is_empty() {
+        return Err(ServerError::NoMonitor);
+    }
+
+    let min_x = monitors.iter().map(|m| m.x()).min().unwrap_or(0);
+    let min_y = monitors.iter().map(|m| m.y()).min().unwrap_or(0);
+    let max_x = monitors.iter().map(|m| m.x() + m.width() as i32).max().unwrap_or(0);
+    let max_y = monitors.iter().map(|m| m.y() + m.height() as i32).max().unwrap_or(0);
+
+    let scaled = |v: i64| (v as f64 * scale).round() as i64;
+    let width = scaled((max_x - min_x) as i64).max(1) as u32;
+    let height = scaled((max_y - min_y) as i64).max(1) as u32;
+    if width > MAX_STITCHED_DIMENSION || height > MAX_STITCHED_DIMENSION {
+        return Err(ServerError::Capture(format!(
+            "Desktop too large to stitch: {}x{}",
+            width, height
+        )));
+    }
+
+    let mut canvas = RgbaImage::new(width, height);
+    for monitor in &monitors {
+        let img = monitor.capture_image()?;
+        let w = scaled(monitor.width() as i64).max(1) as u32;
+        let h = scaled(monitor.height() as i64).max(1) as u32;
+        let img = resize_image(img, w, h, ResizeFilter::Triangle)?;
+        let x = scaled((monitor.x() - min_x) as i64);
+        let y = scaled((monitor.y() - min_y) as i64);
+        image::imageops::replace(&mut canvas, &img, x, y);
+    }
+
+    let mut buffer = Cursor::new(Vec::new());
+    JpegEncoder::new_with_quality(&mut buffer, quality.clamp(1, 100)).encode_image(&canvas)?;
+    Ok(STANDARD.encode(buffer.into_inner()))
+}
+
+#[tauri::command]
+pub async fn capture_all_monitors(
+    scale: Option<f64>,
+    quality: Option<u8>,
+) -> Result<String, AppError> {
+    let scale = scale.unwrap_or(1.0);
+    let quality = quality.unwrap_or(QualityTier::High.jpeg_quality());
+    tokio::task::spawn_blocking(move || capture_stitched(scale, quality))
+        .await
+        .map_err(|e| ServerError::Capture(e.to_string()))?
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+pub async fn capture_screenshot_png(
+    monitor_index: Option<usize>,
+    scale: Option<f64>,
+) -> Result<String, AppError> {
+    let monitor_index = monitor_index.unwrap_or(0);
+    let scale = scale.unwrap_or(1.0);
+    tokio::task::spawn_blocking(move || capture_png(monitor_index, scale))
+        .await
+        .map_err(|e| ServerError::Capture(e.to_string()))?
+        .map_err(AppError::from)
+}
+
+const MAX_BENCHMARK_FRAMES: u32 = 120;
+
+// Chụp/resize/encode như luồng capture chung nhưng không đụng tới frame id, activity hay
+// buffer của nó, nên chạy được cả khi đang stream (chỉ tốn thêm CPU)
+fn run_benchmark(capture: &SharedCapture, frames: u32) -> Result<BenchmarkResult, ServerError> {
+    let config = capture.config.read().map(|c| *c).unwrap_or_default();
+    let tier = QualityTier::default();
+    let source = config.source.open(config.backend)?;
+    let mut jpeg = Vec::new();
+    let mut buffers = ResizeBuffers::default();
+    let (mut capture, mut resize, mut encode) = (Duration::ZERO, Duration::ZERO, Duration::ZERO);
+    let (mut grid, mut tracker) = (None::<TileGrid>, TileTracker::default());
+    let (mut tile_bytes, mut compressed_bytes) = (0.0, 0.0);
+
+    for _ in 0..frames {
+        let started = Instant::now();
+        let img = source.capture()?;
+        let captured = Instant::now();
+        let size = target_size(&config, img.width(), img.height(), source.scale_factor());
+        let resized = match size {
+            Some((width, height)) => {
+                buffers.resize(img, width, height, config.content_mode.filter(config.filter))?
+            }
+            None => img,
+        };
+        let chroma = config.content_mode.chroma(config.chroma);
+        let resized_at = Instant::now();
+        jpeg.clear();
+        let quality = config.content_mode.jpeg_quality(tier);
+        encode_jpeg(&mut jpeg, &resized, quality, chroma)?;
+
+        capture += captured - started;
+        resize += resized_at - captured;
+        encode += resized_at.elapsed();
+
+        // Không tính vào thời gian encode ở trên
+        let next = TileGrid::encode(&resized, quality, chroma, grid.as_ref())?;
+        let message = tracker.message(0, 0, &next, false, true);
+        if let Some((Message::Binary(data), len)) = message {
+            if let Some(ratio) = frame_compression::ratio(&data, config.compression_level) {
+                tile_bytes += len as f64;
+                compressed_bytes += len as f64 * ratio;
+            }
         }
+        grid = Some(next);
+        buffers.recycle(resized);
     }
 
-    send_task.abort();
+    let avg_ms = |total: Duration| total.as_secs_f64() * 1000.0 / frames as f64;
+    let frame_ms = avg_ms(capture + resize + encode);
+    Ok(BenchmarkResult {
+        frames,
+        avg_capture_ms: avg_ms(capture),
+        avg_resize_ms: avg_ms(resize),
+        avg_encode_ms: avg_ms(encode),
+        achievable_fps: if frame_ms > 0.0 { 1000.0 / frame_ms } else { 0.0 },
+        tile_compression_ratio: (tile_bytes > 0.0).then(|| compressed_bytes / tile_bytes),
+    })
+}
+
+// Đo máy host làm được bao nhiêu fps với cấu hình capture hiện tại của server `id`, để UI gợi
+// ý fps/quality
+#[tauri::command]
+pub async fn benchmark_capture(
+    frames: u32,
+    id: Option<String>,
+) -> Result<BenchmarkResult, AppError> {
+    let frames = frames.clamp(1, MAX_BENCHMARK_FRAMES);
+    let capture = capture_for(&server_id(id.as_deref()));
+    tokio::task::spawn_blocking(move || run_benchmark(&capture, frames))
+        .await
+        .map_err(|e| ServerError::Capture(e.to_string()))?
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+pub fn list_monitors() -> Result<Vec<MonitorInfo>, AppError> {
+    Ok(Monitor::all()?
+        .iter()
+        .map(|m| MonitorInfo {
+            id: m.id(),
+            name: m.name().to_string(),
+            width: m.width(),
+            height: m.height(),
+            scale_factor: m.scale_factor(),
+            is_primary: m.is_primary(),
+        })
+        .collect())
+}
+
+// Chọn lại màn hình cho server `id` khi đang stream (vd sau event "monitor-lost"), bỏ chế độ
+// cửa sổ
+#[tauri::command]
+pub fn set_capture_monitor(monitor_id: Option<u32>, id: Option<String>) -> Result<(), AppError> {
+    if monitor_id.is_some() {
+        monitor_by_id(monitor_id)?;
+    }
+    if let Ok(mut config) = capture_for(&server_id(id.as_deref())).config.write() {
+        config.source = SourceKind::Monitor { id: monitor_id };
+    }
+    Ok(())
 }
 
 #[tauri::command]
-pub async fn start_screen_server(port: u16) -> Result<String, String> {
-    if SERVER_RUNNING.load(Ordering::SeqCst) {
-        return Err("Server already running".to_string());
+pub fn list_windows() -> Result<Vec<WindowInfo>, AppError> {
+    Ok(Window::all()?
+        .iter()
+        .map(|w| WindowInfo {
+            id: w.id(),
+            title: w.title().to_string(),
+            app_name: w.app_name().to_string(),
+            width: w.width(),
+            height: w.height(),
+            is_minimized: w.is_minimized(),
+        })
+        .collect())
+}
+
+// Listener tuỳ chọn: không có thì nhánh select này không bao giờ xong
+async fn accept_optional(
+    listener: Option<&TcpListener>,
+) -> std::io::Result<(TcpStream, SocketAddr)> {
+    match listener {
+        Some(listener) => listener.accept().await,
+        None => std::future::pending().await,
     }
+}
 
-    let listener = TcpListener::bind(format!("0.0.0.0:{}", port))
+#[tauri::command]
+pub async fn start_screen_server(
+    app: AppHandle,
+    port: u16,
+    options: Option<ScreenServerOptions>,
+) -> Result<StartedServer, AppError> {
+    let options = options.unwrap_or_default();
+    let id = server_id(options.id.as_deref());
+    if SCREEN_SERVERS.lock().is_ok_and(|servers| servers.contains_key(&id)) {
+        return Err(ServerError::AlreadyRunning.into());
+    }
+    // Headless/RDP không có màn hình: báo NoMonitor ngay thay vì mở server rỗng
+    let (source, backend) = (options.source, options.capture_backend);
+    tokio::task::spawn_blocking(move || source.check(backend))
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| ServerError::Capture(e.to_string()))??;
+
+    let tls = match (&options.tls_cert, &options.tls_key) {
+        (Some(cert), Some(key)) => Some(load_acceptor(cert, key)?),
+        (None, None) => None,
+        _ => {
+            let message = "Both tls_cert and tls_key are required".to_string();
+            return Err(ServerError::Tls(message).into());
+        }
+    };
+
+    let bind_ip = if options.loopback_only {
+        IpAddr::V4(Ipv4Addr::LOCALHOST)
+    } else {
+        resolve_bind_ip(options.bind_ip.as_deref())?
+    };
+    let listener = bind_listener(SocketAddr::new(bind_ip, port)).await?;
+    let bound_addr = listener.local_addr().map_err(ServerError::Bind)?;
+    let mjpeg_listener = match options.mjpeg_port {
+        Some(mjpeg_port) => Some(bind_listener(SocketAddr::new(bind_ip, mjpeg_port)).await?),
+        None => None,
+    };
+    let mjpeg_port = match &mjpeg_listener {
+        Some(l) => Some(l.local_addr().map_err(ServerError::Bind)?.port()),
+        None => None,
+    };
 
     let (shutdown_tx, _) = broadcast::channel::<()>(1);
     let shutdown_tx_clone = shutdown_tx.clone();
+    let counters = ClientCounters::default();
 
     {
-        let mut server = SCREEN_SERVER.lock().await;
-        server.shutdown_tx = Some(shutdown_tx);
+        let mut servers = SCREEN_SERVERS.lock().unwrap_or_else(|e| e.into_inner());
+        // Hai lệnh start cùng id chạy song song: lệnh sau bỏ listener vừa bind
+        if servers.contains_key(&id) {
+            return Err(ServerError::AlreadyRunning.into());
+        }
+        let server = ScreenServer {
+            shutdown_tx,
+            bound_addr,
+            max_clients: options.max_clients,
+            queue_size: options.queue_size,
+            counters: counters.clone(),
+            started_at: Instant::now(),
+        };
+        servers.insert(id.clone(), server);
     }
 
-    SERVER_RUNNING.store(true, Ordering::SeqCst);
+    let capture = capture_for(&id);
+    if let Ok(mut app_slot) = capture.app.lock() {
+        *app_slot = Some(app.clone());
+    }
+    if let Ok(mut config) = capture.config.write() {
+        config.filter = options.filter;
+        config.chroma = options.chroma_subsampling;
+        config.content_mode = options.content_mode;
+        config.source = options.source;
+        config.backend = options.capture_backend;
+        config.max_kbps = options.max_kbps.filter(|k| *k > 0);
+        config.max_dimension = options.max_dimension.filter(|d| *d > 0);
+        config.logical_resolution = options.logical_resolution;
+        config.jitter = options.capture_jitter.clamp(0.0, MAX_CAPTURE_JITTER);
+        config.capture_on_change = options.capture_on_change;
+        config.idle_pause = options.idle_pause_secs.filter(|s| *s > 0).map(|secs| IdlePause {
+            after: Duration::from_secs(secs),
+            threshold_pct: options.idle_threshold_pct.clamp(0.0, 100.0),
+        });
+        config.follow_cursor = options.follow_cursor.filter(|f| f.width > 0 && f.height > 0);
+        config.compression_level = options
+            .compression_level
+            .clamp(frame_compression::MIN_LEVEL, frame_compression::MAX_LEVEL);
+    }
+    if let Ok(mut follower) = capture.follower.lock() {
+        follower.reset();
+    }
+    if let Ok(mut active) = capture.active_backend.lock() {
+        *active = None;
+    }
+    if let Ok(mut output) = capture.output.lock() {
+        *output = None;
+    }
+    capture.set_watermark(options.watermark.as_deref(), options.watermark_corner);
+
+    let local_ip = if !bind_ip.is_unspecified() {
+        bind_ip.to_string()
+    } else {
+        crate::local_ipv4()
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|_| "0.0.0.0".to_string())
+    };
 
-    let local_ip = local_ip_address::local_ip()
-        .map(|ip| ip.to_string())
-        .unwrap_or_else(|_| "0.0.0.0".to_string());
+    let slots = Arc::new(Semaphore::new(options.max_clients.max(1)));
+    let rejects = Arc::new(Semaphore::new(MAX_PENDING_REJECTS));
+    let queue_size = options.queue_size;
+    let mut limiter = AcceptLimiter::new(options.max_accepts_per_sec);
+    let address = format!("{}:{}", local_ip, bound_addr.port());
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    let mjpeg_url = mjpeg_port
+        .map(|p| format!("{}://{}:{}{}", scheme, local_ip, p, mjpeg::STREAM_PATH));
+    let (ready_tx, ready_rx) = tokio::sync::oneshot::channel::<()>();
 
     // Spawn server task
+    let ready_address = address.clone();
+    let loop_id = id.clone();
     tokio::spawn(async move {
         let mut shutdown_rx = shutdown_tx_clone.subscribe();
+        let started_at = Instant::now();
+        let mut heartbeat = heartbeat::ticker();
+        let _ = ready_tx.send(());
+        let event = ServerReadyEvent {
+            id: loop_id.clone(),
+            address: ready_address,
+            secure: tls.is_some(),
+            mjpeg_url,
+        };
+        let _ = app.emit("server-ready", event);
         loop {
             tokio::select! {
                 result = listener.accept() => {
-                    if let Ok((stream, _)) = result {
-                        let client_shutdown_rx = shutdown_tx_clone.subscribe();
-                        tokio::spawn(handle_client(stream, client_shutdown_rx));
-                    }
+                    let Ok((stream, remote_addr)) = result else { continue };
+                    let admission = if !limiter.allow() {
+                        Err(CloseReason::Busy)
+                    } else {
+                        match Arc::clone(&slots).try_acquire_owned() {
+                            Ok(permit) => Ok(Admission::Serve(permit)),
+                            Err(_) if counters.queued.load(Ordering::SeqCst) < queue_size => {
+                                Ok(Admission::Queue(Arc::clone(&slots)))
+                            }
+                            Err(_) => Err(CloseReason::Capacity),
+                        }
+                    };
+                    let admission = match admission {
+                        Ok(admission) => admission,
+                        Err(reason) => match Arc::clone(&rejects).try_acquire_owned() {
+                            Ok(rejecting) => Admission::Reject(reason, rejecting),
+                            // Đang từ chối quá nhiều kết nối: drop stream là đóng TCP luôn
+                            Err(_) => continue,
+                        },
+                    };
+
+                    let client_shutdown_rx = shutdown_tx_clone.subscribe();
+                    let registration = Registration::new("screen", Some(remote_addr));
+                    registration.set_server_id(&loop_id);
+                    let client = admit(
+                        stream,
+                        tls.clone(),
+                        admission,
+                        client_shutdown_rx,
+                        counters.clone(),
+                        Arc::clone(&capture),
+                        registration,
+                    );
+                    tokio::spawn(client);
+                }
+                result = accept_optional(mjpeg_listener.as_ref()) => {
+                    let Ok((stream, _)) = result else { continue };
+                    // Trình duyệt tự kết nối lại, không cần hàng đợi như WebSocket
+                    let admission = if !limiter.allow() {
+                        Err(CloseReason::Busy)
+                    } else {
+                        Arc::clone(&slots)
+                            .try_acquire_owned()
+                            .map_err(|_| CloseReason::Capacity)
+                    };
+                    let admission = match admission {
+                        Ok(permit) => Ok(permit),
+                        Err(reason) => match Arc::clone(&rejects).try_acquire_owned() {
+                            Ok(rejecting) => Err((reason, rejecting)),
+                            Err(_) => continue,
+                        },
+                    };
+
+                    let client_shutdown_rx = shutdown_tx_clone.subscribe();
+                    let client = admit_mjpeg(
+                        stream,
+                        tls.clone(),
+                        admission,
+                        client_shutdown_rx,
+                        counters.clone(),
+                        Arc::clone(&capture),
+                    );
+                    tokio::spawn(client);
+                }
+                // Phát từ chính vòng accept: vòng này kẹt thì heartbeat cũng dừng
+                _ = heartbeat.tick() => {
+                    let event = HeartbeatEvent {
+                        kind: "screen",
+                        id: loop_id.clone(),
+                        uptime_secs: started_at.elapsed().as_secs(),
+                        clients: counters.active.load(Ordering::SeqCst),
+                        rooms: None,
+                    };
+                    heartbeat::emit(&app, event);
                 }
                 _ = shutdown_rx.recv() => {
                     break;
                 }
             }
         }
-        SERVER_RUNNING.store(false, Ordering::SeqCst);
     });
 
-    Ok(format!("{}:{}", local_ip, port))
+    // Chỉ trả về khi vòng accept đã chạy: địa chỉ (và event "server-ready") kết nối được ngay
+    let _ = ready_rx.await;
+    Ok(StartedServer { id, address })
+}
+
+fn server_id(requested: Option<&str>) -> String {
+    requested
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .unwrap_or(DEFAULT_SERVER_ID)
+        .to_string()
+}
+
+// Bỏ trống id = dừng mọi server
+#[tauri::command]
+pub async fn stop_screen_server(id: Option<String>) -> Result<(), AppError> {
+    let mut servers = SCREEN_SERVERS.lock().unwrap_or_else(|e| e.into_inner());
+    let stopped: Vec<ScreenServer> = match id {
+        Some(id) => servers.remove(&id).into_iter().collect(),
+        None => servers.drain().map(|(_, server)| server).collect(),
+    };
+    for server in stopped {
+        let _ = server.shutdown_tx.send(());
+    }
+    Ok(())
+}
+
+// Bỏ trống id = có server nào đang chạy không
+#[tauri::command]
+pub fn is_server_running(id: Option<String>) -> bool {
+    SCREEN_SERVERS.lock().is_ok_and(|servers| match id {
+        Some(id) => servers.contains_key(&id),
+        None => !servers.is_empty(),
+    })
+}
+
+// Tổng viewer WebSocket của mọi screen server
+pub(crate) fn client_count() -> usize {
+    ACTIVE_CLIENTS.load(Ordering::SeqCst)
+}
+
+pub(crate) async fn screen_status() -> ScreenStatus {
+    let servers = SCREEN_SERVERS.lock().unwrap_or_else(|e| e.into_inner());
+    let oldest = servers.iter().min_by_key(|(_, server)| server.started_at);
+    let bound_addr = oldest.map(|(_, server)| server.bound_addr);
+    let capture = oldest.map(|(id, _)| capture_for(id));
+    let capture_backend = capture
+        .as_ref()
+        .and_then(|c| c.active_backend.lock().ok().and_then(|active| *active));
+    let output = capture
+        .as_ref()
+        .and_then(|c| c.output.lock().ok().and_then(|output| *output));
+    let server_count = servers.len();
+    ScreenStatus {
+        running: bound_addr.is_some(),
+        port: bound_addr.map(|a| a.port()),
+        bound_addr: bound_addr.map(|a| a.to_string()),
+        client_count: ACTIVE_CLIENTS.load(Ordering::SeqCst),
+        server_count,
+        capture_backend,
+        output,
+    }
 }
 
+// Đẩy frame của luồng capture của server `id` lên frontend của chính host qua event
+// "preview-frame", để xem đúng những gì viewer thấy mà không cần mở socket
 #[tauri::command]
-pub async fn stop_screen_server() -> Result<(), String> {
-    let mut server = SCREEN_SERVER.lock().await;
-    if let Some(tx) = server.shutdown_tx.take() {
-        let _ = tx.send(());
+pub async fn start_local_preview(app: AppHandle, id: Option<String>) -> Result<(), AppError> {
+    let mut preview = LOCAL_PREVIEW.lock().await;
+    if preview.as_ref().is_some_and(|h| !h.is_finished()) {
+        return Err(ServerError::AlreadyRunning.into());
+    }
+
+    let capture = capture_for(&server_id(id.as_deref()));
+    if let Ok(mut app_slot) = capture.app.lock() {
+        app_slot.get_or_insert_with(|| app.clone());
     }
-    SERVER_RUNNING.store(false, Ordering::SeqCst);
+
+    // Xem đúng tier mặc định mà viewer nhận
+    let mut frames = capture.subscribe(QualityTier::default());
+    *preview = Some(tokio::spawn(async move {
+        if let Some(frame) = capture.latest_frame(QualityTier::default()) {
+            let _ = app.emit("preview-frame", frame.base64.as_str());
+        }
+        loop {
+            match frames.recv().await {
+                Ok(frame) => {
+                    let _ = app.emit("preview-frame", frame.base64.as_str());
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(_) => break,
+            }
+        }
+    }));
     Ok(())
 }
 
 #[tauri::command]
-pub fn is_server_running() -> bool {
-    SERVER_RUNNING.load(Ordering::SeqCst)
+pub async fn stop_local_preview() -> bool {
+    match LOCAL_PREVIEW.lock().await.take() {
+        Some(handle) => {
+            handle.abort();
+            true
+        }
+        None => false,
+    }
+}
+
+// Bỏ trống id = cộng dồn mọi server
+#[tauri::command]
+pub async fn get_server_load(id: Option<String>) -> ServerLoad {
+    let servers = SCREEN_SERVERS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(id) = id {
+        return servers.get(&id).map(ScreenServer::load).unwrap_or_default();
+    }
+    servers
+        .values()
+        .map(ScreenServer::load)
+        .fold(ServerLoad::default(), |total, load| ServerLoad {
+            running: true,
+            active: total.active + load.active,
+            queued: total.queued + load.queued,
+            max_clients: total.max_clients + load.max_clients,
+            queue_size: total.queue_size + load.queue_size,
+        })
+}
+
+// cap = null để bỏ giới hạn, client đang kết nối áp dụng ngay từ frame kế tiếp
+#[tauri::command]
+pub fn set_client_cap(token: String, cap: Option<ClientCap>) {
+    let mut caps = CLIENT_CAPS.lock().unwrap_or_else(|e| e.into_inner());
+    match cap {
+        Some(cap) => caps.insert(token, cap),
+        None => caps.remove(&token),
+    };
+}
+
+// Thống kê luồng capture của server `id`, bỏ trống = "default"
+#[tauri::command]
+pub fn get_capture_stats(id: Option<String>) -> CaptureStatsSummary {
+    let capture = capture_for(&server_id(id.as_deref()));
+    let stats = capture.stats.lock().map(|s| *s).unwrap_or_default();
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            image::Rgba([(x % 256) as u8, (y % 256) as u8, ((x ^ y) % 256) as u8, 255])
+        })
+    }
+
+    #[test]
+    fn small_landscape_screen_is_not_resized() {
+        let config = CaptureConfig::default();
+        assert_eq!(target_size(&config, 1280, 720, 1.0), None);
+    }
+
+    #[test]
+    fn portrait_screen_is_resized_by_its_longest_edge() {
+        let config = CaptureConfig::default();
+        assert_eq!(target_size(&config, 1080, 1920, 1.0), Some((540, 960)));
+    }
+
+    #[test]
+    fn geometry_keeps_separate_scale_per_axis() {
+        // 1366x768 chia đôi có làm tròn: 683x384, hai chiều lệch tỉ lệ nhau
+        let geometry = FrameGeometry::new((683, 384), (1366, 768), (1920, 0));
+        assert_eq!(geometry.to_desktop(683.0, 384.0), (1920 + 1366, 768));
+        assert_eq!(geometry.to_desktop(0.0, 0.0), (1920, 0));
+    }
+
+    #[test]
+    fn downscaled_rounds_and_never_reaches_zero() {
+        assert_eq!(downscaled(1), 1);
+        assert_eq!(downscaled(2), 1);
+        assert_eq!(downscaled(1367), 684);
+        assert_eq!(downscaled(1920), 960);
+    }
+
+    #[test]
+    fn resize_of_one_pixel_image_does_not_panic() {
+        let (w, h) = (downscaled(1), downscaled(1));
+        let out = resize_image(gradient(1, 1), w, h, ResizeFilter::Triangle).unwrap();
+        assert_eq!(out.dimensions(), (1, 1));
+        // Kích thước 0 được đưa lên 1 thay vì panic trong imageops::resize
+        let out = resize_image(gradient(1, 1), 0, 0, ResizeFilter::Triangle).unwrap();
+        assert_eq!(out.dimensions(), (1, 1));
+    }
+
+    #[test]
+    fn resize_of_odd_sized_image_keeps_rounded_size_and_content() {
+        let img = RgbaImage::from_pixel(1367, 769, image::Rgba([200, 100, 50, 255]));
+        let (w, h) = (downscaled(1367), downscaled(769));
+        let out = resize_image(img, w, h, ResizeFilter::Triangle).unwrap();
+        assert_eq!(out.dimensions(), (684, 385));
+        // Ảnh một màu thì mép phải/dưới cũng phải giữ nguyên màu, không bị viền đen
+        assert_eq!(out.get_pixel(683, 384).0, [200, 100, 50, 255]);
+    }
+
+    // Hệ số lấy mẫu của thành phần đầu (Y) trong marker SOF: 0x11 = 4:4:4, 0x22 = 4:2:0
+    fn luma_sampling(jpeg: &[u8]) -> Option<u8> {
+        let sof = jpeg.windows(2).position(|m| m == [0xFF, 0xC0])?;
+        jpeg.get(sof + 11).copied()
+    }
+
+    #[test]
+    fn quarter_chroma_writes_real_420_jpeg() {
+        let img = gradient(256, 256);
+        let (mut full, mut quarter) = (Vec::new(), Vec::new());
+        encode_jpeg(&mut full, &img, 80, ChromaSubsampling::Full).unwrap();
+        encode_jpeg(&mut quarter, &img, 80, ChromaSubsampling::Quarter).unwrap();
+        assert_eq!(luma_sampling(&full), Some(0x11));
+        assert_eq!(luma_sampling(&quarter), Some(0x22));
+        assert!(quarter.len() < full.len());
+    }
+
+    #[test]
+    fn video_mode_keeps_the_configured_chroma() {
+        assert_eq!(ContentMode::Video.chroma(ChromaSubsampling::Full), ChromaSubsampling::Full);
+        assert_eq!(ContentMode::Text.chroma(ChromaSubsampling::Quarter), ChromaSubsampling::Full);
+    }
+
+    #[test]
+    fn rate_accountant_rejects_bytes_over_the_window_budget() {
+        // 80 kbps trong cửa sổ 1 s = 10_000 byte
+        let mut rate = RateAccountant::new(Duration::from_secs(1));
+        let start = Instant::now();
+        assert!(!rate.would_exceed_at(10_000, 80, start));
+        assert!(rate.would_exceed_at(10_001, 80, start));
+        rate.record_at(6_000, start);
+        assert!(!rate.would_exceed_at(4_000, 80, start));
+        assert!(rate.would_exceed_at(4_001, 80, start));
+    }
+
+    #[test]
+    fn rate_accountant_forgets_samples_outside_the_window() {
+        let mut rate = RateAccountant::new(Duration::from_secs(1));
+        let start = Instant::now();
+        rate.record_at(6_000, start);
+        rate.record_at(3_000, start + Duration::from_millis(600));
+        // Tới 1.2 s thì mẫu đầu đã trôi ra khỏi cửa sổ, chỉ còn 3_000 byte
+        let later = start + Duration::from_millis(1_200);
+        assert!(!rate.would_exceed_at(7_000, 80, later));
+        assert!(rate.would_exceed_at(7_001, 80, later));
+        assert_eq!(rate.total, 3_000);
+    }
+
+    #[test]
+    fn both_resize_filters_halve_1080p_and_4k() {
+        for (width, height) in [(1920, 1080), (3840, 2160)] {
+            let img = gradient(width, height);
+            let (w, h) = (downscaled(width), downscaled(height));
+            for filter in [ResizeFilter::Nearest, ResizeFilter::Triangle] {
+                let out = resize_image(img.clone(), w, h, filter).unwrap();
+                assert_eq!(out.dimensions(), (w, h));
+            }
+        }
+    }
+
+    // So thời gian phụ thuộc máy nên không chạy mặc định: cargo test --release -- --ignored.
+    // Nearest chỉ lấy mẫu một điểm nên phải nhanh hơn Triangle
+    #[test]
+    #[ignore]
+    fn nearest_resize_is_faster_than_triangle() {
+        for (width, height) in [(1920, 1080), (3840, 2160)] {
+            let img = gradient(width, height);
+            let (w, h) = (downscaled(width), downscaled(height));
+            let time = |filter: ResizeFilter| {
+                let started = Instant::now();
+                resize_image(img.clone(), w, h, filter).unwrap();
+                started.elapsed()
+            };
+            let nearest = time(ResizeFilter::Nearest);
+            let triangle = time(ResizeFilter::Triangle);
+            assert!(nearest < triangle, "{}x{}: {:?} vs {:?}", width, height, nearest, triangle);
+        }
+    }
+
+    #[test]
+    fn throughput_test_is_refused_during_cooldown_per_ip() {
+        let first: IpAddr = "192.0.2.10".parse().unwrap();
+        let second: IpAddr = "192.0.2.11".parse().unwrap();
+        assert!(throughput_allowed(Some(first)));
+        assert!(!throughput_allowed(Some(first)));
+        assert!(throughput_allowed(Some(second)));
+    }
+
+    #[test]
+    fn throughput_chunk_fits_the_bandwidth_cap() {
+        let capture = SharedCapture::new("throughput-chunk");
+        assert_eq!(capture.throughput_chunk(), THROUGHPUT_CHUNK);
+        capture.config.write().unwrap().max_kbps = Some(400);
+        let chunk = capture.throughput_chunk();
+        assert_eq!(chunk, 5_000);
+        assert!(capture.fits_budget(chunk));
+    }
 }
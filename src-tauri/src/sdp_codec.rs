@@ -0,0 +1,92 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+
+// Giá trị field "encoding" của offer/answer khi sdp là base64 của gzip
+pub const GZIP: &str = "gzip";
+// SDP nhỏ hơn mức này nén không lợi bao nhiêu
+const COMPRESS_MIN_BYTES: usize = 1024;
+// Chặn gzip bomb: SDP thật hiếm khi quá vài chục KB
+const MAX_SDP_BYTES: usize = 256 * 1024;
+
+pub enum SdpError {
+    TooLarge,
+    UnknownEncoding(String),
+    Malformed,
+}
+
+impl SdpError {
+    // Nội dung message error trả về cho bên gửi
+    pub fn message(&self) -> String {
+        match self {
+            SdpError::TooLarge => format!("SDP exceeds {} bytes", MAX_SDP_BYTES),
+            SdpError::UnknownEncoding(encoding) => format!("Unknown SDP encoding: {}", encoding),
+            SdpError::Malformed => "Malformed SDP".to_string(),
+        }
+    }
+}
+
+// Đưa sdp nhận được về dạng plaintext. Đọc dư một byte để biết bản giải nén có vượt giới
+// hạn không, thay vì cắt cụt rồi chuyển tiếp một SDP hỏng
+pub fn decode(sdp: String, encoding: Option<&str>) -> Result<String, SdpError> {
+    let plain = match encoding {
+        None => sdp,
+        Some(GZIP) => {
+            let compressed = STANDARD.decode(sdp).map_err(|_| SdpError::Malformed)?;
+            let mut plain = String::new();
+            GzDecoder::new(compressed.as_slice())
+                .take(MAX_SDP_BYTES as u64 + 1)
+                .read_to_string(&mut plain)
+                .map_err(|_| SdpError::Malformed)?;
+            plain
+        }
+        Some(other) => return Err(SdpError::UnknownEncoding(other.to_string())),
+    };
+    if plain.len() > MAX_SDP_BYTES {
+        return Err(SdpError::TooLarge);
+    }
+    Ok(plain)
+}
+
+// Nén cho bên nhận đã báo hỗ trợ gzip, còn lại gửi plaintext như cũ
+pub fn encode(sdp: String, gzip: bool) -> (String, Option<String>) {
+    if !gzip || sdp.len() < COMPRESS_MIN_BYTES {
+        return (sdp, None);
+    }
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    match encoder.write_all(sdp.as_bytes()).and_then(|_| encoder.finish()) {
+        Ok(compressed) => (STANDARD.encode(compressed), Some(GZIP.to_string())),
+        Err(_) => (sdp, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let sdp = "a=candidate:1 1 udp 2122260223 192.168.1.2 54321 typ host\r\n".repeat(40);
+        let (encoded, encoding) = encode(sdp.clone(), true);
+        assert_eq!(encoding.as_deref(), Some(GZIP));
+        assert!(matches!(decode(encoded, encoding.as_deref()), Ok(plain) if plain == sdp));
+    }
+
+    #[test]
+    fn rejects_oversize_after_inflate() {
+        let (encoded, encoding) = encode("a".repeat(MAX_SDP_BYTES + 1), true);
+        let result = decode(encoded, encoding.as_deref());
+        assert!(matches!(result, Err(SdpError::TooLarge)));
+
+        let (encoded, encoding) = encode("a".repeat(MAX_SDP_BYTES), true);
+        assert!(decode(encoded, encoding.as_deref()).is_ok());
+    }
+
+    #[test]
+    fn rejects_unknown_encoding() {
+        let result = decode("v=0".to_string(), Some("br"));
+        assert!(matches!(result, Err(SdpError::UnknownEncoding(e)) if e == "br"));
+    }
+}
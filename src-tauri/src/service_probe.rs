@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Instant};
+
+// Port thường chạy HTTP thuần / chạy TLS
+const HTTP_PORTS: &[u16] = &[80, 3000, 5000, 5985, 8000, 8008, 8080, 8888];
+const TLS_PORTS: &[u16] = &[443, 465, 636, 853, 993, 995, 5986, 8443];
+
+// Chỉ đọc phần đầu phản hồi, đủ để lấy status line và vài header
+const MAX_RESPONSE_BYTES: usize = 2048;
+const MAX_BANNER_CHARS: usize = 120;
+
+// ClientHello TLS 1.2 tối giản: không SNI, vài cipher suite phổ biến.
+// Server TLS sẽ trả ServerHello hoặc alert, cả hai đều đủ để nhận ra TLS.
+const TLS_CLIENT_HELLO: &[u8] = &[
+    0x16, 0x03, 0x01, 0x00, 0x35, 0x01, 0x00, 0x00, 0x31, 0x03, 0x03, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x08, 0xc0, 0x2f, 0xc0, 0x2b, 0x00, 0x9c, 0x00, 0x2f, 0x01, 0x00, 0x00, 0x00,
+];
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PortService {
+    pub port: u16,
+    // "http", "https", "tls", "ssh" hoặc "tcp" (mở nhưng không nhận ra giao thức)
+    pub service: Option<String>,
+    // Header Server của HTTP hoặc dòng chào đầu tiên server tự gửi (SSH, FTP, SMTP)
+    pub banner: Option<String>,
+}
+
+// Một kết nối, một probe nhỏ, tổng thời gian không quá `wait`
+pub async fn identify(ip: IpAddr, port: u16, wait: Duration) -> PortService {
+    let deadline = Instant::now() + wait;
+    let mut result = PortService {
+        port,
+        ..Default::default()
+    };
+
+    let response = if TLS_PORTS.contains(&port) {
+        exchange(ip, port, TLS_CLIENT_HELLO, deadline).await
+    } else if HTTP_PORTS.contains(&port) {
+        let request = format!("GET / HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n", ip);
+        exchange(ip, port, request.as_bytes(), deadline).await
+    } else {
+        // Port lạ: chỉ chờ server tự chào, không gửi gì
+        exchange(ip, port, &[], deadline).await
+    };
+
+    let Some(response) = response else {
+        return result;
+    };
+
+    if is_tls_record(&response) {
+        let name = if port == 443 || port == 8443 { "https" } else { "tls" };
+        result.service = Some(name.to_string());
+    } else if response.starts_with(b"HTTP/") {
+        result.service = Some("http".to_string());
+        result.banner = http_server_header(&response).or_else(|| first_line(&response));
+    } else if response.starts_with(b"SSH-") {
+        result.service = Some("ssh".to_string());
+        result.banner = first_line(&response);
+    } else {
+        result.service = Some("tcp".to_string());
+        result.banner = first_line(&response);
+    }
+    result
+}
+
+// None nếu không kết nối được; Some(rỗng) nếu kết nối được mà server không nói gì
+async fn exchange(ip: IpAddr, port: u16, probe: &[u8], deadline: Instant) -> Option<Vec<u8>> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    let mut stream = timeout(remaining, TcpStream::connect(SocketAddr::new(ip, port)))
+        .await
+        .ok()?
+        .ok()?;
+
+    if !probe.is_empty() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if !matches!(timeout(remaining, stream.write_all(probe)).await, Ok(Ok(()))) {
+            return Some(Vec::new());
+        }
+    }
+
+    let mut buf = vec![0u8; MAX_RESPONSE_BYTES];
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    match timeout(remaining, stream.read(&mut buf)).await {
+        Ok(Ok(n)) => {
+            buf.truncate(n);
+            Some(buf)
+        }
+        _ => Some(Vec::new()),
+    }
+}
+
+// Content type handshake (0x16) hoặc alert (0x15), version 3.x
+fn is_tls_record(data: &[u8]) -> bool {
+    data.len() >= 3 && (data[0] == 0x16 || data[0] == 0x15) && data[1] == 0x03
+}
+
+fn http_server_header(data: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("server").then(|| clean_banner(value))
+        })
+        .flatten()
+}
+
+fn first_line(data: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(data).lines().next().and_then(clean_banner)
+}
+
+// Bỏ ký tự điều khiển và cắt ngắn để banner hiển thị được trên UI
+fn clean_banner(text: &str) -> Option<String> {
+    let cleaned: String = text
+        .trim()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_BANNER_CHARS)
+        .collect();
+    (!cleaned.is_empty()).then_some(cleaned)
+}
@@ -0,0 +1,45 @@
+use serde::Serialize;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::timeout;
+
+use crate::health::stop_health_server;
+use crate::screen_share::{is_server_running, stop_local_preview, stop_screen_server};
+use crate::signaling::{is_signaling_running, stop_signaling_server};
+use crate::stop_scan_watch;
+
+// Mỗi bước dọn dẹp chờ tối đa chừng này, để đóng app không bị treo
+const STEP_TIMEOUT: Duration = Duration::from_secs(2);
+
+// true = thứ đó đang chạy và đã được dừng
+#[derive(Serialize, Clone, Default)]
+pub struct ShutdownSummary {
+    screen_server: bool,
+    signaling: bool,
+    scan_watch: bool,
+    local_preview: bool,
+    health_server: bool,
+}
+
+async fn bounded<T>(step: impl Future<Output = T>) -> Option<T> {
+    timeout(STEP_TIMEOUT, step).await.ok()
+}
+
+// Dừng mọi server/task nền để không còn listener nào giữ port sau khi UI đóng
+#[tauri::command]
+pub async fn shutdown_all() -> ShutdownSummary {
+    let mut summary = ShutdownSummary::default();
+
+    if is_server_running(None) {
+        summary.screen_server = matches!(bounded(stop_screen_server(None)).await, Some(Ok(())));
+    }
+    // stop_signaling_server cũng xoá ROOMS
+    if is_signaling_running() {
+        summary.signaling = matches!(bounded(stop_signaling_server()).await, Some(Ok(())));
+    }
+    summary.scan_watch = bounded(stop_scan_watch()).await.unwrap_or(false);
+    summary.local_preview = bounded(stop_local_preview()).await.unwrap_or(false);
+    summary.health_server = stop_health_server();
+
+    summary
+}
@@ -1,32 +1,84 @@
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{broadcast, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, Mutex, Notify, RwLock};
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
 use tokio_tungstenite::{accept_async, tungstenite::Message};
 
+use crate::bind_addr::{bind_listener, resolve_bind_ip};
+use crate::close_code::CloseReason;
+use crate::connections::Registration;
+use crate::error::{AppError, ServerError};
+use crate::heartbeat::{self, HeartbeatEvent};
+use crate::remote_input::{self, InputEvent, KeyAction, MouseAction, MouseButton};
+use crate::sdp_codec::{self, SdpError};
+
 static SIGNALING_RUNNING: AtomicBool = AtomicBool::new(false);
+// Số phòng tối đa, 0 = không giới hạn
+static MAX_ROOMS: AtomicUsize = AtomicUsize::new(0);
+static NEXT_ROOM_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+const DEFAULT_MAX_MESSAGES_PER_SEC: u32 = 50;
+const DEFAULT_QUEUE_CAPACITY: usize = 256;
+const CLOSE_GRACE: Duration = Duration::from_secs(1);
+// Candidate của viewer gửi trước khi host nhận viewer (gửi offer) được giữ tạm
+const MAX_PENDING_ICE: usize = 32;
+const PENDING_ICE_TTL: Duration = Duration::from_secs(10);
+const MAX_PARSE_ERROR_CHARS: usize = 200;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ViewerRole {
+    #[default]
+    View,
+    Control,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 enum SignalMessage {
+    // gzip = true: connection nhận được sdp nén (encoding "gzip")
     #[serde(rename = "host")]
-    Host { room: String },
+    Host {
+        room: String,
+        #[serde(default)]
+        gzip: bool,
+        #[serde(default)]
+        title: Option<String>,
+        #[serde(rename = "hostName", default)]
+        host_name: Option<String>,
+    },
     #[serde(rename = "viewer")]
-    Viewer { room: String },
+    Viewer {
+        room: String,
+        #[serde(default)]
+        role: ViewerRole,
+        #[serde(default)]
+        gzip: bool,
+    },
+    // Renegotiation giữa phiên (vd thêm track) dùng lại đúng offer/answer này, không có message
+    // riêng: host gửi offer mới cho viewer đang trong phòng, viewer trả answer như lần đầu
     #[serde(rename = "offer")]
     Offer {
         #[serde(rename = "viewerId")]
         viewer_id: String,
         sdp: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        encoding: Option<String>,
     },
     #[serde(rename = "answer")]
     Answer {
         #[serde(rename = "viewerId")]
         viewer_id: Option<String>,
         sdp: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        encoding: Option<String>,
     },
     #[serde(rename = "ice-candidate")]
     IceCandidate {
@@ -46,101 +98,560 @@ enum SignalMessage {
     },
     #[serde(rename = "host-left")]
     HostLeft,
+    // Gửi cho host khi tạo phòng và khi số viewer đổi, gửi cho viewer lúc join
+    #[serde(rename = "room-info")]
+    RoomInfo {
+        room: String,
+        title: Option<String>,
+        #[serde(rename = "hostName")]
+        host_name: Option<String>,
+        // Unix millis
+        #[serde(rename = "createdAt")]
+        created_at: u64,
+        #[serde(rename = "viewerCount")]
+        viewer_count: usize,
+    },
+    // Gửi cho host và mọi viewer mỗi khi có viewer vào/ra phòng
+    #[serde(rename = "viewer-count")]
+    ViewerCount { count: usize },
+    #[serde(rename = "input-mouse")]
+    InputMouse {
+        x: f64,
+        y: f64,
+        button: Option<MouseButton>,
+        action: MouseAction,
+    },
+    #[serde(rename = "input-key")]
+    InputKey { code: String, action: KeyAction },
+    // Viewer xin quyền điều khiển, server điền viewerId trước khi chuyển cho host
+    #[serde(rename = "request-control")]
+    RequestControl {
+        #[serde(rename = "viewerId", default)]
+        viewer_id: Option<String>,
+    },
+    #[serde(rename = "grant-control")]
+    GrantControl {
+        #[serde(rename = "viewerId")]
+        viewer_id: String,
+    },
+    // Host thu hồi, hoặc viewer tự trả quyền (viewerId bỏ trống)
+    #[serde(rename = "revoke-control")]
+    RevokeControl {
+        #[serde(rename = "viewerId", default)]
+        viewer_id: Option<String>,
+    },
+    // Viewer gửi kích thước màn hình/cửa sổ khi join, server điền viewerId rồi chuyển cho host
+    #[serde(rename = "viewer-info")]
+    ViewerInfo {
+        #[serde(rename = "viewerId", default)]
+        viewer_id: Option<String>,
+        #[serde(flatten)]
+        display: ViewerDisplay,
+    },
+    // code/retryAfter theo bảng mã trong close_code, client dùng để backoff
     #[serde(rename = "error")]
-    Error { message: String },
+    Error {
+        message: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        code: Option<u16>,
+        #[serde(rename = "retryAfter", default, skip_serializing_if = "Option::is_none")]
+        retry_after: Option<u64>,
+    },
+}
+
+impl SignalMessage {
+    // Trùng với tag "type" trên wire
+    fn kind(&self) -> &'static str {
+        match self {
+            SignalMessage::Host { .. } => "host",
+            SignalMessage::Viewer { .. } => "viewer",
+            SignalMessage::Offer { .. } => "offer",
+            SignalMessage::Answer { .. } => "answer",
+            SignalMessage::IceCandidate { .. } => "ice-candidate",
+            SignalMessage::ViewerJoined { .. } => "viewer-joined",
+            SignalMessage::ViewerLeft { .. } => "viewer-left",
+            SignalMessage::HostLeft => "host-left",
+            SignalMessage::RoomInfo { .. } => "room-info",
+            SignalMessage::ViewerCount { .. } => "viewer-count",
+            SignalMessage::InputMouse { .. } => "input-mouse",
+            SignalMessage::InputKey { .. } => "input-key",
+            SignalMessage::RequestControl { .. } => "request-control",
+            SignalMessage::GrantControl { .. } => "grant-control",
+            SignalMessage::RevokeControl { .. } => "revoke-control",
+            SignalMessage::ViewerInfo { .. } => "viewer-info",
+            SignalMessage::Error { .. } => "error",
+        }
+    }
+}
+
+fn parse_error(e: &serde_json::Error) -> SignalMessage {
+    let detail: String = e.to_string().chars().take(MAX_PARSE_ERROR_CHARS).collect();
+    SignalMessage::Error {
+        message: format!("Invalid message: {}", detail),
+        code: None,
+        retry_after: None,
+    }
+}
+
+fn sdp_error(e: SdpError) -> SignalMessage {
+    SignalMessage::Error {
+        message: e.message(),
+        code: None,
+        retry_after: None,
+    }
+}
+
+struct Outbound {
+    msg: Message,
+    // ICE candidate có thể bỏ được, offer/answer/thông báo thì không
+    droppable: bool,
+}
+
+#[derive(Default)]
+struct QueueState {
+    items: VecDeque<Outbound>,
+    closed: bool,
+    // Lý do gửi kèm Close frame khi task gửi kết thúc
+    close_reason: Option<CloseReason>,
+}
+
+// Hàng đợi gửi có giới hạn để ws chậm không làm phình bộ nhớ
+struct OutboundQueue {
+    state: std::sync::Mutex<QueueState>,
+    notify: Notify,
+    capacity: usize,
+    // Bên kia đã báo nhận được sdp nén
+    gzip: AtomicBool,
+}
+
+impl OutboundQueue {
+    fn new(capacity: usize) -> Self {
+        Self {
+            state: std::sync::Mutex::new(QueueState::default()),
+            notify: Notify::new(),
+            capacity: capacity.max(1),
+            gzip: AtomicBool::new(false),
+        }
+    }
+
+    fn supports_gzip(&self) -> bool {
+        self.gzip.load(Ordering::SeqCst)
+    }
+
+    fn send_signal(&self, signal: &SignalMessage) {
+        let droppable = matches!(signal, SignalMessage::IceCandidate { .. });
+        let msg = Message::Text(serde_json::to_string(signal).unwrap());
+        self.push(Outbound { msg, droppable });
+    }
+
+    fn push(&self, outbound: Outbound) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        if state.closed {
+            return;
+        }
+
+        if state.items.len() >= self.capacity {
+            // Đầy: bỏ ICE candidate cũ nhất, nếu không có thì bỏ message mới nếu bỏ được
+            if let Some(pos) = state.items.iter().position(|o| o.droppable) {
+                state.items.remove(pos);
+                log::warn!("queue full, dropped oldest ice-candidate");
+            } else if outbound.droppable {
+                log::warn!("queue full, dropped new ice-candidate");
+                return;
+            } else {
+                // Không thể mất message quan trọng: đóng connection
+                log::warn!("queue full with critical messages, closing connection");
+                state.closed = true;
+                state.items.clear();
+                drop(state);
+                self.notify.notify_one();
+                return;
+            }
+        }
+
+        state.items.push_back(outbound);
+        drop(state);
+        self.notify.notify_one();
+    }
+
+    // Dừng nhận message mới, task gửi vẫn xả hết những gì đang chờ
+    fn close(&self, reason: Option<CloseReason>) {
+        if let Ok(mut state) = self.state.lock() {
+            state.closed = true;
+            state.close_reason = state.close_reason.or(reason);
+        }
+        self.notify.notify_one();
+    }
+
+    fn close_frame(&self) -> Option<CloseFrame<'static>> {
+        let state = self.state.lock().ok()?;
+        state.close_reason.map(CloseReason::frame)
+    }
+
+    async fn pop(&self) -> Option<Message> {
+        loop {
+            {
+                let mut state = self.state.lock().ok()?;
+                if let Some(outbound) = state.items.pop_front() {
+                    return Some(outbound.msg);
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            self.notify.notified().await;
+        }
+    }
 }
 
-type Tx = tokio::sync::mpsc::UnboundedSender<Message>;
+type Tx = Arc<OutboundQueue>;
 
 struct Room {
+    // Mỗi lần tạo phòng một số mới. Kết nối chỉ dọn phòng đúng lần tạo mình đã vào, vì sau
+    // close_room hay host tạo lại, mã phòng có thể đã thuộc về phòng khác
+    generation: u64,
     host_tx: Option<Tx>,
+    title: Option<String>,
+    host_name: Option<String>,
+    created_at: u64,
     viewers: HashMap<String, Tx>,
+    // Host phải bật thủ công cho từng phiên, mặc định tắt
+    allow_control: bool,
+    // Viewer duy nhất đang được host cấp quyền điều khiển
+    controller: Option<String>,
+    stats: RoomStats,
+    // Viewer mà host đã gửi offer, candidate của họ được chuyển thẳng cho host
+    acked_viewers: HashSet<String>,
+    pending_ice: HashMap<String, PendingIce>,
+    // ViewerInfo mới nhất của từng viewer
+    displays: HashMap<String, ViewerDisplay>,
+}
+
+// Kích thước theo CSS pixel, dpr = devicePixelRatio của viewer
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ViewerDisplay {
+    width: u32,
+    height: u32,
+    #[serde(default = "default_dpr")]
+    dpr: f64,
+}
+
+fn default_dpr() -> f64 {
+    1.0
+}
+
+impl Room {
+    fn info(&self, code: &str) -> SignalMessage {
+        SignalMessage::RoomInfo {
+            room: code.to_string(),
+            title: self.title.clone(),
+            host_name: self.host_name.clone(),
+            created_at: self.created_at,
+            viewer_count: self.viewers.len(),
+        }
+    }
+
+    fn notify_host_info(&self, code: &str) {
+        if let Some(host_tx) = &self.host_tx {
+            host_tx.send_signal(&self.info(code));
+        }
+    }
+
+    // Gọi khi đang giữ write lock của ROOMS nên số viewer luôn khớp với lần vào/ra vừa xảy ra
+    fn broadcast_viewer_count(&self) {
+        let msg = SignalMessage::ViewerCount { count: self.viewers.len() };
+        for tx in self.host_tx.iter().chain(self.viewers.values()) {
+            tx.send_signal(&msg);
+        }
+    }
+}
+
+struct PendingIce {
+    since: Instant,
+    candidates: VecDeque<serde_json::Value>,
+}
+
+#[derive(Serialize, Clone, Copy, Default)]
+pub struct TrafficCount {
+    messages: u64,
+    bytes: u64,
+}
+
+impl TrafficCount {
+    fn add(&mut self, bytes: usize) {
+        self.messages += 1;
+        self.bytes += bytes as u64;
+    }
+}
+
+// Lưu lượng client gửi vào room, mất cùng room khi host rời đi
+#[derive(Serialize, Clone, Default)]
+pub struct RoomStats {
+    total: TrafficCount,
+    by_type: HashMap<String, TrafficCount>,
+}
+
+enum RateDecision {
+    Allow,
+    Drop { notify: bool },
+    Close,
+}
+
+// Token bucket cho từng connection, nằm trong task nên tự dọn khi ngắt kết nối
+struct RateLimiter {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+    dropped: u32,
+}
+
+impl RateLimiter {
+    fn new(max_per_sec: u32) -> Self {
+        let capacity = max_per_sec.max(1) as f64;
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_per_sec: capacity,
+            last_refill: Instant::now(),
+            dropped: 0,
+        }
+    }
+
+    fn check(&mut self) -> RateDecision {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            self.dropped = 0;
+            return RateDecision::Allow;
+        }
+
+        self.dropped += 1;
+        // Flood kéo dài quá 2 lần capacity thì đóng luôn
+        if self.dropped as f64 > self.capacity * 2.0 {
+            RateDecision::Close
+        } else {
+            RateDecision::Drop {
+                notify: self.dropped == 1,
+            }
+        }
+    }
 }
 
 lazy_static::lazy_static! {
     static ref ROOMS: Arc<RwLock<HashMap<String, Room>>> = Arc::new(RwLock::new(HashMap::new()));
     static ref SHUTDOWN_TX: Arc<Mutex<Option<broadcast::Sender<()>>>> = Arc::new(Mutex::new(None));
+    static ref BOUND_ADDR: std::sync::Mutex<Option<SocketAddr>> = std::sync::Mutex::new(None);
 }
 
-async fn handle_connection(stream: TcpStream, mut shutdown_rx: broadcast::Receiver<()>) {
+#[derive(Serialize, Clone, Default)]
+pub struct SignalingStatus {
+    running: bool,
+    port: Option<u16>,
+    bound_addr: Option<String>,
+    room_count: usize,
+    // None = không giới hạn
+    max_rooms: Option<usize>,
+}
+
+fn bound_addr() -> Option<SocketAddr> {
+    BOUND_ADDR.lock().ok().and_then(|addr| *addr)
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    mut shutdown_rx: broadcast::Receiver<()>,
+    max_messages_per_sec: u32,
+    queue_capacity: usize,
+) {
+    let registration = Registration::new("signaling", stream.peer_addr().ok());
+    let dropped = registration.drop_signal();
     let ws_stream = match accept_async(stream).await {
         Ok(ws) => ws,
         Err(_) => return,
     };
 
     let (mut ws_tx, mut ws_rx) = ws_stream.split();
-    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Message>();
+    let tx: Tx = Arc::new(OutboundQueue::new(queue_capacity));
+    let queue = Arc::clone(&tx);
 
     let mut room_code: Option<String> = None;
+    let mut room_generation = 0;
     let mut is_host = false;
     let mut viewer_id: Option<String> = None;
+    let mut limiter = RateLimiter::new(max_messages_per_sec);
+    let mut close_reason: Option<CloseReason> = None;
 
     // Task gửi message
-    let send_task = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
+    let mut send_task = tokio::spawn(async move {
+        while let Some(msg) = queue.pop().await {
             if ws_tx.send(msg).await.is_err() {
-                break;
+                return;
             }
         }
+        let _ = ws_tx.send(Message::Close(queue.close_frame())).await;
+        let _ = ws_tx.close().await;
     });
+    let mut send_done = false;
 
     // Nhận message
     loop {
         tokio::select! {
             _ = shutdown_rx.recv() => break,
+            _ = dropped.notified() => {
+                close_reason = Some(CloseReason::Dropped);
+                break;
+            }
+            // Task gửi dừng (ws lỗi hoặc queue bị đóng) thì ngắt luôn
+            _ = &mut send_task => {
+                send_done = true;
+                break;
+            }
             msg = ws_rx.next() => {
                 match msg {
                     Some(Ok(Message::Text(text))) => {
-                        if let Ok(signal) = serde_json::from_str::<SignalMessage>(&text) {
+                        match limiter.check() {
+                            RateDecision::Allow => {}
+                            RateDecision::Drop { notify } => {
+                                if notify {
+                                    let msg = SignalMessage::Error {
+                                        message: "Rate limit exceeded".to_string(),
+                                        code: Some(CloseReason::Busy.code()),
+                                        retry_after: CloseReason::Busy.retry_after_secs(),
+                                    };
+                                    tx.send_signal(&msg);
+                                }
+                                continue;
+                            }
+                            RateDecision::Close => {
+                                close_reason = Some(CloseReason::Busy);
+                                break;
+                            }
+                        }
+
+                        let parsed = serde_json::from_str::<SignalMessage>(&text);
+                        // Báo lại lỗi parse (vd sai tên field camelCase) thay vì bỏ qua im lặng
+                        if let Err(e) = &parsed {
+                            log::warn!("invalid message: {}", e);
+                            tx.send_signal(&parse_error(e));
+                        }
+                        if let Ok(signal) = parsed {
+                            let kind = signal.kind();
                             match signal {
-                                SignalMessage::Host { room } => {
+                                SignalMessage::Host { room, gzip, title, host_name } => {
+                                    tx.gzip.store(gzip, Ordering::SeqCst);
                                     let mut rooms = ROOMS.write().await;
-                                    rooms.insert(room.clone(), Room {
+                                    // Host tạo lại phòng đang có thì không làm tăng số phòng
+                                    let max_rooms = MAX_ROOMS.load(Ordering::SeqCst);
+                                    let is_new = !rooms.contains_key(&room);
+                                    if max_rooms > 0 && is_new && rooms.len() >= max_rooms {
+                                        let msg = SignalMessage::Error {
+                                            message: "server at capacity".to_string(),
+                                            code: Some(CloseReason::Capacity.code()),
+                                            retry_after: CloseReason::Capacity.retry_after_secs(),
+                                        };
+                                        tx.send_signal(&msg);
+                                        continue;
+                                    }
+                                    let generation =
+                                        NEXT_ROOM_GENERATION.fetch_add(1, Ordering::SeqCst);
+                                    let created = Room {
+                                        generation,
                                         host_tx: Some(tx.clone()),
+                                        title,
+                                        host_name,
+                                        created_at: crate::now_millis(),
                                         viewers: HashMap::new(),
-                                    });
+                                        allow_control: false,
+                                        controller: None,
+                                        stats: RoomStats::default(),
+                                        acked_viewers: HashSet::new(),
+                                        pending_ice: HashMap::new(),
+                                        displays: HashMap::new(),
+                                    };
+                                    // Xác nhận cho host phòng đã được tạo
+                                    created.notify_host_info(&room);
+                                    rooms.insert(room.clone(), created);
+                                    registration.set_role("host");
+                                    registration.set_room(&room);
                                     room_code = Some(room);
+                                    room_generation = generation;
                                     is_host = true;
                                 }
-                                SignalMessage::Viewer { room } => {
+                                SignalMessage::Viewer { room, role, gzip } => {
+                                    tx.gzip.store(gzip, Ordering::SeqCst);
                                     let mut rooms = ROOMS.write().await;
                                     if let Some(r) = rooms.get_mut(&room) {
                                         let vid = uuid::Uuid::new_v4().to_string();
                                         r.viewers.insert(vid.clone(), tx.clone());
                                         viewer_id = Some(vid.clone());
-                                        room_code = Some(room);
+                                        tx.send_signal(&r.info(&room));
 
-                                        // Thông báo host
+                                        // Thông báo host, viewer muốn điều khiển thì host phải duyệt
                                         if let Some(host_tx) = &r.host_tx {
-                                            let msg = SignalMessage::ViewerJoined { viewer_id: vid };
-                                            let _ = host_tx.send(Message::Text(serde_json::to_string(&msg).unwrap()));
+                                            let msg = SignalMessage::ViewerJoined { viewer_id: vid.clone() };
+                                            host_tx.send_signal(&msg);
+                                            if role == ViewerRole::Control {
+                                                let msg = SignalMessage::RequestControl { viewer_id: Some(vid) };
+                                                host_tx.send_signal(&msg);
+                                            }
                                         }
+                                        r.notify_host_info(&room);
+                                        r.broadcast_viewer_count();
+                                        registration.set_role("viewer");
+                                        registration.set_room(&room);
+                                        room_code = Some(room);
+                                        room_generation = r.generation;
                                     } else {
-                                        let msg = SignalMessage::Error { message: "Room not found".to_string() };
-                                        let _ = tx.send(Message::Text(serde_json::to_string(&msg).unwrap()));
+                                        let msg = SignalMessage::Error {
+                                            message: "Room not found".to_string(),
+                                            code: None,
+                                            retry_after: None,
+                                        };
+                                        tx.send_signal(&msg);
                                     }
                                 }
-                                SignalMessage::Offer { viewer_id: vid, sdp } => {
+                                // SDP được giải nén ở server rồi nén lại tuỳ khả năng của bên nhận
+                                SignalMessage::Offer { viewer_id: vid, sdp, encoding } => {
+                                    let sdp = match sdp_codec::decode(sdp, encoding.as_deref()) {
+                                        Ok(sdp) => sdp,
+                                        Err(e) => {
+                                            tx.send_signal(&sdp_error(e));
+                                            continue;
+                                        }
+                                    };
                                     if let Some(ref room) = room_code {
-                                        let rooms = ROOMS.read().await;
-                                        if let Some(r) = rooms.get(room) {
-                                            if let Some(viewer_tx) = r.viewers.get(&vid) {
-                                                let msg = SignalMessage::Offer { viewer_id: vid, sdp };
-                                                let _ = viewer_tx.send(Message::Text(serde_json::to_string(&msg).unwrap()));
-                                            }
+                                        if !forward_offer(room, vid, sdp).await {
+                                            let msg = SignalMessage::Error {
+                                                message: "Viewer not found".to_string(),
+                                                code: None,
+                                                retry_after: None,
+                                            };
+                                            tx.send_signal(&msg);
                                         }
                                     }
                                 }
-                                SignalMessage::Answer { viewer_id: _, sdp } => {
-                                    if let Some(ref room) = room_code {
-                                        if let Some(ref vid) = viewer_id {
-                                            let rooms = ROOMS.read().await;
-                                            if let Some(r) = rooms.get(room) {
-                                                if let Some(host_tx) = &r.host_tx {
-                                                    let msg = SignalMessage::Answer { viewer_id: Some(vid.clone()), sdp };
-                                                    let _ = host_tx.send(Message::Text(serde_json::to_string(&msg).unwrap()));
-                                                }
-                                            }
+                                SignalMessage::Answer { viewer_id: _, sdp, encoding } => {
+                                    let sdp = match sdp_codec::decode(sdp, encoding.as_deref()) {
+                                        Ok(sdp) => sdp,
+                                        Err(e) => {
+                                            tx.send_signal(&sdp_error(e));
+                                            continue;
+                                        }
+                                    };
+                                    if let (Some(ref room), Some(ref vid)) = (&room_code, &viewer_id) {
+                                        let rooms = ROOMS.read().await;
+                                        if let Some(host_tx) = rooms.get(room).and_then(|r| r.host_tx.as_ref()) {
+                                            let (sdp, encoding) = sdp_codec::encode(sdp, host_tx.supports_gzip());
+                                            let msg = SignalMessage::Answer { viewer_id: Some(vid.clone()), sdp, encoding };
+                                            host_tx.send_signal(&msg);
                                         }
                                     }
                                 }
@@ -153,21 +664,53 @@ async fn handle_connection(stream: TcpStream, mut shutdown_rx: broadcast::Receiv
                                                 if let Some(vid) = target_vid {
                                                     if let Some(viewer_tx) = r.viewers.get(&vid) {
                                                         let msg = SignalMessage::IceCandidate { viewer_id: Some(vid), candidate };
-                                                        let _ = viewer_tx.send(Message::Text(serde_json::to_string(&msg).unwrap()));
+                                                        viewer_tx.send_signal(&msg);
                                                     }
                                                 }
-                                            } else {
+                                            } else if let Some(ref vid) = viewer_id {
                                                 // Viewer gửi cho host
-                                                if let Some(host_tx) = &r.host_tx {
-                                                    let msg = SignalMessage::IceCandidate { viewer_id: viewer_id.clone(), candidate };
-                                                    let _ = host_tx.send(Message::Text(serde_json::to_string(&msg).unwrap()));
-                                                }
+                                                drop(rooms);
+                                                relay_viewer_candidate(room, vid, candidate).await;
                                             }
                                         }
                                     }
                                 }
+                                SignalMessage::RequestControl { .. } if !is_host => {
+                                    if let (Some(room), Some(vid)) = (&room_code, &viewer_id) {
+                                        let rooms = ROOMS.read().await;
+                                        if let Some(host_tx) = rooms.get(room).and_then(|r| r.host_tx.as_ref()) {
+                                            let msg = SignalMessage::RequestControl { viewer_id: Some(vid.clone()) };
+                                            host_tx.send_signal(&msg);
+                                        }
+                                    }
+                                }
+                                SignalMessage::GrantControl { viewer_id: vid } if is_host => {
+                                    if let Some(ref room) = room_code {
+                                        grant_control(room, vid).await;
+                                    }
+                                }
+                                SignalMessage::RevokeControl { viewer_id: target_vid } => {
+                                    if let Some(ref room) = room_code {
+                                        // Viewer chỉ được trả quyền của chính mình
+                                        let target = if is_host { target_vid } else { viewer_id.clone() };
+                                        revoke_control(room, target.as_deref(), !is_host).await;
+                                    }
+                                }
+                                SignalMessage::ViewerInfo { display, .. } if !is_host => {
+                                    if let (Some(room), Some(vid)) = (&room_code, &viewer_id) {
+                                        relay_viewer_info(room, vid, display).await;
+                                    }
+                                }
+                                // Chỉ nhận input từ viewer đang giữ quyền, khi host đã cho phép
+                                SignalMessage::InputMouse { x, y, button, action } if !is_host => {
+                                    forward_input(&room_code, &viewer_id, InputEvent::Mouse { x, y, button, action }).await;
+                                }
+                                SignalMessage::InputKey { code, action } if !is_host => {
+                                    forward_input(&room_code, &viewer_id, InputEvent::Key { code, action }).await;
+                                }
                                 _ => {}
                             }
+                            record_traffic(&room_code, kind, text.len()).await;
                         }
                     }
                     Some(Ok(Message::Close(_))) | None => break,
@@ -181,36 +724,234 @@ async fn handle_connection(stream: TcpStream, mut shutdown_rx: broadcast::Receiv
     if let Some(room) = room_code {
         let mut rooms = ROOMS.write().await;
         if is_host {
-            if let Some(r) = rooms.get(&room) {
-                for (_, viewer_tx) in &r.viewers {
+            let own = rooms.get(&room).is_some_and(|r| r.generation == room_generation);
+            if let Some(r) = own.then(|| rooms.remove(&room)).flatten() {
+                for viewer_tx in r.viewers.values() {
                     let msg = SignalMessage::HostLeft;
-                    let _ = viewer_tx.send(Message::Text(serde_json::to_string(&msg).unwrap()));
+                    viewer_tx.send_signal(&msg);
                 }
             }
-            rooms.remove(&room);
         } else if let Some(vid) = viewer_id {
-            if let Some(r) = rooms.get_mut(&room) {
+            let own = rooms.get_mut(&room).filter(|r| r.generation == room_generation);
+            // Chỉ báo host khi viewer thực sự còn trong phòng
+            if let Some(r) = own.filter(|r| r.viewers.contains_key(&vid)) {
                 r.viewers.remove(&vid);
+                r.acked_viewers.remove(&vid);
+                r.pending_ice.remove(&vid);
+                r.displays.remove(&vid);
+                if r.controller.as_deref() == Some(vid.as_str()) {
+                    r.controller = None;
+                }
                 if let Some(host_tx) = &r.host_tx {
                     let msg = SignalMessage::ViewerLeft { viewer_id: vid };
-                    let _ = host_tx.send(Message::Text(serde_json::to_string(&msg).unwrap()));
+                    host_tx.send_signal(&msg);
                 }
+                r.notify_host_info(&room);
+                r.broadcast_viewer_count();
             }
         }
     }
 
-    send_task.abort();
+    // Gửi nốt message đang chờ rồi Close, quá hạn mới abort
+    if !send_done {
+        tx.close(close_reason);
+        if tokio::time::timeout(CLOSE_GRACE, &mut send_task).await.is_err() {
+            send_task.abort();
+        }
+    }
+}
+
+// Offer của host coi như host đã sẵn sàng cho viewer: chuyển offer rồi xả candidate đang giữ.
+// false = viewer không còn trong phòng (đã rời hoặc sai id)
+async fn forward_offer(room: &str, vid: String, sdp: String) -> bool {
+    let mut rooms = ROOMS.write().await;
+    let Some(r) = rooms.get_mut(room) else { return false };
+    let Some(viewer_tx) = r.viewers.get(&vid) else { return false };
+
+    let (sdp, encoding) = sdp_codec::encode(sdp, viewer_tx.supports_gzip());
+    viewer_tx.send_signal(&SignalMessage::Offer { viewer_id: vid.clone(), sdp, encoding });
+    r.acked_viewers.insert(vid.clone());
+
+    let Some(pending) = r.pending_ice.remove(&vid) else { return true };
+    if pending.since.elapsed() > PENDING_ICE_TTL {
+        return true;
+    }
+    if let Some(host_tx) = &r.host_tx {
+        for candidate in pending.candidates {
+            let msg = SignalMessage::IceCandidate { viewer_id: Some(vid.clone()), candidate };
+            host_tx.send_signal(&msg);
+        }
+    }
+    true
+}
+
+async fn relay_viewer_candidate(room: &str, vid: &str, candidate: serde_json::Value) {
+    let mut rooms = ROOMS.write().await;
+    let Some(r) = rooms.get_mut(room) else { return };
+
+    if r.acked_viewers.contains(vid) {
+        if let Some(host_tx) = &r.host_tx {
+            let msg = SignalMessage::IceCandidate { viewer_id: Some(vid.to_string()), candidate };
+            host_tx.send_signal(&msg);
+        }
+        return;
+    }
+
+    let pending = r.pending_ice.entry(vid.to_string()).or_insert_with(|| PendingIce {
+        since: Instant::now(),
+        candidates: VecDeque::new(),
+    });
+    // Hết hạn thì bắt đầu lại, đầy thì bỏ candidate cũ nhất
+    if pending.since.elapsed() > PENDING_ICE_TTL {
+        pending.since = Instant::now();
+        pending.candidates.clear();
+    }
+    if pending.candidates.len() >= MAX_PENDING_ICE {
+        pending.candidates.pop_front();
+    }
+    pending.candidates.push_back(candidate);
+}
+
+async fn relay_viewer_info(room: &str, vid: &str, display: ViewerDisplay) {
+    let mut rooms = ROOMS.write().await;
+    let Some(r) = rooms.get_mut(room) else { return };
+    r.displays.insert(vid.to_string(), display);
+    if let Some(host_tx) = &r.host_tx {
+        let msg = SignalMessage::ViewerInfo { viewer_id: Some(vid.to_string()), display };
+        host_tx.send_signal(&msg);
+    }
+}
+
+async fn record_traffic(room_code: &Option<String>, kind: &str, bytes: usize) {
+    let Some(room) = room_code else { return };
+    if let Some(r) = ROOMS.write().await.get_mut(room) {
+        r.stats.total.add(bytes);
+        r.stats.by_type.entry(kind.to_string()).or_default().add(bytes);
+    }
+}
+
+// Chỉ một viewer giữ quyền tại một thời điểm, cấp cho người mới thì thu hồi người cũ
+async fn grant_control(room: &str, vid: String) {
+    let mut rooms = ROOMS.write().await;
+    let Some(r) = rooms.get_mut(room) else { return };
+    let Some(viewer_tx) = r.viewers.get(&vid) else { return };
+
+    viewer_tx.send_signal(&SignalMessage::GrantControl { viewer_id: vid.clone() });
+    if let Some(previous) = r.controller.replace(vid.clone()) {
+        if previous != vid {
+            if let Some(prev_tx) = r.viewers.get(&previous) {
+                prev_tx.send_signal(&SignalMessage::RevokeControl { viewer_id: Some(previous) });
+            }
+        }
+    }
+}
+
+async fn revoke_control(room: &str, vid: Option<&str>, notify_host: bool) {
+    let mut rooms = ROOMS.write().await;
+    let Some(r) = rooms.get_mut(room) else { return };
+    let Some(current) = r.controller.clone() else { return };
+    // Không chỉ định viewer thì thu hồi người đang giữ
+    if vid.is_some_and(|v| v != current) {
+        return;
+    }
+
+    r.controller = None;
+    let msg = SignalMessage::RevokeControl { viewer_id: Some(current.clone()) };
+    if let Some(viewer_tx) = r.viewers.get(&current) {
+        viewer_tx.send_signal(&msg);
+    }
+    if notify_host {
+        if let Some(host_tx) = &r.host_tx {
+            host_tx.send_signal(&msg);
+        }
+    }
+}
+
+async fn forward_input(room_code: &Option<String>, viewer_id: &Option<String>, event: InputEvent) {
+    let allowed = match (room_code, viewer_id) {
+        (Some(room), Some(vid)) => ROOMS
+            .read()
+            .await
+            .get(room)
+            .map(|r| r.allow_control && r.controller.as_deref() == Some(vid.as_str()))
+            .unwrap_or(false),
+        _ => false,
+    };
+    if allowed {
+        remote_input::inject(event);
+    }
+}
+
+#[tauri::command]
+pub async fn room_stats(room: String) -> Result<RoomStats, AppError> {
+    let rooms = ROOMS.read().await;
+    let r = rooms.get(&room).ok_or(ServerError::RoomNotFound)?;
+    Ok(r.stats.clone())
+}
+
+// Kích thước hiển thị mới nhất của các viewer trong phòng, theo viewerId
+#[tauri::command]
+pub async fn viewer_displays(room: String) -> Result<HashMap<String, ViewerDisplay>, AppError> {
+    let rooms = ROOMS.read().await;
+    let r = rooms.get(&room).ok_or(ServerError::RoomNotFound)?;
+    Ok(r.displays.clone())
 }
 
+// Đóng một phòng bất kể trạng thái kết nối: viewer nhận host-left, host bị đóng với mã 4004.
+// Trả về false nếu không có phòng này
 #[tauri::command]
-pub async fn start_signaling_server(port: u16) -> Result<u16, String> {
+pub async fn close_room(code: String) -> bool {
+    let Some(room) = ROOMS.write().await.remove(&code) else {
+        return false;
+    };
+    for viewer_tx in room.viewers.values() {
+        viewer_tx.send_signal(&SignalMessage::HostLeft);
+    }
+    if let Some(host_tx) = &room.host_tx {
+        host_tx.close(Some(CloseReason::RoomClosed));
+    }
+    true
+}
+
+#[tauri::command]
+pub async fn set_remote_control(room: String, enabled: bool) -> Result<(), AppError> {
+    let mut rooms = ROOMS.write().await;
+    let r = rooms.get_mut(&room).ok_or(ServerError::RoomNotFound)?;
+    r.allow_control = enabled;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn start_signaling_server(
+    app: AppHandle,
+    port: u16,
+    max_messages_per_sec: Option<u32>,
+    queue_capacity: Option<usize>,
+    bind_ip: Option<String>,
+    max_rooms: Option<usize>,
+) -> Result<u16, AppError> {
+    serve_signaling(Some(app), port, max_messages_per_sec, queue_capacity, bind_ip, max_rooms).await
+}
+
+// Không có AppHandle (vd integration test) thì chạy như thường nhưng không phát heartbeat
+pub async fn serve_signaling(
+    app: Option<AppHandle>,
+    port: u16,
+    max_messages_per_sec: Option<u32>,
+    queue_capacity: Option<usize>,
+    bind_ip: Option<String>,
+    max_rooms: Option<usize>,
+) -> Result<u16, AppError> {
     if SIGNALING_RUNNING.load(Ordering::SeqCst) {
-        return Ok(port);
+        return Ok(bound_addr().map(|a| a.port()).unwrap_or(port));
     }
 
-    let listener = TcpListener::bind(format!("0.0.0.0:{}", port))
-        .await
-        .map_err(|e| e.to_string())?;
+    let bind_ip = resolve_bind_ip(bind_ip.as_deref())?;
+    let listener = bind_listener(SocketAddr::new(bind_ip, port)).await?;
+    let addr = listener.local_addr().map_err(ServerError::Bind)?;
+    if let Ok(mut bound) = BOUND_ADDR.lock() {
+        *bound = Some(addr);
+    }
 
     let (shutdown_tx, _) = broadcast::channel::<()>(1);
     {
@@ -220,14 +961,41 @@ pub async fn start_signaling_server(port: u16) -> Result<u16, String> {
 
     SIGNALING_RUNNING.store(true, Ordering::SeqCst);
 
+    let max_messages_per_sec = max_messages_per_sec.unwrap_or(DEFAULT_MAX_MESSAGES_PER_SEC);
+    let queue_capacity = queue_capacity.unwrap_or(DEFAULT_QUEUE_CAPACITY);
+    MAX_ROOMS.store(max_rooms.unwrap_or(0), Ordering::SeqCst);
+
     tokio::spawn(async move {
         let mut shutdown_rx = shutdown_tx.subscribe();
+        let started_at = Instant::now();
+        let mut heartbeat = heartbeat::ticker();
         loop {
             tokio::select! {
                 result = listener.accept() => {
                     if let Ok((stream, _)) = result {
                         let client_shutdown_rx = shutdown_tx.subscribe();
-                        tokio::spawn(handle_connection(stream, client_shutdown_rx));
+                        tokio::spawn(handle_connection(
+                            stream,
+                            client_shutdown_rx,
+                            max_messages_per_sec,
+                            queue_capacity,
+                        ));
+                    }
+                }
+                _ = heartbeat.tick(), if app.is_some() => {
+                    let rooms = ROOMS.read().await;
+                    let event = HeartbeatEvent {
+                        kind: "signaling",
+                        id: "default".to_string(),
+                        uptime_secs: started_at.elapsed().as_secs(),
+                        clients: rooms
+                            .values()
+                            .map(|r| r.viewers.len() + usize::from(r.host_tx.is_some()))
+                            .sum(),
+                        rooms: Some(rooms.len()),
+                    };
+                    if let Some(app) = &app {
+                        heartbeat::emit(app, event);
                     }
                 }
                 _ = shutdown_rx.recv() => break,
@@ -236,16 +1004,19 @@ pub async fn start_signaling_server(port: u16) -> Result<u16, String> {
         SIGNALING_RUNNING.store(false, Ordering::SeqCst);
     });
 
-    Ok(port)
+    Ok(addr.port())
 }
 
 #[tauri::command]
-pub async fn stop_signaling_server() -> Result<(), String> {
+pub async fn stop_signaling_server() -> Result<(), AppError> {
     let mut tx = SHUTDOWN_TX.lock().await;
     if let Some(shutdown_tx) = tx.take() {
         let _ = shutdown_tx.send(());
     }
     SIGNALING_RUNNING.store(false, Ordering::SeqCst);
+    if let Ok(mut bound) = BOUND_ADDR.lock() {
+        *bound = None;
+    }
     
     // Clear rooms
     let mut rooms = ROOMS.write().await;
@@ -253,3 +1024,107 @@ pub async fn stop_signaling_server() -> Result<(), String> {
     
     Ok(())
 }
+
+pub(crate) fn is_signaling_running() -> bool {
+    SIGNALING_RUNNING.load(Ordering::SeqCst)
+}
+
+pub(crate) async fn room_count() -> usize {
+    ROOMS.read().await.len()
+}
+
+pub(crate) async fn signaling_status() -> SignalingStatus {
+    let running = SIGNALING_RUNNING.load(Ordering::SeqCst);
+    let bound_addr = bound_addr().filter(|_| running);
+    SignalingStatus {
+        running,
+        port: bound_addr.map(|a| a.port()),
+        bound_addr: bound_addr.map(|a| a.to_string()),
+        room_count: ROOMS.read().await.len(),
+        max_rooms: Some(MAX_ROOMS.load(Ordering::SeqCst)).filter(|max| *max > 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ice(n: u32) -> SignalMessage {
+        SignalMessage::IceCandidate {
+            viewer_id: Some("v1".to_string()),
+            candidate: serde_json::json!({ "candidate": n }),
+        }
+    }
+
+    fn queued(queue: &OutboundQueue) -> usize {
+        queue.state.lock().unwrap().items.len()
+    }
+
+    #[test]
+    fn rate_limiter_allows_burst_then_drops_then_closes() {
+        let mut limiter = RateLimiter::new(5);
+        for _ in 0..5 {
+            assert!(matches!(limiter.check(), RateDecision::Allow));
+        }
+        // Chỉ lần drop đầu tiên báo cho client
+        assert!(matches!(limiter.check(), RateDecision::Drop { notify: true }));
+        for _ in 0..9 {
+            assert!(matches!(limiter.check(), RateDecision::Drop { notify: false }));
+        }
+        assert!(matches!(limiter.check(), RateDecision::Close));
+    }
+
+    #[test]
+    fn rate_limiter_refills_over_time() {
+        let mut limiter = RateLimiter::new(4);
+        for _ in 0..4 {
+            limiter.check();
+        }
+        assert!(matches!(limiter.check(), RateDecision::Drop { .. }));
+
+        // Nửa giây nạp lại nửa bucket, không vượt capacity
+        limiter.last_refill -= Duration::from_millis(500);
+        assert!(matches!(limiter.check(), RateDecision::Allow));
+        assert!(matches!(limiter.check(), RateDecision::Allow));
+        assert!(matches!(limiter.check(), RateDecision::Drop { notify: true }));
+
+        limiter.last_refill -= Duration::from_secs(60);
+        for _ in 0..4 {
+            assert!(matches!(limiter.check(), RateDecision::Allow));
+        }
+        assert!(matches!(limiter.check(), RateDecision::Drop { .. }));
+    }
+
+    #[test]
+    fn stalled_queue_stays_bounded_by_dropping_ice() {
+        let queue = OutboundQueue::new(4);
+        queue.send_signal(&SignalMessage::HostLeft);
+        // Task gửi không đọc gì: candidate mới đẩy candidate cũ nhất ra
+        for n in 0..1000 {
+            queue.send_signal(&ice(n));
+            assert!(queued(&queue) <= 4);
+        }
+        let state = queue.state.lock().unwrap();
+        assert!(!state.closed);
+        assert!(!state.items[0].droppable);
+        let last = state.items.back().map(|o| o.msg.clone());
+        assert_eq!(last, Some(Message::Text(serde_json::to_string(&ice(999)).unwrap())));
+    }
+
+    #[tokio::test]
+    async fn stalled_queue_full_of_critical_messages_closes() {
+        let queue = OutboundQueue::new(3);
+        for _ in 0..3 {
+            queue.send_signal(&SignalMessage::HostLeft);
+        }
+        // Đầy mà không bỏ được gì thì bỏ cả hàng đợi và đóng connection
+        queue.send_signal(&ice(0));
+        assert_eq!(queued(&queue), 3);
+        queue.send_signal(&SignalMessage::HostLeft);
+        assert_eq!(queued(&queue), 0);
+
+        queue.send_signal(&SignalMessage::HostLeft);
+        assert_eq!(queued(&queue), 0);
+        assert_eq!(queue.pop().await, None);
+    }
+}
@@ -0,0 +1,19 @@
+use serde::Serialize;
+
+use crate::screen_share::{screen_status, ScreenStatus};
+use crate::signaling::{signaling_status, SignalingStatus};
+
+// Trạng thái gộp của cả hai server để UI poll một lần cho status bar
+#[derive(Serialize, Clone)]
+pub struct ServerStatus {
+    screen: ScreenStatus,
+    signaling: SignalingStatus,
+}
+
+#[tauri::command]
+pub async fn get_server_status() -> ServerStatus {
+    ServerStatus {
+        screen: screen_status().await,
+        signaling: signaling_status().await,
+    }
+}
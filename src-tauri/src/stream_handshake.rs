@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+
+use crate::frame_compression;
+use crate::screen_share::{ContentMode, QualityTier};
+
+pub const CODEC_JPEG: &str = "jpeg";
+// Giới hạn một lần đo băng thông, để client không bắt server gửi mãi
+pub const MAX_THROUGHPUT_BYTES: usize = 16 * 1024 * 1024;
+// Scale client xin lệch scale hiện tại quá chừng này thì báo là không áp dụng
+const SCALE_TOLERANCE: f64 = 0.01;
+
+// Message text đầu tiên client gửi sau khi kết nối WebSocket, không bắt buộc: server gửi frame
+// ngay theo cấu hình mặc định, nhận handshake thì trả lời rồi chuyển sang cấu hình đã thoả thuận
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct HandshakeRequest {
+    // Phải là "handshake", không thì message được coi là điều khiển
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub quality: Option<u8>,
+    pub fps: Option<u32>,
+    pub scale: Option<f64>,
+    pub codec: Option<String>,
+    // true = frame JPEG dạng binary, false = base64 text
+    pub binary: bool,
+    // Gửi kèm header {"type":"frame","id","timestamp"} trước mỗi frame để đo độ trễ
+    pub timestamps: bool,
+    // Chỉ gửi các ô thay đổi (message "tiles"), client tự ghép lại thành frame
+    pub tiles: bool,
+    // Codec nén client giải được (vd ["zstd"]), chỉ áp dụng cho message tiles binary
+    pub compression: Vec<String>,
+    // Đo băng thông: server gửi chừng này byte binary ngay sau handshake rồi đóng, không có frame
+    pub throughput_bytes: Option<usize>,
+    // Định danh tuỳ ý của viewer (vd viewerId bên signaling), host dùng để giới hạn riêng
+    pub client_token: Option<String>,
+}
+
+// Message điều khiển client gửi sau handshake, vd {"request_keyframe": true} khi mất frame
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct ClientControl {
+    pub request_keyframe: bool,
+    // Echo id trong header frame, server tính RTT từ lúc gửi
+    pub ack: Option<u64>,
+    // Đổi preset text/balanced/video của luồng capture, cần allow_content_mode trong ClientCap
+    pub content_mode: Option<ContentMode>,
+}
+
+// Message text client gửi, đã phân loại
+#[derive(Debug)]
+pub enum ClientMessage {
+    Handshake(HandshakeRequest),
+    Control(ClientControl),
+}
+
+impl ClientMessage {
+    // Chỉ message text đầu tiên (first) có "type":"handshake" mới là handshake. Client bỏ qua
+    // handshake mà gửi ngay {"request_keyframe": true} hay {"ack": N} thì vẫn là điều khiển
+    pub fn parse(text: &str, first: bool) -> Option<Self> {
+        if first {
+            let request = serde_json::from_str::<HandshakeRequest>(text).ok();
+            if let Some(request) = request.filter(|r| r.kind.as_deref() == Some("handshake")) {
+                return Some(ClientMessage::Handshake(request));
+            }
+        }
+        serde_json::from_str(text).ok().map(ClientMessage::Control)
+    }
+}
+
+// Header gửi trước frame khi client bật timestamps
+#[derive(Serialize, Clone, Debug)]
+pub struct FrameHeader {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub id: u64,
+    // Unix millis lúc capture
+    pub timestamp: u64,
+}
+
+// Kích thước frame thực sự gửi đi (sau resize) và scale factor của màn hình nguồn
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct OutputResolution {
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f32,
+}
+
+// Kích thước trung bình các frame JPEG gần đây của một tier, để client chọn quality/fps vừa
+// băng thông đo được
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct TierFrameSize {
+    pub tier: QualityTier,
+    pub avg_bytes: usize,
+}
+
+// Cấu hình server thực sự áp dụng, gửi lại trước frame đầu tiên
+#[derive(Serialize, Clone, Debug)]
+pub struct HandshakeResponse {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub quality: u8,
+    pub fps: u32,
+    pub scale: f64,
+    pub codec: &'static str,
+    pub binary: bool,
+    pub timestamps: bool,
+    pub tiles: bool,
+    // Codec đã chọn, null = không nén
+    pub compression: Option<&'static str>,
+    // Theo frame gần nhất, null khi chưa capture được frame nào. Host đổi cấu hình thì kích
+    // thước đổi theo, kích thước trong từng JPEG/message tiles mới là chính xác
+    pub output: Option<OutputResolution>,
+    // Số byte server sẽ gửi (đã chặn theo MAX_THROUGHPUT_BYTES), null = không phải lần đo
+    pub throughput_bytes: Option<usize>,
+    // Field client xin mà server không áp dụng được ("scale", "codec", "compression").
+    // quality được làm tròn về tier, fps bị chặn, giá trị thật nằm ở field tương ứng
+    pub ignored: Vec<&'static str>,
+    // Chỉ các tier server vừa encode, rỗng khi chưa có viewer nào
+    pub frame_sizes: Vec<TierFrameSize>,
+}
+
+// Giới hạn của server. Quality và scale thuộc về luồng capture dùng chung
+// nên client chỉ nhận lại giá trị hiện tại; fps thì mỗi client tự bớt frame.
+#[derive(Clone, Debug)]
+pub struct ServerLimits {
+    pub quality: u8,
+    pub scale: f64,
+    pub max_fps: u32,
+    pub output: Option<OutputResolution>,
+    pub frame_sizes: Vec<TierFrameSize>,
+}
+
+impl HandshakeResponse {
+    pub fn negotiate(request: &HandshakeRequest, limits: ServerLimits) -> Self {
+        let max_fps = limits.max_fps.max(1);
+        let compression = (request.binary && request.tiles)
+            .then(|| frame_compression::negotiate(&request.compression))
+            .flatten();
+        let mut ignored = Vec::new();
+        if request.scale.is_some_and(|scale| (scale - limits.scale).abs() > SCALE_TOLERANCE) {
+            ignored.push("scale");
+        }
+        if request.codec.as_deref().is_some_and(|codec| codec != CODEC_JPEG) {
+            ignored.push("codec");
+        }
+        if !request.compression.is_empty() && compression.is_none() {
+            ignored.push("compression");
+        }
+        Self {
+            kind: "handshake",
+            quality: limits.quality,
+            fps: request.fps.unwrap_or(max_fps).clamp(1, max_fps),
+            scale: limits.scale,
+            codec: CODEC_JPEG,
+            binary: request.binary,
+            timestamps: request.timestamps,
+            tiles: request.tiles,
+            compression,
+            output: limits.output,
+            throughput_bytes: request.throughput_bytes.map(|b| b.min(MAX_THROUGHPUT_BYTES)),
+            ignored,
+            frame_sizes: limits.frame_sizes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: ServerLimits = ServerLimits {
+        quality: 70,
+        scale: 0.5,
+        max_fps: 20,
+        output: None,
+        frame_sizes: Vec::new(),
+    };
+
+    fn request(json: &str) -> HandshakeRequest {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn first_message_is_a_handshake_only_with_its_type() {
+        let text = r#"{"type": "handshake", "binary": true}"#;
+        match ClientMessage::parse(text, true) {
+            Some(ClientMessage::Handshake(request)) => assert!(request.binary),
+            other => panic!("{:?}", other),
+        }
+        // Handshake chỉ nhận ở message đầu
+        assert!(matches!(ClientMessage::parse(text, false), Some(ClientMessage::Control(_))));
+    }
+
+    #[test]
+    fn first_control_message_is_still_applied() {
+        match ClientMessage::parse(r#"{"request_keyframe": true}"#, true) {
+            Some(ClientMessage::Control(control)) => assert!(control.request_keyframe),
+            other => panic!("{:?}", other),
+        }
+        match ClientMessage::parse(r#"{"ack": 7}"#, true) {
+            Some(ClientMessage::Control(control)) => assert_eq!(control.ack, Some(7)),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn empty_request_gets_server_defaults() {
+        let accepted = HandshakeResponse::negotiate(&request("{}"), LIMITS);
+        assert_eq!((accepted.quality, accepted.fps, accepted.scale), (70, 20, 0.5));
+        assert_eq!(accepted.codec, CODEC_JPEG);
+        assert!(!accepted.binary && !accepted.tiles && !accepted.timestamps);
+        assert_eq!(accepted.compression, None);
+        assert_eq!(accepted.throughput_bytes, None);
+        assert!(accepted.ignored.is_empty());
+    }
+
+    #[test]
+    fn fps_is_clamped_to_server_limit() {
+        let fps = |json| HandshakeResponse::negotiate(&request(json), LIMITS).fps;
+        assert_eq!(fps(r#"{"fps": 10}"#), 10);
+        assert_eq!(fps(r#"{"fps": 60}"#), 20);
+        assert_eq!(fps(r#"{"fps": 0}"#), 1);
+    }
+
+    #[test]
+    fn compression_needs_binary_tiles_and_a_known_codec() {
+        let negotiate = |json| HandshakeResponse::negotiate(&request(json), LIMITS);
+        let accepted = negotiate(r#"{"binary": true, "tiles": true, "compression": ["zstd"]}"#);
+        assert_eq!(accepted.compression, Some(frame_compression::ZSTD));
+        assert!(accepted.ignored.is_empty());
+
+        let accepted = negotiate(r#"{"tiles": true, "compression": ["zstd"]}"#);
+        assert_eq!(accepted.compression, None);
+        assert_eq!(accepted.ignored, ["compression"]);
+
+        let accepted = negotiate(r#"{"binary": true, "tiles": true, "compression": ["brotli"]}"#);
+        assert_eq!(accepted.compression, None);
+        assert_eq!(accepted.ignored, ["compression"]);
+    }
+
+    #[test]
+    fn unsupported_scale_and_codec_are_reported() {
+        let negotiate = |json| HandshakeResponse::negotiate(&request(json), LIMITS);
+        let accepted = negotiate(r#"{"scale": 1.0, "codec": "h264"}"#);
+        assert_eq!((accepted.scale, accepted.codec), (0.5, CODEC_JPEG));
+        assert_eq!(accepted.ignored, ["scale", "codec"]);
+
+        assert!(negotiate(r#"{"scale": 0.5, "codec": "jpeg"}"#).ignored.is_empty());
+    }
+
+    #[test]
+    fn throughput_is_capped() {
+        let json = format!(r#"{{"throughput_bytes": {}}}"#, MAX_THROUGHPUT_BYTES * 4);
+        let accepted = HandshakeResponse::negotiate(&request(&json), LIMITS);
+        assert_eq!(accepted.throughput_bytes, Some(MAX_THROUGHPUT_BYTES));
+    }
+}
@@ -0,0 +1,198 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::RgbaImage;
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::error::ServerError;
+use crate::screen_share::{encode_jpeg, ChromaSubsampling};
+
+// Cạnh mỗi ô theo pixel của frame đã resize, ô ở mép phải/dưới có thể nhỏ hơn
+pub const TILE_SIZE: u32 = 128;
+// Gửi lại đủ lưới định kỳ để client vẽ lệch (mất message, lỗi decode) tự hồi phục
+const RESYNC_INTERVAL: Duration = Duration::from_secs(10);
+// Đầu message binary: "TILE", u32 big-endian độ dài header JSON, header, rồi JPEG các ô
+// nối liền theo thứ tự trong header
+const TILE_MAGIC: &[u8; 4] = b"TILE";
+
+struct Tile {
+    hash: u64,
+    jpeg: Arc<Vec<u8>>,
+}
+
+// Frame chia thành lưới ô, mỗi ô là một JPEG riêng. Ô xếp theo hàng, trái sang phải
+pub struct TileGrid {
+    width: u32,
+    height: u32,
+    columns: u32,
+    quality: u8,
+    chroma: ChromaSubsampling,
+    tiles: Vec<Tile>,
+}
+
+impl TileGrid {
+    // Ô giống hệt lưới trước (cùng kích thước frame, quality và chroma) dùng lại JPEG cũ,
+    // nên màn hình ít thay đổi thì gần như không phải encode
+    pub fn encode(
+        img: &RgbaImage,
+        quality: u8,
+        chroma: ChromaSubsampling,
+        previous: Option<&TileGrid>,
+    ) -> Result<Self, ServerError> {
+        let (width, height) = img.dimensions();
+        let columns = width.div_ceil(TILE_SIZE);
+        let rows = height.div_ceil(TILE_SIZE);
+        let settings = (width, height, quality, chroma);
+        let previous = previous.filter(|p| (p.width, p.height, p.quality, p.chroma) == settings);
+
+        let mut tiles = Vec::with_capacity((columns * rows) as usize);
+        for row in 0..rows {
+            for column in 0..columns {
+                let (x, y) = (column * TILE_SIZE, row * TILE_SIZE);
+                let (w, h) = (TILE_SIZE.min(width - x), TILE_SIZE.min(height - y));
+                let hash = tile_hash(img, x, y, w, h);
+                let reused = previous
+                    .map(|p| &p.tiles[tiles.len()])
+                    .filter(|tile| tile.hash == hash);
+                let jpeg = match reused {
+                    Some(tile) => Arc::clone(&tile.jpeg),
+                    None => {
+                        let view = image::imageops::crop_imm(img, x, y, w, h).to_image();
+                        let mut jpeg = Vec::new();
+                        encode_jpeg(&mut jpeg, &view, quality, chroma)?;
+                        Arc::new(jpeg)
+                    }
+                };
+                tiles.push(Tile { hash, jpeg });
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            columns,
+            quality,
+            chroma,
+            tiles,
+        })
+    }
+}
+
+fn tile_hash(img: &RgbaImage, x: u32, y: u32, w: u32, h: u32) -> u64 {
+    let stride = img.width() as usize * 4;
+    let raw = img.as_raw();
+    let mut hasher = DefaultHasher::new();
+    for row in y..y + h {
+        let start = row as usize * stride + x as usize * 4;
+        hasher.write(&raw[start..start + w as usize * 4]);
+    }
+    hasher.finish()
+}
+
+#[derive(Serialize)]
+struct TileMessage {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    id: u64,
+    timestamp: u64,
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    // true = đủ mọi ô, client có thể vẽ lại từ đầu
+    keyframe: bool,
+    tiles: Vec<TileEntry>,
+}
+
+#[derive(Serialize)]
+struct TileEntry {
+    col: u32,
+    row: u32,
+    // Binary: số byte JPEG của ô trong phần thân message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    len: Option<usize>,
+    // Text: JPEG base64
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<String>,
+}
+
+// Hash các ô client đã nhận, mỗi client một cái vì client bớt frame theo fps riêng
+#[derive(Default)]
+pub struct TileTracker {
+    size: (u32, u32),
+    sent: Vec<u64>,
+    last_resync: Option<Instant>,
+}
+
+impl TileTracker {
+    // Message chỉ chứa các ô khác với lần gửi trước, None khi không ô nào đổi.
+    // Trả kèm số byte của message để tính băng thông
+    pub fn message(
+        &mut self,
+        id: u64,
+        timestamp: u64,
+        grid: &TileGrid,
+        keyframe: bool,
+        binary: bool,
+    ) -> Option<(Message, usize)> {
+        let keyframe = keyframe
+            || self.size != (grid.width, grid.height)
+            || self.sent.len() != grid.tiles.len()
+            || self.last_resync.is_none_or(|at| at.elapsed() >= RESYNC_INTERVAL);
+        let changed: Vec<usize> = (0..grid.tiles.len())
+            .filter(|&i| keyframe || self.sent[i] != grid.tiles[i].hash)
+            .collect();
+        if changed.is_empty() {
+            return None;
+        }
+
+        if keyframe {
+            self.size = (grid.width, grid.height);
+            self.sent = grid.tiles.iter().map(|tile| tile.hash).collect();
+            self.last_resync = Some(Instant::now());
+        } else {
+            for &i in &changed {
+                self.sent[i] = grid.tiles[i].hash;
+            }
+        }
+
+        let entries = changed.iter().map(|&i| {
+            let jpeg = &grid.tiles[i].jpeg;
+            TileEntry {
+                col: i as u32 % grid.columns,
+                row: i as u32 / grid.columns,
+                len: binary.then_some(jpeg.len()),
+                data: (!binary).then(|| STANDARD.encode(jpeg.as_slice())),
+            }
+        });
+        let header = TileMessage {
+            kind: "tiles",
+            id,
+            timestamp,
+            width: grid.width,
+            height: grid.height,
+            tile_size: TILE_SIZE,
+            keyframe,
+            tiles: entries.collect(),
+        };
+        if !binary {
+            let text = serde_json::to_string(&header).ok()?;
+            let len = text.len();
+            return Some((Message::Text(text), len));
+        }
+        let header = serde_json::to_vec(&header).ok()?;
+
+        let body: usize = changed.iter().map(|&i| grid.tiles[i].jpeg.len()).sum();
+        let mut message = Vec::with_capacity(TILE_MAGIC.len() + 4 + header.len() + body);
+        message.extend_from_slice(TILE_MAGIC);
+        message.extend_from_slice(&(header.len() as u32).to_be_bytes());
+        message.extend_from_slice(&header);
+        for &i in &changed {
+            message.extend_from_slice(&grid.tiles[i].jpeg);
+        }
+        let len = message.len();
+        Some((Message::Binary(message), len))
+    }
+}
@@ -0,0 +1,99 @@
+use std::io::BufReader;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+use crate::error::ServerError;
+
+// Đọc cert chain và private key dạng PEM để phục vụ wss://
+pub fn load_acceptor(cert_path: &str, key_path: &str) -> Result<TlsAcceptor, ServerError> {
+    let tls_error = |e: std::io::Error| ServerError::Tls(e.to_string());
+
+    let cert_file = std::fs::File::open(cert_path).map_err(tls_error)?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(cert_file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(tls_error)?;
+    if certs.is_empty() {
+        return Err(ServerError::Tls(format!("No certificate in {}", cert_path)));
+    }
+
+    let key_file = std::fs::File::open(key_path).map_err(tls_error)?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(key_file))
+        .map_err(tls_error)?
+        .ok_or_else(|| ServerError::Tls(format!("No private key in {}", key_path)))?;
+
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| ServerError::Tls(e.to_string()))?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+// Kết nối của viewer, plaintext (ws://) hoặc đã bọc TLS (wss://)
+pub enum ClientStream {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl ClientStream {
+    // Handshake TLS quá hạn hoặc lỗi thì bỏ kết nối
+    pub async fn accept(
+        stream: TcpStream,
+        tls: Option<&TlsAcceptor>,
+        wait: Duration,
+    ) -> Option<Self> {
+        match tls {
+            None => Some(ClientStream::Plain(stream)),
+            Some(acceptor) => match tokio::time::timeout(wait, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => Some(ClientStream::Tls(Box::new(stream))),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl AsyncRead for ClientStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            ClientStream::Plain(s) => Pin::new(s).poll_read(cx, buf),
+            ClientStream::Tls(s) => Pin::new(s.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ClientStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        match self.get_mut() {
+            ClientStream::Plain(s) => Pin::new(s).poll_write(cx, buf),
+            ClientStream::Tls(s) => Pin::new(s.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            ClientStream::Plain(s) => Pin::new(s).poll_flush(cx),
+            ClientStream::Tls(s) => Pin::new(s.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            ClientStream::Plain(s) => Pin::new(s).poll_shutdown(cx),
+            ClientStream::Tls(s) => Pin::new(s.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
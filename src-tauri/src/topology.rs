@@ -0,0 +1,117 @@
+use serde::Serialize;
+use std::net::Ipv4Addr;
+
+use crate::{HostInfo, ScanResult};
+
+// Port gợi ý vai trò của host, một host có thể có nhiều vai trò
+const ROLE_PORTS: &[(&str, &[u16])] = &[
+    ("printer", &[9100, 631, 515]),
+    ("remote_desktop", &[3389, 5900]),
+    ("file_share", &[445, 139]),
+    ("ssh", &[22]),
+    ("web", &[80, 443, 8080]),
+    ("database", &[3306, 5432]),
+];
+
+#[derive(Serialize, Clone)]
+pub struct Topology {
+    // Unix millis của lần quét làm nguồn
+    scanned_at: u64,
+    subnet: Option<SubnetInfo>,
+    // Id của gateway, không thấy gateway thì là máy này. Mọi edge đi ra từ gốc
+    root: Option<String>,
+    nodes: Vec<TopologyNode>,
+    edges: Vec<TopologyEdge>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SubnetInfo {
+    // Subnet /24 mà scan_network quét
+    cidr: String,
+    local_ip: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct TopologyNode {
+    // Chính là IP của host
+    id: String,
+    // "gateway", "self", "printer", ... hoặc "host" khi không nhận ra gì
+    roles: Vec<&'static str>,
+    host: HostInfo,
+}
+
+#[derive(Serialize, Clone)]
+pub struct TopologyEdge {
+    from: String,
+    to: String,
+}
+
+// Mạng LAN phẳng: mọi host nối vào gateway. Kết quả quét đã bỏ chính máy này
+// (exclude_self) thì thêm lại một node từ local_ip
+pub fn build(result: &ScanResult, local_ip: Option<Ipv4Addr>) -> Topology {
+    let mut hosts = result.hosts.clone();
+    if let Some(ip) = local_ip.filter(|_| !hosts.iter().any(|h| h.is_self)) {
+        let mut local = HostInfo::new(ip.to_string(), None, "local");
+        local.is_self = true;
+        hosts.push(local);
+    }
+
+    let nodes: Vec<TopologyNode> = hosts
+        .into_iter()
+        .map(|host| TopologyNode {
+            id: host.ip.clone(),
+            roles: roles(&host),
+            host,
+        })
+        .collect();
+    let root = nodes
+        .iter()
+        .find(|n| n.host.is_gateway)
+        .or_else(|| nodes.iter().find(|n| n.host.is_self))
+        .map(|n| n.id.clone());
+    let edges = match &root {
+        Some(root) => nodes
+            .iter()
+            .filter(|n| &n.id != root)
+            .map(|n| TopologyEdge {
+                from: root.clone(),
+                to: n.id.clone(),
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    let subnet = local_ip.map(|ip| {
+        let octets = ip.octets();
+        SubnetInfo {
+            cidr: format!("{}.{}.{}.0/24", octets[0], octets[1], octets[2]),
+            local_ip: ip.to_string(),
+        }
+    });
+
+    Topology {
+        scanned_at: result.scanned_at,
+        subnet,
+        root,
+        nodes,
+        edges,
+    }
+}
+
+fn roles(host: &HostInfo) -> Vec<&'static str> {
+    let mut roles = Vec::new();
+    if host.is_gateway {
+        roles.push("gateway");
+    }
+    if host.is_self {
+        roles.push("self");
+    }
+    for (role, ports) in ROLE_PORTS {
+        if ports.iter().any(|p| host.ports.contains(p)) {
+            roles.push(*role);
+        }
+    }
+    if roles.is_empty() {
+        roles.push("host");
+    }
+    roles
+}
@@ -0,0 +1,64 @@
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+// SNMPv1 get-request community "public" cho sysDescr.0
+const SNMP_GET_SYSDESCR: &[u8] = &[
+    0x30, 0x26, 0x02, 0x01, 0x00, 0x04, 0x06, b'p', b'u', b'b', b'l', b'i', b'c', 0xa0, 0x19,
+    0x02, 0x01, 0x01, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00, 0x30, 0x0e, 0x30, 0x0c, 0x06, 0x08,
+    0x2b, 0x06, 0x01, 0x02, 0x01, 0x01, 0x01, 0x00, 0x05, 0x00,
+];
+
+// DNS query PTR _services._dns-sd._udp.local, bật bit unicast response
+const MDNS_SERVICES_QUERY: &[u8] = &[
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x09, b'_', b's',
+    b'e', b'r', b'v', b'i', b'c', b'e', b's', 0x07, b'_', b'd', b'n', b's', b'-', b's', b'd',
+    0x04, b'_', b'u', b'd', b'p', 0x05, b'l', b'o', b'c', b'a', b'l', 0x00, 0x00, 0x0c, 0x80,
+    0x01,
+];
+
+// DNS query NS cho root zone
+const DNS_ROOT_QUERY: &[u8] = &[
+    0x13, 0x37, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02,
+    0x00, 0x01,
+];
+
+const SSDP_SEARCH: &[u8] =
+    b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\nST: ssdp:all\r\n\r\n";
+
+fn ntp_request() -> Vec<u8> {
+    // LI = 0, version 3, mode 3 (client)
+    let mut packet = vec![0u8; 48];
+    packet[0] = 0x1b;
+    packet
+}
+
+// Payload phù hợp với từng giao thức, port lạ thì gửi một dòng trống
+fn payload_for(port: u16) -> Vec<u8> {
+    match port {
+        53 => DNS_ROOT_QUERY.to_vec(),
+        123 => ntp_request(),
+        161 => SNMP_GET_SYSDESCR.to_vec(),
+        1900 => SSDP_SEARCH.to_vec(),
+        5353 => MDNS_SERVICES_QUERY.to_vec(),
+        _ => b"\r\n".to_vec(),
+    }
+}
+
+// UDP không có kết nối: chỉ coi là "có mặt" khi nhận được phản hồi,
+// không phản hồi thì không kết luận được gì
+pub async fn probe(ip: &str, port: u16, wait: Duration) -> bool {
+    let socket = match UdpSocket::bind("0.0.0.0:0").await {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+    if socket.connect((ip, port)).await.is_err() {
+        return false;
+    }
+    if socket.send(&payload_for(port)).await.is_err() {
+        return false;
+    }
+
+    let mut buf = [0u8; 1500];
+    matches!(timeout(wait, socket.recv(&mut buf)).await, Ok(Ok(n)) if n > 0)
+}
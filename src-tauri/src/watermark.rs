@@ -0,0 +1,172 @@
+use image::{Rgba, RgbaImage};
+use serde::Deserialize;
+
+// Mỗi điểm của font 5x7 vẽ thành ô DOT x DOT pixel
+const DOT: u32 = 2;
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+// Khoảng cách giữa chữ và lề nền, tính theo điểm font
+const SPACING: u32 = 1;
+const PADDING: u32 = 2;
+// Cách mép frame
+const MARGIN: u32 = 8;
+const MAX_CHARS: usize = 64;
+
+const TEXT_COLOR: Rgba<u8> = Rgba([255, 255, 255, 220]);
+const BACKGROUND: Rgba<u8> = Rgba([0, 0, 0, 120]);
+
+// Font chỉ có chữ hoa không dấu, chữ có dấu vẽ bằng chữ gốc
+const FOLD: &[(&str, char)] = &[
+    ("àáảãạăằắẳẵặâầấẩẫậ", 'A'),
+    ("èéẻẽẹêềếểễệ", 'E'),
+    ("ìíỉĩị", 'I'),
+    ("òóỏõọôồốổỗộơờớởỡợ", 'O'),
+    ("ùúủũụưừứửữự", 'U'),
+    ("ỳýỷỹỵ", 'Y'),
+    ("đ", 'D'),
+];
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+// Chữ được vẽ sẵn một lần, mỗi frame chỉ còn blend ảnh nhỏ này vào góc
+pub struct Watermark {
+    text: String,
+    corner: Corner,
+    overlay: RgbaImage,
+}
+
+impl Watermark {
+    pub fn new(text: &str, corner: Corner) -> Self {
+        let glyphs: Vec<[u8; 7]> = text.chars().take(MAX_CHARS).map(glyph).collect();
+        let text_columns = (glyphs.len() as u32 * (GLYPH_WIDTH + SPACING)).saturating_sub(SPACING);
+        let columns = text_columns + PADDING * 2;
+        let rows = GLYPH_HEIGHT + PADDING * 2;
+        let mut overlay = RgbaImage::from_pixel(columns * DOT, rows * DOT, BACKGROUND);
+
+        for (i, rows_bits) in glyphs.iter().enumerate() {
+            let left = PADDING + i as u32 * (GLYPH_WIDTH + SPACING);
+            for (y, bits) in rows_bits.iter().enumerate() {
+                for x in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - x)) == 0 {
+                        continue;
+                    }
+                    let (px, py) = ((left + x) * DOT, (PADDING + y as u32) * DOT);
+                    for dy in 0..DOT {
+                        for dx in 0..DOT {
+                            overlay.put_pixel(px + dx, py + dy, TEXT_COLOR);
+                        }
+                    }
+                }
+            }
+        }
+
+        Self {
+            text: text.to_string(),
+            corner,
+            overlay,
+        }
+    }
+
+    pub fn matches(&self, text: &str, corner: Corner) -> bool {
+        self.text == text && self.corner == corner
+    }
+
+    // Frame nhỏ hơn overlay thì phần thừa bị cắt
+    pub fn apply(&self, frame: &mut RgbaImage) {
+        let (width, height) = (self.overlay.width(), self.overlay.height());
+        let right = frame.width().saturating_sub(width + MARGIN);
+        let bottom = frame.height().saturating_sub(height + MARGIN);
+        let (left, top) = match self.corner {
+            Corner::TopLeft => (MARGIN, MARGIN),
+            Corner::TopRight => (right, MARGIN),
+            Corner::BottomLeft => (MARGIN, bottom),
+            Corner::BottomRight => (right, bottom),
+        };
+
+        for (x, y, pixel) in self.overlay.enumerate_pixels() {
+            let (fx, fy) = (left + x, top + y);
+            if fx >= frame.width() || fy >= frame.height() {
+                continue;
+            }
+            blend(frame.get_pixel_mut(fx, fy), pixel);
+        }
+    }
+}
+
+fn blend(dst: &mut Rgba<u8>, src: &Rgba<u8>) {
+    let alpha = src[3] as u32;
+    for c in 0..3 {
+        dst[c] = ((src[c] as u32 * alpha + dst[c] as u32 * (255 - alpha)) / 255) as u8;
+    }
+}
+
+fn fold(c: char) -> char {
+    let lower = c.to_lowercase().next().unwrap_or(c);
+    FOLD.iter()
+        .find(|(letters, _)| letters.contains(lower))
+        .map(|(_, base)| *base)
+        .unwrap_or_else(|| c.to_ascii_uppercase())
+}
+
+// Mỗi hàng 5 bit, bit cao là cột trái. Ký tự không có trong font vẽ thành '?'
+fn glyph(c: char) -> [u8; 7] {
+    match fold(c) {
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        ' ' => [0x00; 7],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        '@' => [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E],
+        '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
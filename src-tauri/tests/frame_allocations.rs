@@ -0,0 +1,79 @@
+// Binary riêng vì allocator đếm thay cho allocator của cả binary test
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::codecs::jpeg::JpegEncoder;
+use image::RgbaImage;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use share_screen_lib::FramePipeline;
+
+// Chỉ đếm byte, không dùng thread-local để không gọi lại allocator trong lúc cấp phát.
+// File này chỉ có một test nên không thread nào khác cấp phát xen vào
+struct CountingAlloc;
+
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAlloc = CountingAlloc;
+
+// Số byte cấp phát trong lúc chạy f
+fn allocated(f: impl FnOnce()) -> usize {
+    let start = ALLOCATED_BYTES.load(Ordering::SeqCst);
+    f();
+    ALLOCATED_BYTES.load(Ordering::SeqCst) - start
+}
+
+fn gradient(width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| {
+        image::Rgba([(x % 256) as u8, (y % 256) as u8, ((x ^ y) % 256) as u8, 255])
+    })
+}
+
+#[test]
+fn steady_state_frame_reuses_resize_and_encode_buffers() {
+    const QUALITY: u8 = 70;
+    let (w, h) = (960, 540);
+    let frame_bytes = w as usize * h as usize * 4;
+    let source = gradient(1920, 1080);
+
+    // Trước: mỗi frame một ảnh resize, một Vec JPEG và một String base64 mới
+    let input = source.clone();
+    let before = allocated(|| {
+        let filter = image::imageops::FilterType::Triangle;
+        let resized = image::imageops::resize(&input, w, h, filter);
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, QUALITY).encode_image(&resized).unwrap();
+        let _ = STANDARD.encode(&jpeg);
+    });
+
+    // Sau: vòng capture giữ ảnh đích và lấy buffer từ pool, frame bị drop thì trả lại.
+    // Hai frame đầu làm ấm pool
+    let pipeline = FramePipeline::new();
+    let mut after = 0;
+    for _ in 0..3 {
+        let input = source.clone();
+        after = allocated(|| {
+            assert!(pipeline.process(input, w, h, QUALITY).is_some());
+        });
+    }
+    // Resizer vẫn tính lại bảng hệ số mỗi lần (nhiều cấp phát nhỏ), phần đáng kể là các
+    // buffer cỡ frame không còn cấp phát lại
+    assert!(before > frame_bytes, "{}", before);
+    assert!(after < frame_bytes / 10, "{}", after);
+}
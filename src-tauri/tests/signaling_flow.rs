@@ -0,0 +1,311 @@
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::{Mutex, MutexGuard};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use share_screen_lib::signaling::{close_room, room_stats, serve_signaling, stop_signaling_server};
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+const RECV_TIMEOUT: Duration = Duration::from_secs(2);
+// Đủ lâu để chắc server không chuyển message nào tới client này
+const SILENCE: Duration = Duration::from_millis(200);
+
+// Server signaling là global: mỗi test giữ khoá này suốt lúc chạy và mở một server mới
+static SERVER_LOCK: Mutex<()> = Mutex::const_new(());
+
+struct Server {
+    port: u16,
+    _lock: MutexGuard<'static, ()>,
+}
+
+// Dừng server test trước để lại (kể cả khi test đó panic, phòng cũng bị xoá) rồi mở lại
+async fn start_server(max_rooms: Option<usize>) -> Server {
+    let lock = SERVER_LOCK.lock().await;
+    stop_signaling_server().await.expect("stop signaling");
+    let bind_ip = Some("127.0.0.1".to_string());
+    let port = serve_signaling(None, 0, None, None, bind_ip, max_rooms)
+        .await
+        .expect("start signaling");
+    Server { port, _lock: lock }
+}
+
+async fn connect(port: u16) -> Client {
+    let url = format!("ws://127.0.0.1:{}", port);
+    connect_async(url).await.expect("connect").0
+}
+
+async fn send(client: &mut Client, value: Value) {
+    client.send(Message::Text(value.to_string())).await.expect("send");
+}
+
+async fn next_message(client: &mut Client, wait: Duration) -> Option<Value> {
+    loop {
+        match tokio::time::timeout(wait, client.next()).await.ok()?? {
+            Ok(Message::Text(text)) => return Some(serde_json::from_str(&text).expect("json")),
+            Ok(Message::Close(_)) | Err(_) => return None,
+            Ok(_) => continue,
+        }
+    }
+}
+
+async fn recv(client: &mut Client, kind: &str) -> Value {
+    let msg = next_message(client, RECV_TIMEOUT).await.expect("message");
+    assert_eq!(msg["type"], kind, "unexpected message: {}", msg);
+    msg
+}
+
+async fn assert_silent(client: &mut Client) {
+    if let Some(msg) = next_message(client, SILENCE).await {
+        panic!("unexpected message: {}", msg);
+    }
+}
+
+// Host mở phòng và nhận room-info xác nhận
+async fn host_room(port: u16, room: &str) -> Client {
+    let mut host = connect(port).await;
+    send(&mut host, json!({ "type": "host", "room": room })).await;
+    let info = recv(&mut host, "room-info").await;
+    assert_eq!(info["room"], room);
+    host
+}
+
+// Viewer nhận room-info, host nhận viewer-joined rồi room-info với số viewer mới.
+// Cả hai nhận viewer-count sau cùng, viewer cũ trong phòng cần tự nhận bằng expect_count
+async fn join_viewer(
+    port: u16,
+    host: &mut Client,
+    room: &str,
+    viewer_count: usize,
+) -> (Client, String) {
+    let mut viewer = connect(port).await;
+    send(&mut viewer, json!({ "type": "viewer", "room": room })).await;
+    let info = recv(&mut viewer, "room-info").await;
+    assert_eq!(info["viewerCount"], viewer_count);
+    expect_count(&mut viewer, viewer_count).await;
+    let joined = recv(host, "viewer-joined").await;
+    let vid = joined["viewerId"].as_str().expect("viewerId").to_string();
+    let info = recv(host, "room-info").await;
+    assert_eq!(info["viewerCount"], viewer_count);
+    expect_count(host, viewer_count).await;
+    (viewer, vid)
+}
+
+async fn expect_count(client: &mut Client, count: usize) {
+    let msg = recv(client, "viewer-count").await;
+    assert_eq!(msg["count"], count);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn host_gets_room_info_with_metadata() {
+    let server = start_server(None).await;
+    let mut host = connect(server.port).await;
+    let msg = json!({ "type": "host", "room": "meta", "title": "Demo", "hostName": "Alice" });
+    send(&mut host, msg).await;
+    // room-info là xác nhận phòng đã được tạo
+    let info = recv(&mut host, "room-info").await;
+    assert_eq!(info["room"], "meta");
+    assert_eq!(info["title"], "Demo");
+    assert_eq!(info["hostName"], "Alice");
+    assert_eq!(info["viewerCount"], 0);
+    assert!(room_stats("meta".to_string()).await.is_ok());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn max_rooms_rejects_extra_host_until_a_room_closes() {
+    let server = start_server(Some(1)).await;
+    let mut host = host_room(server.port, "cap-1").await;
+
+    // Đã đủ phòng: host mới bị từ chối, phòng không được tạo
+    let mut extra_host = connect(server.port).await;
+    send(&mut extra_host, json!({ "type": "host", "room": "cap-extra" })).await;
+    let error = recv(&mut extra_host, "error").await;
+    assert_eq!(error["message"], "server at capacity");
+    assert_eq!(error["code"], 4003);
+    assert!(room_stats("cap-extra".to_string()).await.is_err());
+    extra_host.close(None).await.expect("close extra host");
+
+    // Phòng cũ bị xoá khi host rời (viewer nhận host-left) nên lại tạo được phòng mới
+    let (mut viewer, _) = join_viewer(server.port, &mut host, "cap-1", 1).await;
+    host.close(None).await.expect("close host");
+    recv(&mut viewer, "host-left").await;
+    host_room(server.port, "cap-2").await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn viewer_count_follows_joins_and_leaves() {
+    let server = start_server(None).await;
+    let mut host = host_room(server.port, "count").await;
+    let (mut viewer1, vid1) = join_viewer(server.port, &mut host, "count", 1).await;
+    let (mut viewer2, vid2) = join_viewer(server.port, &mut host, "count", 2).await;
+    assert_ne!(vid1, vid2);
+    expect_count(&mut viewer1, 2).await;
+
+    viewer1.close(None).await.expect("close viewer1");
+    let left = recv(&mut host, "viewer-left").await;
+    assert_eq!(left["viewerId"], vid1.as_str());
+    let info = recv(&mut host, "room-info").await;
+    assert_eq!(info["viewerCount"], 1);
+    expect_count(&mut host, 1).await;
+    expect_count(&mut viewer2, 1).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn offer_and_answer_reach_only_the_addressed_peer() {
+    let server = start_server(None).await;
+    let mut host = host_room(server.port, "offer").await;
+    let (mut viewer1, _) = join_viewer(server.port, &mut host, "offer", 1).await;
+    let (mut viewer2, vid2) = join_viewer(server.port, &mut host, "offer", 2).await;
+    expect_count(&mut viewer1, 2).await;
+
+    send(&mut host, json!({ "type": "offer", "viewerId": vid2, "sdp": "offer-2" })).await;
+    let offer = recv(&mut viewer2, "offer").await;
+    assert_eq!(offer["sdp"], "offer-2");
+    assert_eq!(offer["viewerId"], vid2.as_str());
+    assert_silent(&mut viewer1).await;
+
+    // Answer quay về host, được server gắn viewerId của người gửi
+    send(&mut viewer2, json!({ "type": "answer", "viewerId": null, "sdp": "answer-2" })).await;
+    let answer = recv(&mut host, "answer").await;
+    assert_eq!(answer["sdp"], "answer-2");
+    assert_eq!(answer["viewerId"], vid2.as_str());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn renegotiation_follows_the_same_route() {
+    let server = start_server(None).await;
+    let mut host = host_room(server.port, "renegotiate").await;
+    let (mut viewer, vid) = join_viewer(server.port, &mut host, "renegotiate", 1).await;
+
+    for round in ["a", "b"] {
+        let sdp = format!("offer-{}", round);
+        send(&mut host, json!({ "type": "offer", "viewerId": vid, "sdp": sdp })).await;
+        assert_eq!(recv(&mut viewer, "offer").await["sdp"], sdp.as_str());
+        let sdp = format!("answer-{}", round);
+        send(&mut viewer, json!({ "type": "answer", "viewerId": null, "sdp": sdp })).await;
+        let answer = recv(&mut host, "answer").await;
+        assert_eq!(answer["sdp"], sdp.as_str());
+        assert_eq!(answer["viewerId"], vid.as_str());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invalid_message_is_reported_to_the_sender_only() {
+    let server = start_server(None).await;
+    let mut host = host_room(server.port, "invalid").await;
+    let (mut viewer, vid) = join_viewer(server.port, &mut host, "invalid", 1).await;
+
+    // Offer thiếu sdp
+    send(&mut host, json!({ "type": "offer", "viewerId": vid })).await;
+    let error = recv(&mut host, "error").await;
+    let detail = error["message"].as_str().expect("message");
+    assert!(detail.starts_with("Invalid message:"), "{}", detail);
+    assert!(detail.contains("sdp"), "{}", detail);
+    assert_silent(&mut viewer).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn offer_to_unknown_viewer_is_reported_to_the_host() {
+    let server = start_server(None).await;
+    let mut host = host_room(server.port, "unknown").await;
+    let (mut viewer, _) = join_viewer(server.port, &mut host, "unknown", 1).await;
+
+    send(&mut host, json!({ "type": "offer", "viewerId": "missing", "sdp": "offer-x" })).await;
+    let error = recv(&mut host, "error").await;
+    assert_eq!(error["message"], "Viewer not found");
+    assert_silent(&mut viewer).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ice_candidates_route_both_ways() {
+    let server = start_server(None).await;
+    let mut host = host_room(server.port, "ice").await;
+    let (mut viewer1, _) = join_viewer(server.port, &mut host, "ice", 1).await;
+    let (mut viewer2, vid2) = join_viewer(server.port, &mut host, "ice", 2).await;
+    expect_count(&mut viewer1, 2).await;
+    send(&mut host, json!({ "type": "offer", "viewerId": vid2, "sdp": "offer-2" })).await;
+    recv(&mut viewer2, "offer").await;
+
+    let host_candidate = json!({ "candidate": "candidate:host", "sdpMid": "0" });
+    let msg = json!({ "type": "ice-candidate", "viewerId": vid2, "candidate": host_candidate });
+    send(&mut host, msg).await;
+    let ice = recv(&mut viewer2, "ice-candidate").await;
+    assert_eq!(ice["candidate"], host_candidate);
+    assert_silent(&mut viewer1).await;
+
+    // Viewer đã có offer: chuyển thẳng cho host, gắn viewerId
+    let viewer_candidate = json!({ "candidate": "candidate:viewer2", "sdpMid": "0" });
+    let msg = json!({ "type": "ice-candidate", "viewerId": null, "candidate": viewer_candidate });
+    send(&mut viewer2, msg).await;
+    let ice = recv(&mut host, "ice-candidate").await;
+    assert_eq!(ice["viewerId"], vid2.as_str());
+    assert_eq!(ice["candidate"], viewer_candidate);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn early_viewer_candidate_is_flushed_after_the_offer() {
+    let server = start_server(None).await;
+    let mut host = host_room(server.port, "early").await;
+    let (mut viewer, vid) = join_viewer(server.port, &mut host, "early", 1).await;
+
+    // Candidate của viewer chưa có offer được giữ lại, xả cho host ngay sau offer
+    let early_candidate = json!({ "candidate": "candidate:viewer", "sdpMid": "0" });
+    let msg = json!({ "type": "ice-candidate", "viewerId": null, "candidate": early_candidate });
+    send(&mut viewer, msg).await;
+    assert_silent(&mut host).await;
+    send(&mut host, json!({ "type": "offer", "viewerId": vid, "sdp": "offer-1" })).await;
+    assert_eq!(recv(&mut viewer, "offer").await["sdp"], "offer-1");
+    let ice = recv(&mut host, "ice-candidate").await;
+    assert_eq!(ice["viewerId"], vid.as_str());
+    assert_eq!(ice["candidate"], early_candidate);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn host_leaving_notifies_viewers() {
+    let server = start_server(None).await;
+    let mut host = host_room(server.port, "leave").await;
+    let (mut viewer, _) = join_viewer(server.port, &mut host, "leave", 1).await;
+
+    host.close(None).await.expect("close host");
+    recv(&mut viewer, "host-left").await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn close_room_notifies_viewers_and_closes_the_host() {
+    let server = start_server(None).await;
+    let mut host = host_room(server.port, "closed").await;
+    let (mut viewer, _) = join_viewer(server.port, &mut host, "closed", 1).await;
+
+    // Viewer nhận host-left, host bị đóng với mã room-closed
+    assert!(close_room("closed".to_string()).await);
+    recv(&mut viewer, "host-left").await;
+    let close = loop {
+        match tokio::time::timeout(RECV_TIMEOUT, host.next()).await {
+            Ok(Some(Ok(Message::Close(frame)))) => break frame,
+            Ok(Some(Ok(_))) => continue,
+            other => panic!("expected close frame, got {:?}", other),
+        }
+    };
+    assert_eq!(u16::from(close.expect("close frame").code), 4004);
+    assert!(!close_room("closed".to_string()).await);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn evicted_viewer_leaving_does_not_touch_a_recreated_room() {
+    let server = start_server(None).await;
+    let mut host = host_room(server.port, "reused").await;
+    let (mut viewer, _) = join_viewer(server.port, &mut host, "reused", 1).await;
+    assert!(close_room("reused".to_string()).await);
+    recv(&mut viewer, "host-left").await;
+
+    // Host mới tạo lại đúng mã phòng trước khi viewer cũ ngắt kết nối
+    let mut new_host = host_room(server.port, "reused").await;
+    viewer.close(None).await.expect("close viewer");
+    assert_silent(&mut new_host).await;
+    drop(host);
+    assert_silent(&mut new_host).await;
+    assert!(room_stats("reused".to_string()).await.is_ok());
+}
@@ -6,10 +6,25 @@ interface HostInfo {
   ip: string;
   hostname: string | null;
   source: string;
+  mac: string | null;
+}
+
+interface ScanResult {
+  hosts: HostInfo[];
+  took_ms: number;
+  counts: { mdns: number; arp: number; tcp: number; ping: number; udp: number };
 }
 
 type Mode = "home" | "server" | "client" | "webrtc-host" | "webrtc-view" | "scanner";
 
+// Lỗi từ command trả về dạng { code, message, retriable }
+function errorMessage(e: unknown): string {
+  if (e && typeof e === "object" && "message" in e) {
+    return String((e as { message: unknown }).message);
+  }
+  return String(e);
+}
+
 function App() {
   const [mode, setMode] = useState<Mode>("home");
 
@@ -164,7 +179,7 @@ function WebRTCHostScreen({ setMode }: { setMode: (m: Mode) => void }) {
       };
 
     } catch (e) {
-      setError(String(e));
+      setError(errorMessage(e));
     }
   }
 
@@ -378,11 +393,14 @@ function ServerScreen({ setMode }: { setMode: (m: Mode) => void }) {
   async function startServer() {
     try {
       setError("");
-      const address = await invoke<string>("start_screen_server", { port: 9000 });
+      const { address } = await invoke<{ id: string; address: string }>(
+        "start_screen_server",
+        { port: 9000 },
+      );
       setServerAddress(address);
       setIsRunning(true);
     } catch (e) {
-      setError(String(e));
+      setError(errorMessage(e));
     }
   }
 
@@ -392,7 +410,7 @@ function ServerScreen({ setMode }: { setMode: (m: Mode) => void }) {
       setIsRunning(false);
       setServerAddress("");
     } catch (e) {
-      setError(String(e));
+      setError(errorMessage(e));
     }
   }
 
@@ -437,8 +455,13 @@ function ClientScreen({ setMode }: { setMode: (m: Mode) => void }) {
     setError("");
     const ws = new WebSocket(`ws://${serverIp}`);
 
-    ws.onopen = () => setConnected(true);
+    ws.onopen = () => {
+      setConnected(true);
+      ws.send(JSON.stringify({ codec: "jpeg", binary: false }));
+    };
     ws.onmessage = (event) => {
+      // Message đầu tiên là cấu hình server chấp nhận, không phải frame
+      if (typeof event.data === "string" && event.data.startsWith("{")) return;
       if (imgRef.current) {
         imgRef.current.src = `data:image/jpeg;base64,${event.data}`;
       }
@@ -507,10 +530,10 @@ function ScannerScreen({ setMode }: { setMode: (m: Mode) => void }) {
     try {
       const ip = await invoke<string>("get_local_ip");
       setLocalIp(ip);
-      const result = await invoke<HostInfo[]>("scan_network");
-      setHosts(result);
+      const result = await invoke<ScanResult>("scan_network");
+      setHosts(result.hosts);
     } catch (e) {
-      setError(String(e));
+      setError(errorMessage(e));
     } finally {
       setScanning(false);
     }